@@ -0,0 +1,82 @@
+extern crate rltk;
+use rltk::{ RGB, Rltk, Console, Point };
+
+extern crate specs;
+use specs::prelude::*;
+
+use super::{ Map, Position, Renderable, themes::tile_glyph };
+
+/// The part of the screen the map is drawn in; the log panel sits below it
+const VIEW_WIDTH : i32 = 80;
+const VIEW_HEIGHT : i32 = 43;
+
+/// Draw a faint pattern where the view runs off the edge of the map
+const SHOW_BOUNDARIES : bool = true;
+
+/// The map coordinates of the view's top left corner when it is centred
+/// on the given point
+fn view_origin(center: Point) -> Point {
+    Point::new(center.x - VIEW_WIDTH / 2, center.y - VIEW_HEIGHT / 2)
+}
+
+/// The map coordinates of the view's top left corner, which follows the
+/// player
+pub fn camera_origin(ecs: &World) -> Point {
+    view_origin(*ecs.fetch::<Point>())
+}
+
+/// Converts a point on the screen (such as the mouse) to map coordinates
+pub fn screen_to_map(ecs: &World, screen: (i32, i32)) -> Point {
+    let origin = camera_origin(ecs);
+    Point::new(screen.0 + origin.x, screen.1 + origin.y)
+}
+
+/// Converts map coordinates to a point on the screen
+pub fn map_to_screen(ecs: &World, pos: Point) -> Point {
+    let origin = camera_origin(ecs);
+    Point::new(pos.x - origin.x, pos.y - origin.y)
+}
+
+/// Draws the part of the map around the player, and everything visible on it
+pub fn render_camera(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let origin = camera_origin(ecs);
+    draw_view(&map, ctx, origin);
+
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+    data.sort_by_key(|(_pos, render)| std::cmp::Reverse(render.render_order));
+    for (pos, render) in data.iter() {
+        let (x, y) = (pos.x - origin.x, pos.y - origin.y);
+        if !(0 .. VIEW_WIDTH).contains(&x) || !(0 .. VIEW_HEIGHT).contains(&y) { continue; }
+
+        let idx = map.xy_idx(pos.x, pos.y);
+        if map.visible[idx] {
+            ctx.set(x, y, render.fg, render.bg, render.glyph);
+        }
+    }
+}
+
+/// Draws a map on its own, centred on the middle of the map, for the
+/// map generation visualizer
+pub fn render_debug_map(map: &Map, ctx: &mut Rltk) {
+    draw_view(map, ctx, view_origin(Point::new(map.width / 2, map.height / 2)));
+}
+
+fn draw_view(map: &Map, ctx: &mut Rltk, origin: Point) {
+    for y in 0 .. VIEW_HEIGHT {
+        for x in 0 .. VIEW_WIDTH {
+            let (map_x, map_y) = (x + origin.x, y + origin.y);
+            if map_x >= 0 && map_x < map.width && map_y >= 0 && map_y < map.height {
+                let idx = map.xy_idx(map_x, map_y);
+                if map.revealed[idx] {
+                    let (glyph, fg, bg) = tile_glyph(idx, map);
+                    ctx.set(x, y, fg, bg, glyph);
+                }
+            } else if SHOW_BOUNDARIES {
+                ctx.set(x, y, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), rltk::to_cp437('·'));
+            }
+        }
+    }
+}
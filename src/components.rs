@@ -11,6 +11,8 @@ extern crate specs_derive;
 extern crate serde;
 use serde::{ Serialize, Deserialize };
 
+use std::collections::HashMap;
+
 
 #[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Player {}
@@ -32,8 +34,62 @@ pub struct Monster {}
 pub struct CombatStats {
     pub max_hp: i32,
     pub hp: i32,
-    pub defense: i32,
-    pub power: i32,
+}
+
+/// One of an actor's four attributes. `modifiers` is the total of any
+/// temporary changes on top of the rolled `base`.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct Attribute {
+    pub base: i32,
+    pub modifiers: i32,
+}
+
+impl Attribute {
+    pub fn new(base: i32) -> Attribute {
+        Attribute { base, modifiers: 0 }
+    }
+
+    pub fn value(&self) -> i32 {
+        self.base + self.modifiers
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Attributes {
+    pub might: Attribute,
+    pub fitness: Attribute,
+    pub quickness: Attribute,
+    pub intelligence: Attribute,
+}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum AttributeName { Might, Fitness, Quickness, Intelligence }
+
+impl Attributes {
+    pub fn get_mut(&mut self, name: AttributeName) -> &mut Attribute {
+        match name {
+            AttributeName::Might => &mut self.might,
+            AttributeName::Fitness => &mut self.fitness,
+            AttributeName::Quickness => &mut self.quickness,
+            AttributeName::Intelligence => &mut self.intelligence,
+        }
+    }
+}
+
+/// Monsters only use `level`, to decide how much experience they are
+/// worth; the player's `xp` counts up across every level.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Skill { Melee, Defense, Magic, Ranged }
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Skills {
+    pub skills: HashMap<Skill, i32>,
 }
 
 #[derive(Component, Debug, Clone, ConvertSaveload)]
@@ -42,14 +98,17 @@ pub struct WantsToMelee {
 }
 
 #[derive(Component, Debug, Clone, ConvertSaveload)]
-pub struct SufferDamage {
-    pub amount: i32
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
 }
 
+/// Replaces `Position` on entities left behind on another level
 #[derive(Component, Debug, Clone, ConvertSaveload)]
-pub struct Position {
+pub struct OtherLevelPosition {
     pub x: i32,
     pub y: i32,
+    pub depth: i32,
 }
 
 #[derive(Component, Debug, Clone, ConvertSaveload)]
@@ -69,8 +128,12 @@ pub struct ProvidesFood {}
 #[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct MagicMapper {}
 
+/// Moves whoever uses it to the targeted tile
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct TeleportsUser {}
+
 #[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
-pub enum EquipmentSlot { Melee, Shield }
+pub enum EquipmentSlot { Melee, Shield, Ranged, Ammo }
 
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Equippable {
@@ -83,14 +146,64 @@ pub struct Equipped {
     pub slot: EquipmentSlot
 }
 
-#[derive(Component, ConvertSaveload, Clone)]
-pub struct MeleePowerBonus {
-    pub power: i32,
+/// The attribute a weapon adds to its to-hit and damage rolls
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum WeaponAttribute { Might, Quickness }
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct MeleeWeapon {
+    pub attribute: WeaponAttribute,
+    pub damage_n_dice: i32,
+    pub damage_die_type: i32,
+    pub damage_bonus: i32,
+    pub hit_bonus: i32,
 }
 
-#[derive(Component, ConvertSaveload, Clone)]
-pub struct DefenseBonus {
-    pub defense: i32,
+/// What a ranged weapon shoots
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum AmmoKind { Arrow, Bolt, Stone }
+
+/// A bow, crossbow or sling. It shoots whatever matching ammunition its
+/// owner has in the ammo slot.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct RangedWeapon {
+    pub range: i32,
+    pub ammo: AmmoKind,
+    pub attribute: WeaponAttribute,
+    pub damage_n_dice: i32,
+    pub damage_die_type: i32,
+    pub damage_bonus: i32,
+    pub hit_bonus: i32,
+}
+
+/// A bundle of arrows, bolts or stones
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Ammunition {
+    pub kind: AmmoKind,
+    pub count: i32,
+}
+
+/// Equipment that adds to its owner's armour class
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Wearable {
+    pub armor_class: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NaturalAttack {
+    pub name: String,
+    pub damage_n_dice: i32,
+    pub damage_die_type: i32,
+    pub damage_bonus: i32,
+    pub hit_bonus: i32,
+}
+
+/// Claws, hide and the like for creatures that don't use equipment.
+/// `armor_class` replaces the base of 10 when set.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct NaturalAttackDefense {
+    pub armor_class: Option<i32>,
+    pub attacks: Vec<NaturalAttack>,
 }
 
 #[derive(Component, Debug, Clone, ConvertSaveload)]
@@ -108,9 +221,41 @@ pub struct InflictsDamage {
     pub damage: i32,
 }
 
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum StatusKind { Confusion, Poison, Regeneration, Slow, Haste, Paralysis, Blindness }
+
+impl StatusKind {
+    /// How the status is described on whoever has it
+    pub fn adjective(self) -> &'static str {
+        match self {
+            StatusKind::Confusion => "confused",
+            StatusKind::Poison => "poisoned",
+            StatusKind::Regeneration => "regenerating",
+            StatusKind::Slow => "slowed",
+            StatusKind::Haste => "hasted",
+            StatusKind::Paralysis => "paralysed",
+            StatusKind::Blindness => "blind",
+        }
+    }
+}
+
+/// A status effect on `target`. Each one is an entity of its own, so an
+/// actor can have any number of them. `magnitude` is the damage or
+/// healing per turn for poison and regeneration.
 #[derive(Component, Debug, Clone, ConvertSaveload)]
-pub struct Confusion {
+pub struct StatusEffect {
+    pub target: Entity,
+    pub kind: StatusKind,
+    pub turns: i32,
+    pub magnitude: i32,
+}
+
+/// Consumables that put a status effect on whoever they are used on
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct InflictsStatus {
+    pub kind: StatusKind,
     pub turns: i32,
+    pub magnitude: i32,
 }
 
 #[derive(Component, Debug, Clone, ConvertSaveload)]
@@ -134,6 +279,11 @@ pub struct ProvidesHealing {
     pub heal_amount: i32
 }
 
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToShoot {
+    pub target: rltk::Point,
+}
+
 #[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToUseItem {
     pub item: Entity,
@@ -158,6 +308,16 @@ pub struct Viewshed {
 #[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct BlocksTile {}
 
+/// Stops light, so nothing can be seen through the tile
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct BlocksVisibility {}
+
+/// A door; while closed it blocks both movement and sight
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Door {
+    pub open: bool,
+}
+
 #[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct ParticleLifetime {
     pub lifetime_ms: f32,
@@ -167,5 +327,14 @@ pub struct SerializeMe;
 
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
-    pub map: super::map::Map
+    /// Deflated MessagePack of the `Map`; see `saveload_system::compress_map`
+    #[serde(with = "crate::saveload_system::packed_bytes")]
+    pub map: Vec<u8>,
+    /// The other levels visited, compressed the same way
+    #[serde(default, with = "crate::saveload_system::packed_bytes_list")]
+    pub levels: Vec<Vec<u8>>,
+    pub seed: u64,
+    /// The generator as it stood when the game was saved
+    pub rng: crate::RandomNumberGenerator,
+    pub turns: i32,
 }
@@ -3,43 +3,10 @@ use specs::prelude::*;
 
 use super::{ 
     CombatStats,
-    SufferDamage,
     Player,
     Name, 
     GameLog,
-    RunState,
-    Map,
-    Position, };
-
-pub struct DamageSystem {}
-
-impl<'a> System<'a> for DamageSystem {
-    type SystemData = ( WriteStorage<'a, CombatStats>,
-                        WriteStorage<'a, SufferDamage>,
-                        ReadStorage<'a, Position>,
-                        WriteExpect<'a, Map>,
-                        Entities<'a> );
-
-    fn run(&mut self, data : Self::SystemData) {
-        let (mut stats, 
-             mut damage,
-             positions,
-             mut map,
-             entities) = data;
-
-        for (entity, mut stats, damage) in (&entities, &mut stats, &damage).join() {
-            stats.hp -= damage.amount;
-
-            let pos = positions.get(entity);
-            if let Some(pos) = pos {
-                let idx = Map::xy_idx(pos.x, pos.y);
-                map.bloodstains.insert(idx);
-            }
-        }
-
-        damage.clear();
-    }
-}
+    RunState, };
 
 pub fn delete_the_dead(ecs: &mut World) {
     let mut dead : Vec<Entity> = Vec::new();
@@ -0,0 +1,104 @@
+use specs::prelude::*;
+use std::collections::BTreeMap;
+
+use super::{
+    Map,
+    TileType,
+    Position,
+    OtherLevelPosition,
+    Player,
+    Viewshed,
+    ParticleLifetime };
+
+/// Every level the player has left, by depth. The level being played
+/// lives in the `Map` resource and is only stored here once the player
+/// leaves it.
+#[derive(Default)]
+pub struct MasterDungeonMap {
+    maps: BTreeMap<i32, Map>,
+}
+
+impl MasterDungeonMap {
+    pub fn new() -> MasterDungeonMap {
+        MasterDungeonMap { maps: BTreeMap::new() }
+    }
+
+    pub fn store_map(&mut self, map: &Map) {
+        let mut stored = map.clone();
+        stored.contents = Vec::new();
+        self.maps.insert(map.depth, stored);
+    }
+
+    pub fn get_map(&self, depth: i32) -> Option<Map> {
+        self.maps.get(&depth).cloned()
+    }
+
+    /// The stored levels in order of depth
+    pub fn levels(&self) -> impl Iterator<Item = &Map> {
+        self.maps.values()
+    }
+}
+
+/// Takes everything on the current level except the player off the map,
+/// remembering where it was. Particles are simply dropped.
+pub fn freeze_level_entities(ecs: &mut World) {
+    let depth = ecs.fetch::<Map>().depth;
+    let mut to_freeze : Vec<(Entity, Position)> = Vec::new();
+    let mut to_delete : Vec<Entity> = Vec::new();
+    {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let players = ecs.read_storage::<Player>();
+        let particles = ecs.read_storage::<ParticleLifetime>();
+        for (entity, pos, _p) in (&entities, &positions, !&players).join() {
+            if particles.get(entity).is_some() {
+                to_delete.push(entity);
+            } else {
+                to_freeze.push((entity, pos.clone()));
+            }
+        }
+    }
+
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    for (entity, pos) in to_freeze.iter() {
+        other_level_positions.insert(*entity, OtherLevelPosition { x: pos.x, y: pos.y, depth })
+            .expect("Insert fail");
+        positions.remove(*entity);
+    }
+    drop(positions);
+    drop(other_level_positions);
+
+    for entity in to_delete.iter() {
+        ecs.delete_entity(*entity).expect("Unable to delete entity");
+    }
+}
+
+/// Puts back everything that was left on the level at `depth`
+pub fn thaw_level_entities(ecs: &mut World, depth: i32) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+
+    let mut thawed : Vec<Entity> = Vec::new();
+    for (entity, pos) in (&entities, &other_level_positions).join() {
+        if pos.depth == depth {
+            positions.insert(entity, Position { x: pos.x, y: pos.y }).expect("Insert fail");
+            thawed.push(entity);
+        }
+    }
+
+    for entity in thawed.iter() {
+        other_level_positions.remove(*entity);
+        if let Some(vs) = viewsheds.get_mut(*entity) { vs.dirty = true; }
+    }
+}
+
+/// Finds the first tile of the given type, used to place the player on
+/// the stairs they arrived by.
+pub fn find_tile(map: &Map, tile: TileType) -> Option<(i32, i32)> {
+    map.tiles.iter()
+        .position(|t| *t == tile)
+        .map(|idx| (idx as i32 % map.width, idx as i32 / map.width))
+}
@@ -0,0 +1,20 @@
+extern crate specs;
+use specs::prelude::*;
+
+use super::Map;
+use super::super::{ Ammunition, raws::{ RAWS, SpawnType, spawn_named_item } };
+
+/// Leaves a single arrow, bolt or stone of the named kind on the tile,
+/// for whoever comes along to pick it up
+pub fn drop_ammo(ecs: &mut World, tile_idx: usize, name: &str) {
+    let (x, y) = {
+        let map = ecs.fetch::<Map>();
+        (tile_idx as i32 % map.width, tile_idx as i32 / map.width)
+    };
+    let dropped = spawn_named_item(&RAWS.lock().unwrap(), ecs, name, SpawnType::AtPosition { x, y });
+    if let Some(dropped) = dropped {
+        if let Some(ammo) = ecs.write_storage::<Ammunition>().get_mut(dropped) {
+            ammo.count = 1;
+        }
+    }
+}
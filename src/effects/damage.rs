@@ -0,0 +1,41 @@
+extern crate specs;
+use specs::prelude::*;
+
+use super::{ Map, entity_position };
+use super::super::{ CombatStats, Experience, gamelog::GameLog, gamesystem::xp_for_kill };
+
+/// Takes hit points off the target, leaving blood on the floor. The
+/// player earns experience for anything they kill.
+pub fn inflict_damage(ecs: &mut World, creator: Option<Entity>, target: Entity, amount: i32) {
+    let killed;
+    {
+        let mut combat_stats = ecs.write_storage::<CombatStats>();
+        let stats = match combat_stats.get_mut(target) {
+            Some(stats) => stats,
+            None => return
+        };
+        let was_alive = stats.hp > 0;
+        stats.hp -= amount;
+        killed = was_alive && stats.hp < 1;
+    }
+
+    if let Some(idx) = entity_position(ecs, target) {
+        ecs.write_resource::<Map>().bloodstains.insert(idx);
+    }
+
+    let player_entity = *ecs.fetch::<Entity>();
+    if killed && creator == Some(player_entity) {
+        let mut experience = ecs.write_storage::<Experience>();
+        let gain = xp_for_kill(experience.get(target).map_or(1, |e| e.level));
+        if let Some(player_xp) = experience.get_mut(player_entity) {
+            player_xp.xp += gain;
+            ecs.write_resource::<GameLog>().entries.insert(0, format!("You gain {} experience", gain));
+        }
+    }
+}
+
+pub fn heal_damage(ecs: &mut World, target: Entity, amount: i32) {
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(target) {
+        stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+    }
+}
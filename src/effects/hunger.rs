@@ -0,0 +1,11 @@
+extern crate specs;
+use specs::prelude::*;
+
+use super::super::{ HungerClock, HungerState };
+
+pub fn well_fed(ecs: &mut World, target: Entity) {
+    if let Some(hc) = ecs.write_storage::<HungerClock>().get_mut(target) {
+        hc.state = HungerState::WellFed;
+        hc.duration = 20;
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+extern crate specs;
+use specs::prelude::*;
+
+extern crate rltk;
+use rltk::RGB;
+
+use super::{ Map, StatusKind };
+
+mod targeting;
+pub use targeting::{ entity_position, entities_in, tiles_in };
+
+mod damage;
+mod status;
+mod hunger;
+mod movement;
+mod particles;
+mod ammo;
+mod triggers;
+
+/// Something that happens to whatever a `Targets` picks out
+#[derive(Clone)]
+pub enum EffectType {
+    Damage { amount: i32 },
+    Healing { amount: i32 },
+    Status { kind: StatusKind, turns: i32, magnitude: i32 },
+    Particle { glyph: u8, fg: RGB, bg: RGB, lifetime: f32 },
+    WellFed,
+    RevealMap,
+    TeleportTo { x: i32, y: i32 },
+    /// A spent arrow, bolt or stone, named for the item it came from
+    DropAmmo { name: String },
+    /// Turns into the effects of whatever the item does
+    ItemUse { item: Entity },
+}
+
+impl EffectType {
+    /// Whether the effect lands on the entities in the targeted tiles,
+    /// rather than the tiles themselves
+    fn affects_entities(&self) -> bool {
+        !matches!(self, EffectType::Particle { .. } | EffectType::DropAmmo { .. } | EffectType::RevealMap | EffectType::ItemUse { .. })
+    }
+}
+
+/// What an effect lands on
+#[derive(Clone)]
+pub enum Targets {
+    Single { target: Entity },
+    Tile { tile_idx: usize },
+    Tiles { tiles: Vec<usize> },
+    /// Every tile within `radius` of the centre that it can see
+    Area { x: i32, y: i32, radius: i32 },
+}
+
+pub struct EffectSpawner {
+    /// Who caused the effect, if anyone; the player gets the credit for kills
+    pub creator: Option<Entity>,
+    pub effect_type: EffectType,
+    pub targets: Targets,
+}
+
+/// Effects waiting to be applied. Anything can queue one; they all land
+/// when `run_effects_queue` next runs.
+pub struct EffectQueue {
+    effects: VecDeque<EffectSpawner>,
+}
+
+impl EffectQueue {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> EffectQueue {
+        EffectQueue { effects: VecDeque::new() }
+    }
+
+    pub fn add(&mut self, creator: Option<Entity>, effect_type: EffectType, targets: Targets) {
+        self.effects.push_back(EffectSpawner { creator, effect_type, targets });
+    }
+}
+
+/// Applies every queued effect, including any queued while doing so
+pub fn run_effects_queue(ecs: &mut World) {
+    loop {
+        let effect = ecs.write_resource::<EffectQueue>().effects.pop_front();
+        match effect {
+            Some(effect) => target_applicator(ecs, &effect),
+            None => break
+        }
+    }
+}
+
+fn target_applicator(ecs: &mut World, effect: &EffectSpawner) {
+    match &effect.effect_type {
+        EffectType::ItemUse { item } => {
+            triggers::item_trigger(ecs, effect.creator, *item, &effect.targets);
+            return;
+        }
+        EffectType::RevealMap => {
+            for r in ecs.write_resource::<Map>().revealed.iter_mut() {
+                *r = true;
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    match &effect.targets {
+        Targets::Single { target } => affect_entity(ecs, effect, *target),
+        Targets::Tile { tile_idx } => affect_tile(ecs, effect, *tile_idx),
+        Targets::Tiles { .. } | Targets::Area { .. } => {
+            for tile_idx in tiles_in(ecs, &effect.targets) {
+                affect_tile(ecs, effect, tile_idx);
+            }
+        }
+    }
+}
+
+fn affect_tile(ecs: &mut World, effect: &EffectSpawner, tile_idx: usize) {
+    if effect.effect_type.affects_entities() {
+        let content = ecs.fetch::<Map>().contents[tile_idx].clone();
+        for entity in content.iter() {
+            affect_entity(ecs, effect, *entity);
+        }
+    }
+
+    match &effect.effect_type {
+        EffectType::Particle { .. } => particles::particle_to_tile(ecs, tile_idx, &effect.effect_type),
+        EffectType::DropAmmo { name } => ammo::drop_ammo(ecs, tile_idx, name),
+        _ => {}
+    }
+}
+
+fn affect_entity(ecs: &mut World, effect: &EffectSpawner, target: Entity) {
+    match &effect.effect_type {
+        EffectType::Damage { amount } => damage::inflict_damage(ecs, effect.creator, target, *amount),
+        EffectType::Healing { amount } => damage::heal_damage(ecs, target, *amount),
+        EffectType::Status { kind, turns, magnitude } => status::apply_status(ecs, target, *kind, *turns, *magnitude),
+        EffectType::WellFed => hunger::well_fed(ecs, target),
+        EffectType::TeleportTo { x, y } => movement::apply_teleport(ecs, target, *x, *y),
+        EffectType::Particle { .. } => {
+            if let Some(tile_idx) = entity_position(ecs, target) {
+                particles::particle_to_tile(ecs, tile_idx, &effect.effect_type);
+            }
+        }
+        EffectType::DropAmmo { name } => {
+            if let Some(tile_idx) = entity_position(ecs, target) {
+                ammo::drop_ammo(ecs, tile_idx, name);
+            }
+        }
+        EffectType::RevealMap | EffectType::ItemUse { .. } => {}
+    }
+}
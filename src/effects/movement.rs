@@ -0,0 +1,36 @@
+extern crate specs;
+use specs::prelude::*;
+
+extern crate rltk;
+use rltk::Point;
+
+use super::Map;
+use super::super::{ Position, Viewshed, gamelog::GameLog };
+
+/// Moves the target straight to the tile, if there's room for it there
+pub fn apply_teleport(ecs: &mut World, target: Entity, x: i32, y: i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    {
+        let map = ecs.fetch::<Map>();
+        let idx = map.xy_idx(x, y);
+        if map.blocked[idx] {
+            if target == player_entity {
+                ecs.write_resource::<GameLog>().entries.insert(0, "Something blocks the way".to_string());
+            }
+            return;
+        }
+    }
+
+    if let Some(pos) = ecs.write_storage::<Position>().get_mut(target) {
+        pos.x = x;
+        pos.y = y;
+    }
+    if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) {
+        viewshed.dirty = true;
+    }
+    if target == player_entity {
+        let mut ppos = ecs.write_resource::<Point>();
+        ppos.x = x;
+        ppos.y = y;
+    }
+}
@@ -0,0 +1,14 @@
+extern crate specs;
+use specs::prelude::*;
+
+use super::{ EffectType, Map };
+use super::super::particle_system::ParticleBuilder;
+
+pub fn particle_to_tile(ecs: &mut World, tile_idx: usize, effect: &EffectType) {
+    if let EffectType::Particle { glyph, fg, bg, lifetime } = effect {
+        let map = ecs.fetch::<Map>();
+        let x = tile_idx as i32 % map.width;
+        let y = tile_idx as i32 / map.width;
+        ecs.write_resource::<ParticleBuilder>().requests(x, y, *fg, *bg, *glyph, *lifetime);
+    }
+}
@@ -0,0 +1,65 @@
+extern crate specs;
+use specs::prelude::*;
+use specs::saveload::{ MarkerAllocator, SimpleMarker, SimpleMarkerAllocator };
+
+use super::super::{ StatusEffect, StatusKind, Viewshed, SerializeMe, gamelog::GameLog };
+
+/// Haste and slow cancel each other out
+fn opposite(kind: StatusKind) -> Option<StatusKind> {
+    match kind {
+        StatusKind::Haste => Some(StatusKind::Slow),
+        StatusKind::Slow => Some(StatusKind::Haste),
+        _ => None
+    }
+}
+
+/// Puts the target under the effect. A second dose of poison adds to
+/// the damage; anything else already on the target just lasts as long
+/// as the longer of the two.
+pub fn apply_status(ecs: &mut World, target: Entity, kind: StatusKind, turns: i32, magnitude: i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let mut statuses = ecs.write_storage::<StatusEffect>();
+    let mut log = ecs.write_resource::<GameLog>();
+
+    let mut cancelled : Option<Entity> = None;
+    let mut existing : Option<Entity> = None;
+    for (entity, status) in (&entities, &statuses).join() {
+        if status.target != target { continue; }
+        if Some(status.kind) == opposite(kind) { cancelled = Some(entity); }
+        if status.kind == kind { existing = Some(entity); }
+    }
+
+    if let Some(cancelled) = cancelled {
+        statuses.remove(cancelled);
+        entities.delete(cancelled).expect("Unable to delete status");
+        if target == player_entity {
+            log.entries.insert(0, format!("You are no longer {}", opposite(kind).unwrap().adjective()));
+        }
+        return;
+    }
+
+    match existing.and_then(|e| statuses.get_mut(e)) {
+        Some(status) => {
+            status.turns = i32::max(status.turns, turns);
+            if status.kind == StatusKind::Poison {
+                status.magnitude += magnitude;
+            } else {
+                status.magnitude = i32::max(status.magnitude, magnitude);
+            }
+        }
+        None => {
+            let status = entities.create();
+            statuses.insert(status, StatusEffect { target, kind, turns, magnitude }).expect("Unable to insert status");
+            ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>()
+                .mark(status, &mut ecs.write_storage::<SimpleMarker<SerializeMe>>());
+        }
+    }
+
+    if target == player_entity {
+        log.entries.insert(0, format!("You are {}!", kind.adjective()));
+    }
+    if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) {
+        viewshed.dirty = true;
+    }
+}
@@ -0,0 +1,41 @@
+extern crate specs;
+use specs::prelude::*;
+
+extern crate rltk;
+use rltk::Point;
+
+use super::{ Targets, Map };
+use super::super::Position;
+
+/// The map index an entity stands on, if it's on the map
+pub fn entity_position(ecs: &World, target: Entity) -> Option<usize> {
+    let positions = ecs.read_storage::<Position>();
+    positions.get(target).map(|pos| ecs.fetch::<Map>().xy_idx(pos.x, pos.y))
+}
+
+/// The map indices an effect covers; a single target covers the tile it
+/// stands on
+pub fn tiles_in(ecs: &World, targets: &Targets) -> Vec<usize> {
+    match targets {
+        Targets::Single { target } => entity_position(ecs, *target).into_iter().collect(),
+        Targets::Tile { tile_idx } => vec![*tile_idx],
+        Targets::Tiles { tiles } => tiles.clone(),
+        Targets::Area { x, y, radius } => {
+            let map = ecs.fetch::<Map>();
+            let mut blast_tiles = rltk::field_of_view(Point::new(*x, *y), *radius, &*map);
+            blast_tiles.retain(|p| p.x > 0 && p.x < map.width-1 && p.y > 0 && p.y < map.height-1);
+            blast_tiles.iter().map(|p| map.xy_idx(p.x, p.y)).collect()
+        }
+    }
+}
+
+/// Everything an effect would land on
+pub fn entities_in(ecs: &World, targets: &Targets) -> Vec<Entity> {
+    match targets {
+        Targets::Single { target } => vec![*target],
+        _ => {
+            let map = ecs.fetch::<Map>();
+            tiles_in(ecs, targets).iter().flat_map(|idx| map.contents[*idx].iter().cloned()).collect()
+        }
+    }
+}
@@ -0,0 +1,109 @@
+extern crate specs;
+use specs::prelude::*;
+
+extern crate rltk;
+use rltk::RGB;
+
+use super::{ EffectQueue, EffectType, Targets, Map, entities_in, tiles_in };
+use super::super::{
+    Name,
+    Consumable,
+    ProvidesHealing,
+    InflictsDamage,
+    InflictsStatus,
+    ProvidesFood,
+    MagicMapper,
+    TeleportsUser,
+    CombatStats,
+    HungerClock,
+    gamelog::GameLog };
+
+fn particle(glyph: char, fg: (u8, u8, u8)) -> EffectType {
+    EffectType::Particle {
+        glyph: rltk::to_cp437(glyph),
+        fg: RGB::named(fg),
+        bg: RGB::named(rltk::BLACK),
+        lifetime: 200.0
+    }
+}
+
+/// Queues up everything the item does to its targets, then uses it up
+/// if it's consumable
+pub fn item_trigger(ecs: &mut World, creator: Option<Entity>, item: Entity, targets: &Targets) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let used_by_player = creator == Some(player_entity);
+    let victims = entities_in(ecs, targets);
+    let tiles = tiles_in(ecs, targets);
+    {
+        let mut queue = ecs.write_resource::<EffectQueue>();
+        let mut log = ecs.write_resource::<GameLog>();
+        let names = ecs.read_storage::<Name>();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let item_name = &names.get(item).unwrap().name;
+
+        if let Targets::Area { .. } = targets {
+            queue.add(creator, particle('░', rltk::ORANGE), Targets::Tiles { tiles: tiles.clone() });
+        }
+
+        if let Some(status) = ecs.read_storage::<InflictsStatus>().get(item) {
+            queue.add(creator, EffectType::Status { kind: status.kind, turns: status.turns, magnitude: status.magnitude }, targets.clone());
+            for victim in victims.iter() {
+                queue.add(creator, particle('?', rltk::MAGENTA), Targets::Single { target: *victim });
+                if used_by_player && *victim != player_entity {
+                    let victim_name = &names.get(*victim).unwrap().name;
+                    log.entries.insert(0, format!("You use {} on {}, leaving them {}", item_name, victim_name, status.kind.adjective()));
+                }
+            }
+        }
+
+        if let Some(healer) = ecs.read_storage::<ProvidesHealing>().get(item) {
+            queue.add(creator, EffectType::Healing { amount: healer.heal_amount }, targets.clone());
+            for victim in victims.iter().filter(|v| combat_stats.get(**v).is_some()) {
+                queue.add(creator, particle('♥', rltk::GREEN), Targets::Single { target: *victim });
+                if used_by_player && *victim == player_entity {
+                    log.entries.insert(0, format!("The {} heals you by {} points", item_name, healer.heal_amount));
+                } else if used_by_player {
+                    let victim_name = &names.get(*victim).unwrap().name;
+                    log.entries.insert(0, format!("The {} heals {} by {} points", item_name, victim_name, healer.heal_amount));
+                }
+            }
+        }
+
+        if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(item) {
+            queue.add(creator, EffectType::Damage { amount: damage.damage }, targets.clone());
+            for victim in victims.iter().filter(|v| combat_stats.get(**v).is_some()) {
+                queue.add(creator, particle('‼', rltk::ORANGE), Targets::Single { target: *victim });
+                if used_by_player {
+                    let victim_name = &names.get(*victim).unwrap().name;
+                    log.entries.insert(0, format!("You use {} on {} and inflict {} damage", item_name, victim_name, damage.damage));
+                }
+            }
+        }
+
+        if ecs.read_storage::<ProvidesFood>().get(item).is_some() {
+            if let Some(eater) = victims.first() {
+                if ecs.read_storage::<HungerClock>().get(*eater).is_some() {
+                    queue.add(creator, EffectType::WellFed, Targets::Single { target: *eater });
+                    log.entries.insert(0, format!("You eat the {}", item_name));
+                }
+            }
+        }
+
+        if ecs.read_storage::<MagicMapper>().get(item).is_some() {
+            queue.add(creator, EffectType::RevealMap, targets.clone());
+            log.entries.insert(0, "The map is revealed to you!".to_string());
+        }
+
+        if ecs.read_storage::<TeleportsUser>().get(item).is_some() {
+            if let (Some(user), Some(destination)) = (creator, tiles.first()) {
+                let width = ecs.fetch::<Map>().width;
+                let (x, y) = (*destination as i32 % width, *destination as i32 / width);
+                queue.add(creator, EffectType::TeleportTo { x, y }, Targets::Single { target: user });
+            }
+        }
+    }
+
+    if ecs.read_storage::<Consumable>().get(item).is_some() {
+        ecs.entities().delete(item).expect("Delete failed");
+    }
+}
@@ -0,0 +1,63 @@
+use rand::{ Rng, RngCore, SeedableRng };
+use rand_xorshift::XorShiftRng;
+use serde::{ Serialize, Deserialize };
+
+/// The seed the current game was started from. Every random roll in a
+/// session comes from the world's RandomNumberGenerator, which is seeded
+/// from this value, so the same seed and inputs replay the same game.
+pub struct GameSeed {
+    pub seed: u64,
+}
+
+/// Picks a fresh seed from system entropy
+pub fn new_seed() -> u64 {
+    RandomNumberGenerator::new().next_u64()
+}
+
+/// Returns the seed passed on the command line with `--seed <n>`, if any
+pub fn seed_from_args() -> Option<u64> {
+    let args : Vec<String> = std::env::args().collect();
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--seed" {
+            return args.get(i + 1).and_then(|s| s.parse::<u64>().ok());
+        }
+    }
+    None
+}
+
+/// The same generator as `rltk::RandomNumberGenerator`, rolling exactly
+/// what it would, but with state that can be saved. Saves store it as it
+/// stands, so a loaded game goes on to roll exactly what the game it was
+/// saved from does, and saving never changes what is rolled.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RandomNumberGenerator {
+    rng: XorShiftRng,
+}
+
+impl RandomNumberGenerator {
+    /// Creates a generator seeded from system entropy
+    #[allow(clippy::new_without_default)] // like rltk's, there's no sensible default
+    pub fn new() -> RandomNumberGenerator {
+        RandomNumberGenerator { rng: XorShiftRng::from_entropy() }
+    }
+
+    pub fn seeded(seed: u64) -> RandomNumberGenerator {
+        RandomNumberGenerator { rng: XorShiftRng::seed_from_u64(seed) }
+    }
+
+    /// A random value in `min .. max`, including `min` but not `max`
+    pub fn range<T>(&mut self, min: T, max: T) -> T
+        where T: rand::distributions::uniform::SampleUniform
+    {
+        self.rng.gen_range(min, max)
+    }
+
+    /// The total of `n` rolls of a `die_type` sided die
+    pub fn roll_dice(&mut self, n: i32, die_type: i32) -> i32 {
+        (0 .. n).map(|_| self.range(1, die_type + 1)).sum()
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+}
@@ -0,0 +1,51 @@
+use super::{ Skill, Skills, Attributes, NaturalAttackDefense };
+
+/// The modifier an attribute adds to rolls: 10 and 11 are average, and
+/// every two points either side is worth one more
+pub fn attr_bonus(value: i32) -> i32 {
+    (value - 10).div_euclid(2)
+}
+
+/// Maximum hit points for the player at the given level
+pub fn player_hp_at_level(fitness: i32, level: i32) -> i32 {
+    20 + (10 + attr_bonus(fitness)) * level
+}
+
+/// Experience earned for killing a monster of the given level
+pub fn xp_for_kill(level: i32) -> i32 {
+    100 * level
+}
+
+/// Total experience needed to go up from the given level
+pub fn xp_to_level_up(level: i32) -> i32 {
+    500 * level
+}
+
+/// The bonus for a skill. Having no training at all counts against you.
+pub fn skill_bonus(skill: Skill, skills: &Skills) -> i32 {
+    skills.skills.get(&skill).copied().unwrap_or(-4)
+}
+
+/// Armour class before anything worn: natural armour (10 without any),
+/// plus quickness and defensive training
+pub fn base_armor_class(natural: Option<&NaturalAttackDefense>, attributes: Option<&Attributes>, skills: Option<&Skills>) -> i32 {
+    natural.and_then(|n| n.armor_class).unwrap_or(10)
+        + attributes.map_or(0, |a| attr_bonus(a.quickness.value()))
+        + skills.map_or(0, |s| skill_bonus(Skill::Defense, s))
+}
+
+/// What shooting at something `distance` tiles away takes off the attack
+/// roll: nothing up close, then one for every two tiles
+pub fn range_penalty(distance: f32) -> i32 {
+    i32::max(0, distance as i32 - 1) / 2
+}
+
+/// Whether a d20 attack roll beats the armour class. A natural 1 always
+/// misses and a natural 20 always hits, whatever the bonuses.
+pub fn attack_hits(natural_roll: i32, hit_bonus: i32, armor_class: i32) -> bool {
+    match natural_roll {
+        1 => false,
+        20 => true,
+        _ => natural_roll + hit_bonus >= armor_class
+    }
+}
@@ -15,10 +15,19 @@ use super::{
     Name, 
     Position,
     HungerState,
-    HungerClock };
+    HungerClock,
+    Attributes,
+    AttributeName,
+    Experience,
+    StatusEffect,
+    StatusKind,
+    Ammunition,
+    Equipped,
+    gamesystem::xp_to_level_up,
+    camera };
 
 #[derive(PartialEq, Copy, Clone)]
-pub enum MainMenuSelection { NewGame, LoadGame, Quit }
+pub enum MainMenuSelection { NewGame, LoadGame, Permadeath, Quit }
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum MainMenuResult {
@@ -44,10 +53,42 @@ pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
     }
 }
 
+/// Asks which attribute to raise on going up a level. There's no way to
+/// cancel; returns None until a choice is made.
+pub fn level_up_menu(gs: &mut State, ctx: &mut Rltk) -> Option<AttributeName> {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let attributes = gs.ecs.read_storage::<Attributes>();
+    let player_attributes = attributes.get(*player_entity)?;
+    let choices = [
+        (AttributeName::Might, "Might", player_attributes.might),
+        (AttributeName::Fitness, "Fitness", player_attributes.fitness),
+        (AttributeName::Quickness, "Quickness", player_attributes.quickness),
+        (AttributeName::Intelligence, "Intelligence", player_attributes.intelligence),
+    ];
+
+    let top = 20;
+    ctx.draw_box(15, top-2, 40, (choices.len()+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, top-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Level Up! Raise which attribute?");
+    for (j, (_, name, attribute)) in choices.iter().enumerate() {
+        let y = top + j as i32;
+        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
+        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+        ctx.print(21, y, &format!("{} ({})", name, attribute.value()));
+    }
+
+    let selection = rltk::letter_to_option(ctx.key?);
+    if selection > -1 && selection < choices.len() as i32 {
+        return Some(choices[selection as usize].0);
+    }
+    None
+}
+
 pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
+    let ammunition = gs.ecs.read_storage::<Ammunition>();
     let entities = gs.ecs.entities();
 
     let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity );
@@ -65,7 +106,10 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
-        ctx.print(21, y, &name.name.to_string());
+        match ammunition.get(entity) {
+            Some(ammo) => ctx.print(21, y, &format!("{} ({})", name.name, ammo.count)),
+            None => ctx.print(21, y, &name.name.to_string()),
+        }
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -92,6 +136,7 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
+    let ammunition = gs.ecs.read_storage::<Ammunition>();
     let entities = gs.ecs.entities();
 
     let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity);
@@ -107,7 +152,10 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
-        ctx.print(21, y, &name.name.to_string());
+        match ammunition.get(entity) {
+            Some(ammo) => ctx.print(21, y, &format!("{} ({})", name.name, ammo.count)),
+            None => ctx.print(21, y, &name.name.to_string()),
+        }
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -140,7 +188,8 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     let combat_stats = ecs.read_storage::<CombatStats>();
     let players = ecs.read_storage::<Player>();
     let hunger = ecs.read_storage::<HungerClock>();
-    for (_, stats, hc) in (&players, &combat_stats, &hunger).join() {
+    let experience = ecs.read_storage::<Experience>();
+    for (_, stats, hc, exp) in (&players, &combat_stats, &hunger, &experience).join() {
         let health = format!(" HP: {} / {}", stats.hp, stats.max_hp);
         ctx.print_color(
             12, 
@@ -151,11 +200,18 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         ctx.draw_bar_horizontal(
             28, 
             43, 
-            51, 
+            30, 
             stats.hp, 
             stats.max_hp, 
             RGB::named(rltk::RED), 
             RGB::named(rltk::BLACK));
+        let level = format!("Level {}  XP {}/{}", exp.level, exp.xp, xp_to_level_up(exp.level));
+        ctx.print_color(
+            60,
+            43,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &level);
         match hc.state {
             HungerState::WellFed => ctx.print_color(
                 71, 
@@ -179,6 +235,24 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         }
     }
 
+    // status effects run leftwards from the hunger display
+    let player_entity = ecs.fetch::<Entity>();
+    let statuses = ecs.read_storage::<StatusEffect>();
+    let mut x = 70;
+    for status in statuses.join().filter(|s| s.target == *player_entity) {
+        let label = format!("{} ({})", capitalize(status.kind.adjective()), status.turns);
+        x -= label.len() as i32 + 1;
+        ctx.print_color(x, 42, status_color(status.kind), RGB::named(rltk::BLACK), &label);
+    }
+
+    // whatever is readied to shoot sits at the other end
+    let names = ecs.read_storage::<Name>();
+    let ammunition = ecs.read_storage::<Ammunition>();
+    let equipped = ecs.read_storage::<Equipped>();
+    for (ammo, name, _) in (&ammunition, &names, &equipped).join().filter(|a| a.2.owner == *player_entity) {
+        ctx.print_color(2, 42, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("{}: {}", name.name, ammo.count));
+    }
+
     let log = ecs.fetch::<GameLog>();
     let mut y = 44;
     for s in log.entries.iter() {
@@ -193,18 +267,46 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     draw_tooltips(ecs, ctx);
 }
 
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+fn status_color(kind: StatusKind) -> RGB {
+    match kind {
+        StatusKind::Confusion => RGB::named(rltk::MAGENTA),
+        StatusKind::Poison => RGB::named(rltk::GREEN),
+        StatusKind::Regeneration => RGB::named(rltk::PINK),
+        StatusKind::Slow => RGB::named(rltk::BLUE),
+        StatusKind::Haste => RGB::named(rltk::CYAN),
+        StatusKind::Paralysis => RGB::named(rltk::YELLOW),
+        StatusKind::Blindness => RGB::named(rltk::GREY),
+    }
+}
+
 fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
+    let statuses = ecs.read_storage::<StatusEffect>();
+    let entities = ecs.entities();
 
     let mouse_pos = ctx.mouse_pos();
-    if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height { return; }
+    let mouse_map_pos = camera::screen_to_map(ecs, mouse_pos);
+    if mouse_map_pos.x < 0 || mouse_map_pos.x >= map.width || mouse_map_pos.y < 0 || mouse_map_pos.y >= map.height { return; }
 
     let mut tooltip : Vec<String> = Vec::new();
-    for (name, pos) in (&names, &positions).join() {
-        if pos.x == mouse_pos.0 && pos.y == mouse_pos.1 {
-            tooltip.push(name.name.to_string());
+    for (entity, name, pos) in (&entities, &names, &positions).join() {
+        if pos.x == mouse_map_pos.x && pos.y == mouse_map_pos.y {
+            let effects : Vec<&str> = statuses.join().filter(|s| s.target == entity).map(|s| s.kind.adjective()).collect();
+            if effects.is_empty() {
+                tooltip.push(name.name.to_string());
+            } else {
+                tooltip.push(format!("{} ({})", name.name, effects.join(", ")));
+            }
         }
     }
 
@@ -287,7 +389,8 @@ pub fn ranged_target(gs: &mut State, ctx: &mut Rltk, range: i32) -> (ItemMenuRes
         for idx in visible.tiles.iter() {
             let distance = rltk::DistanceAlg::Pythagoras.distance2d(*pos, *idx);
             if distance <= range as f32 {
-                ctx.set_bg(idx.x, idx.y, RGB::named(rltk::BLUE));
+                let screen = camera::map_to_screen(&gs.ecs, *idx);
+                ctx.set_bg(screen.x, screen.y, RGB::named(rltk::BLUE));
                 available_cells.push(idx);
             }
         }
@@ -297,14 +400,15 @@ pub fn ranged_target(gs: &mut State, ctx: &mut Rltk, range: i32) -> (ItemMenuRes
 
     // Draw mouse cursor
     let mouse_pos = ctx.mouse_pos();
+    let mouse_map_pos = camera::screen_to_map(&gs.ecs, mouse_pos);
     let mut valid_target = false;
     for idx in available_cells.iter() { 
-        if idx.x == mouse_pos.0 && idx.y == mouse_pos.1 { valid_target = true; }
+        if idx.x == mouse_map_pos.x && idx.y == mouse_map_pos.y { valid_target = true; }
     }
     if valid_target {
         ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::CYAN));
         if ctx.left_click {
-            return (ItemMenuResult::Selected, Some(Point::new(mouse_pos.0, mouse_pos.1)));
+            return (ItemMenuResult::Selected, Some(mouse_map_pos));
         }
     } else {
         ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED));
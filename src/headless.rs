@@ -0,0 +1,130 @@
+use specs::prelude::*;
+
+use super::{
+    State,
+    RunState,
+    CombatStats,
+    Map,
+    raws,
+    saveload_system,
+    damage_system,
+    particle_system,
+    player::{ PlayerCommand, apply_command } };
+
+/// Particles have no one to show them to, so each step ages them past
+/// any lifetime we use.
+const HEADLESS_FRAME_MS : f32 = 1000.0;
+
+/// Drives the game's turn logic without an Rltk window, feeding it
+/// player commands instead of key presses.
+pub struct HeadlessGame {
+    pub gs: State,
+}
+
+impl HeadlessGame {
+    /// Builds a new game from the seed and runs it up to the first
+    /// point where the player is asked for input.
+    pub fn new(seed: u64) -> HeadlessGame {
+        raws::load_raws();
+
+        let mut gs = State::new();
+        gs.ecs.write_resource::<saveload_system::SaveMode>().autosave = false;
+        gs.new_game(seed);
+        gs.ecs.insert(RunState::PreRun);
+
+        let mut game = HeadlessGame { gs };
+        game.settle();
+        game
+    }
+
+    pub fn runstate(&self) -> RunState {
+        *self.gs.ecs.fetch::<RunState>()
+    }
+
+    /// Applies a single command and advances the game until it is waiting
+    /// for the player again (or the game has ended). Commands given while
+    /// the game isn't waiting for them are ignored; level ups are only
+    /// taken on the level up screen.
+    pub fn run_command(&mut self, command: PlayerCommand) -> RunState {
+        let accepted = match command {
+            PlayerCommand::LevelUp { .. } => self.runstate() == RunState::LevelUp,
+            _ => self.runstate() == RunState::AwaitingInput
+        };
+        if !accepted { return self.runstate(); }
+
+        let newrunstate = apply_command(&mut self.gs.ecs, command);
+        self.finish_step(newrunstate);
+        self.settle();
+        self.runstate()
+    }
+
+    /// Runs the commands in order, stopping early if the game ends.
+    /// Returns the number of commands that were applied.
+    pub fn run_script(&mut self, commands: &[PlayerCommand]) -> usize {
+        let mut applied = 0;
+        for command in commands.iter() {
+            if self.runstate() != RunState::AwaitingInput && self.runstate() != RunState::LevelUp { break; }
+            self.run_command(*command);
+            applied += 1;
+        }
+        applied
+    }
+
+    fn settle(&mut self) {
+        loop {
+            let runstate = self.runstate();
+            match runstate {
+                RunState::PreRun |
+                RunState::PlayerTurn |
+                RunState::MonsterTurn |
+                RunState::NextLevel |
+                RunState::PreviousLevel => {
+                    let newrunstate = self.gs.advance(runstate);
+                    self.finish_step(newrunstate);
+                }
+                _ => return
+            }
+        }
+    }
+
+    /// Mirrors the end of `State::tick`
+    fn finish_step(&mut self, newrunstate: RunState) {
+        {
+            let mut runwriter = self.gs.ecs.write_resource::<RunState>();
+            *runwriter = newrunstate;
+        }
+
+        damage_system::delete_the_dead(&mut self.gs.ecs);
+        particle_system::cull_dead_particles(&mut self.gs.ecs, HEADLESS_FRAME_MS);
+    }
+}
+
+/// Returns the command script passed on the command line with
+/// `--headless <script>`, if any.
+pub fn script_from_args() -> Option<String> {
+    let args : Vec<String> = std::env::args().collect();
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--headless" {
+            return Some(args.get(i + 1).cloned().unwrap_or_default());
+        }
+    }
+    None
+}
+
+/// Plays the script (see `PlayerCommand::from_char`) against a new game
+/// and prints a short summary; used for balance runs without a display.
+pub fn simulate(seed: u64, script: &str) {
+    let commands : Vec<PlayerCommand> = script.chars().filter_map(PlayerCommand::from_char).collect();
+
+    let mut game = HeadlessGame::new(seed);
+    let applied = game.run_script(&commands);
+
+    let ecs = &game.gs.ecs;
+    let depth = ecs.fetch::<Map>().depth;
+    let player = ecs.fetch::<Entity>();
+    let stats = ecs.read_storage::<CombatStats>();
+    let hp = stats.get(*player).map(|s| s.hp).unwrap_or(0);
+    let outcome = if game.runstate() == RunState::GameOver { "dead" } else { "alive" };
+
+    println!("seed {}: {} of {} commands, depth {}, hp {}, {}", seed, applied, commands.len(), depth, hp, outcome);
+}
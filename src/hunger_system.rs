@@ -3,7 +3,7 @@ use super::{
     HungerClock,
     RunState,
     HungerState,
-    SufferDamage,
+    effects::{ EffectQueue, EffectType, Targets },
     gamelog::GameLog };
 
 pub struct HungerSystem {}
@@ -14,7 +14,7 @@ impl<'a> System<'a> for HungerSystem {
                         WriteStorage<'a, HungerClock>,
                         ReadExpect<'a, Entity>, 
                         ReadExpect<'a, RunState>,
-                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, EffectQueue>,
                         WriteExpect<'a, GameLog>
                       );
 
@@ -24,7 +24,7 @@ impl<'a> System<'a> for HungerSystem {
             mut hunger_clock,
             player_entity,
             runstate,
-            mut inflict_damage, 
+            mut effects,
             mut log) = data;
 
         for (entity, mut clock) in (&entities, &mut hunger_clock).join() {
@@ -81,9 +81,7 @@ impl<'a> System<'a> for HungerSystem {
                                     0, 
                                     "Your hunger pangs are getting painful".to_string());
                             }
-                            inflict_damage.insert(
-                                entity, 
-                                SufferDamage { amount: 1 }).expect("Unable to do damage");
+                            effects.add(None, EffectType::Damage { amount: 1 }, Targets::Single { target: entity });
                         }
                     }
                 }
@@ -6,23 +6,14 @@ use super::{
     WantsToPickupItem,
     WantsToUseItem,
     WantsToDropItem,
-    ProvidesHealing,
-    InflictsDamage,
-    Consumable,
     Name,
     Position,
-    CombatStats,
-    SufferDamage,
     AreaOfEffect,
-    Confusion,
     InBackpack,
     Equippable,
     Equipped,
-    HungerState,
-    HungerClock,
-    ProvidesFood,
-    MagicMapper,
-    particle_system::ParticleBuilder,
+    Ammunition,
+    effects::{ EffectQueue, EffectType, Targets },
     gamelog::GameLog };
 
 pub struct ItemCollectionSystem {}
@@ -30,19 +21,42 @@ pub struct ItemCollectionSystem {}
 impl<'a> System<'a> for ItemCollectionSystem {
     type SystemData = ( ReadExpect<'a, Entity>,
                         WriteExpect<'a, GameLog>,
+                        Entities<'a>,
                         WriteStorage<'a, WantsToPickupItem>,
                         WriteStorage<'a, Position>,
                         ReadStorage<'a, Name>,
-                        WriteStorage<'a, InBackpack>, );
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Ammunition>,
+                        ReadStorage<'a, Equipped>, );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) = data;
+        let (player_entity, mut gamelog, entities, mut wants_pickup, mut positions, names, mut backpack, mut ammunition, equipment) = data;
 
         for pickup in wants_pickup.join() {
             positions.remove(pickup.item);
-            backpack.
-                insert(pickup.item, InBackpack { owner: pickup.collected_by }).
-                expect("Unable to insert item into backpack");
+
+            // arrows and the like go in with any of the same already carried
+            let bundle = match (ammunition.get(pickup.item), names.get(pickup.item)) {
+                (Some(_), Some(item_name)) => (&entities, &ammunition, &names).join()
+                    .find(|(e, _, name)| {
+                        let owner = backpack.get(*e).map(|b| b.owner).or_else(|| equipment.get(*e).map(|eq| eq.owner));
+                        *e != pickup.item && owner == Some(pickup.collected_by) && name.name == item_name.name
+                    })
+                    .map(|(e, _, _)| e),
+                _ => None
+            };
+            match bundle {
+                Some(bundle) => {
+                    let count = ammunition.get(pickup.item).unwrap().count;
+                    ammunition.get_mut(bundle).unwrap().count += count;
+                    entities.delete(pickup.item).expect("Unable to delete ammunition");
+                }
+                None => {
+                    backpack.
+                        insert(pickup.item, InBackpack { owner: pickup.collected_by }).
+                        expect("Unable to insert item into backpack");
+                }
+            }
 
             if pickup.collected_by == *player_entity {
                 gamelog.entries.insert(0, format!(
@@ -55,236 +69,92 @@ impl<'a> System<'a> for ItemCollectionSystem {
     }
 }
 
-pub struct ItemUseSystem {}
+/// Using something that can be worn or wielded puts it on, swapping out
+/// whatever was already in its slot
+pub struct ItemEquipOnUse {}
 
-impl<'a> System<'a> for ItemUseSystem {
+impl<'a> System<'a> for ItemEquipOnUse {
     type SystemData = ( ReadExpect<'a, Entity>,
                         WriteExpect<'a, GameLog>,
                         Entities<'a>,
                         WriteStorage<'a, WantsToUseItem>,
                         ReadStorage<'a, Name>,
-                        ReadStorage<'a, Consumable>,
-                        ReadStorage<'a, ProvidesFood>,
-                        ReadStorage<'a, ProvidesHealing>,
-                        ReadStorage<'a, MagicMapper>,
-                        ReadStorage<'a, InflictsDamage>,
-                        WriteStorage<'a, CombatStats>,
-                        WriteStorage<'a, HungerClock>,
-                        WriteStorage<'a, SufferDamage>,
-                        ReadStorage<'a, AreaOfEffect>,
-                        WriteStorage<'a, Confusion>,
                         ReadStorage<'a, Equippable>,
                         WriteStorage<'a, Equipped>,
-                        WriteStorage<'a, InBackpack>,
-                        WriteExpect<'a, Map>,
-                        WriteExpect<'a, ParticleBuilder>,
-                        ReadStorage<'a, Position> );
+                        WriteStorage<'a, InBackpack> );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, 
-             mut gamelog, 
-             entities, 
-             mut wants_use, 
-             names, 
-             consumables, 
-             provides_food,
-             healers,
-             magic_mappers,
-             damagers,
-             mut combat_stats,
-             mut hunger_clocks,
-             mut suffer_damage,
-             aoe,
-             mut confused,
+        let (player_entity,
+             mut gamelog,
+             entities,
+             mut wants_use,
+             names,
              equippable,
              mut equipment,
-             mut backpack,
-             mut map, 
-             mut particle_builder,
-             positions) = data;
-
-        for(entity, useitem) in (&entities, &wants_use).join() {
-            let mut targets : Vec<Entity> = Vec::new();
-            match useitem.target {
-                None => { targets.push(*player_entity); }
-                Some(target) => {
-                    let area_effect = aoe.get(useitem.item);
-                    match area_effect {
-                        None => {
-                            let idx = Map::xy_idx(target.x, target.y);
-                            for mob in map.contents[idx].iter() { targets.push(*mob); }
-                        }
-                        Some(area_effect) => {
-                            let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
-                            blast_tiles.retain(|p| p.x > 0 && p.x < map.width-1 && p.y > 0 && p.y < map.height-1);
-                            for tile_idx in blast_tiles.iter() {
-                                let idx = Map::xy_idx(tile_idx.x, tile_idx.y);
-                                for mob in map.contents[idx].iter() { targets.push(*mob); }
-                                particle_builder.requests(
-                                    tile_idx.x,
-                                    tile_idx.y,
-                                    rltk::RGB::named(rltk::ORANGE),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('░'),
-                                    200.0);
-                            }
-                        }
-                    }
-                }
-            }
+             mut backpack) = data;
 
-            let mut add_confusion = Vec::new();
-            {
-                let causes_confusion = confused.get(useitem.item);
-                match causes_confusion {
-                    None => {},
-                    Some(confusion) => {
-                        for mob in targets.iter() {
-                            add_confusion.push((*mob, confusion.turns));
-                            if entity == *player_entity {
-                                let mob_name = names.get(*mob).unwrap();
-                                let item_name = names.get(useitem.item).unwrap();
-                                gamelog.entries.insert(0, format!("You use {} on {}, confusing them", item_name.name, mob_name.name));
-                            }
-                            let pos = positions.get(*mob);
-                            if let Some(pos) = pos {
-                                particle_builder.requests(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::MAGENTA),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('?'),
-                                    200.0);
-                            }
-                        }
+        let mut equipped : Vec<Entity> = Vec::new();
+        for (target, useitem) in (&entities, &wants_use).join() {
+            let can_equip = match equippable.get(useitem.item) {
+                Some(can_equip) => can_equip,
+                None => continue
+            };
+            let target_slot = can_equip.slot;
+
+            // remove any items the target has in the item's slot
+            let mut to_unequip : Vec<Entity> = Vec::new();
+            for (item_entity, already_equipped, name) in (&entities, &equipment, &names).join() {
+                if already_equipped.owner == target && already_equipped.slot == target_slot {
+                    to_unequip.push(item_entity);
+                    if target == *player_entity {
+                        gamelog.entries.insert(0, format!("You unequip {}", name.name));
                     }
                 }
             }
-            for mob in add_confusion.iter() {
-                confused.insert(mob.0, Confusion { turns: mob.1 }).expect("Unable to insert confused");
-            }
-
-            let item_heals = healers.get(useitem.item);
-            match item_heals {
-                None => {},
-                Some(healer) => {
-                    for target in targets.iter() {
-                        let stats = combat_stats.get_mut(*target);
-                        if let Some(stats) = stats {
-                            stats.hp = i32::max(stats.max_hp, stats.hp + healer.heal_amount);
-                            if entity == *player_entity {
-                                gamelog.entries.insert(0, format!("The {} heals you by {} points", names.get(useitem.item).unwrap().name, healer.heal_amount));
-                            }
 
-                            let pos = positions.get(*target);
-                            if let Some(pos) = pos {
-                                particle_builder.requests(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::GREEN),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('♥'),
-                                    200.0);
-                            }
-                        }
-                    }
-                }
+            for item in to_unequip.iter() {
+                equipment.remove(*item);
+                backpack.insert(*item, InBackpack { owner: target }).expect("Unable to put in backpack");
             }
 
-            let item_hurts = damagers.get(useitem.item);
-            match item_hurts {
-                None => {},
-                Some(damage) => {
-                    for mob in targets.iter() {
-                        suffer_damage.insert(*mob, SufferDamage { amount: damage.damage }).expect("Unable to insert");
-                        let pos = positions.get(*mob);
-                        if let Some(pos) = pos {
-                            particle_builder.requests(
-                                pos.x,
-                                pos.y,
-                                rltk::RGB::named(rltk::ORANGE),
-                                rltk::RGB::named(rltk::BLACK),
-                                rltk::to_cp437('‼'),
-                                200.0);
-                        }
-
-                        if entity == *player_entity {
-                            let mob_name = names.get(*mob).unwrap();
-                            let item_name = names.get(useitem.item).unwrap();
-                            gamelog.entries.insert(0, format!("You use {} on {} and inflict {} damage", item_name.name, mob_name.name, damage.damage));
-                        }
-                    }
-                }
+            // weild the item
+            equipment.insert(useitem.item, Equipped { owner: target, slot: target_slot }).expect("unable to wield equipment");
+            backpack.remove(useitem.item);
+            if target == *player_entity {
+                gamelog.entries.insert(0, format!("You equip {}", names.get(useitem.item).unwrap().name));
             }
+            equipped.push(target);
+        }
 
-            let item_equippable = equippable.get(useitem.item);
-            match item_equippable {
-                None => {},
-                Some(can_equip) => {
-                    let target_slot = can_equip.slot;
-                    let target = targets[0];
-
-                    // remove any items the target has in the item's slot
-                    let mut to_unequip : Vec<Entity> = Vec::new();
-                    for (item_entity, already_equipped, name) in (&entities, &equipment, &names).join() {
-                        if already_equipped.owner == target && already_equipped.slot == target_slot {
-                            to_unequip.push(item_entity);
-                            if target == *player_entity {
-                                gamelog.entries.insert(0, format!("You unequip {}", name.name));
-                            }
-                        }
-                    }
-
-                    for item in to_unequip.iter() {
-                        equipment.remove(*item);
-                        backpack.insert(*item, InBackpack { owner: target }).expect("Unable to put in backpack");
-                    }
-
-                    // weild the item
-                    equipment.insert(useitem.item, Equipped { owner: target, slot: target_slot }).expect("unable to wield equipment");
-                    backpack.remove(useitem.item);
-                    if target == *player_entity {
-                        gamelog.entries.insert(0, format!("You equip {}", names.get(useitem.item).unwrap().name));
-                    }
-                }
-            }
+        for target in equipped.iter() {
+            wants_use.remove(*target);
+        }
+    }
+}
 
-            let item_edible = provides_food.get(useitem.item);
-            match item_edible {
-                None => {}
-                Some(_) => {
-                    let target = targets[0];
-                    let hc = hunger_clocks.get_mut(target);
-                    if let Some(hc) = hc {
-                        hc.state = HungerState::WellFed;
-                        hc.duration = 20;
-                        gamelog.entries.insert(
-                            0,
-                            format!("You eat the {}", names.get(useitem.item).unwrap().name));
-                    }
-                }
-            }
+/// Hands every other item use to the effects queue, aimed at the user,
+/// the targeted tile or the area around it
+pub struct ItemUseSystem {}
 
-            let magic_mapper = magic_mappers.get(useitem.item);
-            match magic_mapper {
-                None => {},
-                Some(_) => {
-                    for r in map.revealed.iter_mut() {
-                        *r = true;
-                    }
-                    gamelog.entries.insert(
-                        0,
-                        "The map is revealed to you!".to_string());
-                }
-            }
+impl<'a> System<'a> for ItemUseSystem {
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, WantsToUseItem>,
+                        ReadStorage<'a, AreaOfEffect>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, EffectQueue> );
 
-            let consumable = consumables.get(useitem.item);
-            match consumable {
-                None => {},
-                Some(_) => {
-                    entities.delete(useitem.item).expect("Delete failed");
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut wants_use, aoe, map, mut effects) = data;
+
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            let targets = match useitem.target {
+                None => Targets::Single { target: entity },
+                Some(target) => match aoe.get(useitem.item) {
+                    None => Targets::Tile { tile_idx: map.xy_idx(target.x, target.y) },
+                    Some(area_effect) => Targets::Area { x: target.x, y: target.y, radius: area_effect.radius }
                 }
-            }
+            };
+            effects.add(Some(entity), EffectType::ItemUse { item: useitem.item }, targets);
         }
 
         wants_use.clear();
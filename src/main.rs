@@ -1,4 +1,4 @@
-#![recursion_limit="16"]
+#![recursion_limit="256"]
 
 extern crate rltk;
 rltk::add_wasm_support!();
@@ -9,6 +9,8 @@ use specs::prelude::*;
 use specs::saveload::{ SimpleMarker, SimpleMarkerAllocator };
 
 extern crate serde;
+extern crate rmp_serde;
+extern crate flate2;
 
 mod components;
 pub use components::*;
@@ -16,6 +18,9 @@ pub use components::*;
 mod map;
 pub use map::*;
 
+mod camera;
+mod themes;
+
 mod gui;
 mod menu;
 mod saveload_system;
@@ -25,14 +30,23 @@ pub mod map_builders;
 mod gamelog;
 use gamelog::*;
 
+mod game_seed;
+use game_seed::GameSeed;
+pub use game_seed::RandomNumberGenerator;
+
 mod player;
 use player::*;
 
+mod dungeon;
+pub use dungeon::MasterDungeonMap;
+
 mod rect;
 pub use rect::Rect;
 
 mod random_table;
+mod gamesystem;
 mod spawner;
+pub mod raws;
 
 mod visibility_system;
 use visibility_system::VisibilitySystem;
@@ -46,25 +60,42 @@ use map_indexing_system::MapIndexingSystem;
 mod melee_combat_system;
 use melee_combat_system::MeleeCombatSystem;
 
+mod ranged_combat_system;
+use ranged_combat_system::RangedCombatSystem;
+
 mod damage_system;
-use damage_system::DamageSystem;
 
 mod inventory_system;
 use inventory_system::ItemCollectionSystem;
 use inventory_system::ItemDropSystem;
+use inventory_system::ItemEquipOnUse;
 use inventory_system::ItemUseSystem;
 
+mod effects;
+
 mod particle_system;
 
 mod hunger_system;
 
+mod status_system;
+use status_system::has_status;
+
+pub mod headless;
+
+#[cfg(test)]
+mod tests;
+
 #[macro_use]
 extern crate specs_derive;
 
+#[macro_use]
+extern crate lazy_static;
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum RunState {
     MainMenu { menu_selection: gui::MainMenuSelection },
     SaveGame,
+    LoadGame,
     AwaitingInput,
     PreRun,
     PlayerTurn,
@@ -73,10 +104,30 @@ pub enum RunState {
     ShowDropItem,
     ShowTargeting { range: i32, item: Entity },
     NextLevel,
-    GameOver }
+    PreviousLevel,
+    GameOver,
+    LevelUp,
+    MapGeneration }
+
+/// How long each map generation snapshot stays on screen
+const MAPGEN_FRAME_TIME : f32 = 100.0;
+
+/// Number of player turns taken in the current game
+pub struct TurnCounter {
+    pub turns: i32,
+}
 
 pub struct State {
     pub ecs: World,
+    /// Replay each new level's generation before play starts
+    pub show_mapgen: bool,
+    mapgen_next_state: Option<RunState>,
+    mapgen_history: Vec<Map>,
+    mapgen_index: usize,
+    mapgen_timer: f32,
+    /// The save slots as the menus last read them; cleared whenever the
+    /// run state changes so each menu reads them afresh
+    save_listing: Option<saveload_system::SaveListing>,
 }
 
 impl GameState for State {
@@ -88,55 +139,46 @@ impl GameState for State {
         }
 
         ctx.cls();
-        particle_system::cull_dead_particles(&mut self.ecs, ctx);
+        particle_system::cull_dead_particles(&mut self.ecs, ctx.frame_time_ms);
 
         match newrunstate {
-            RunState::MainMenu {..} => {}
+            RunState::MainMenu {..} |
+            RunState::LoadGame |
+            RunState::MapGeneration => {}
 
             _ => {
-                draw_map(&self.ecs, ctx);
-
-                {
-                    let map = self.ecs.fetch::<Map>();
-                    let positions = self.ecs.read_storage::<Position>();
-                    let renderables = self.ecs.read_storage::<Renderable>();
-
-                    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-                    data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order) );
-                    for (pos, render) in data.iter() {
-                        let idx = Map::xy_idx(pos.x, pos.y);
-                        if map.visible[idx] {
-                            ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
-                        }
-                    }
-
-                    gui::draw_ui(&self.ecs, ctx);
-                }
+                camera::render_camera(&self.ecs, ctx);
+                gui::draw_ui(&self.ecs, ctx);
             }
         }
 
 
         match newrunstate {
-            RunState::PreRun => {
-                self.run_systems();
-                self.ecs.maintain();
-                newrunstate = RunState::AwaitingInput;
+            RunState::PreRun |
+            RunState::PlayerTurn |
+            RunState::MonsterTurn |
+            RunState::NextLevel |
+            RunState::PreviousLevel => {
+                newrunstate = self.advance(newrunstate);
             }
 
             RunState::AwaitingInput => {
                 newrunstate = player_input(self, ctx);
             }
 
-            RunState::PlayerTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                newrunstate = RunState::MonsterTurn;
-            }
-
-            RunState::MonsterTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                newrunstate = RunState::AwaitingInput;
+            RunState::MapGeneration => {
+                camera::render_debug_map(&self.mapgen_history[self.mapgen_index], ctx);
+
+                // any key skips to the end
+                self.mapgen_timer += ctx.frame_time_ms;
+                if self.mapgen_timer > MAPGEN_FRAME_TIME || ctx.key.is_some() {
+                    self.mapgen_timer = 0.0;
+                    self.mapgen_index += 1;
+                    if self.mapgen_index >= self.mapgen_history.len() || ctx.key.is_some() {
+                        self.mapgen_history.clear();
+                        newrunstate = self.mapgen_next_state.take().expect("Map generation has nowhere to go");
+                    }
+                }
             }
 
             RunState::ShowInventory => {
@@ -146,17 +188,11 @@ impl GameState for State {
                     gui::ItemMenuResult::NoResponse => {},
                     gui::ItemMenuResult::Selected => {
                         let item = result.1.unwrap();
-                        let is_ranged = self.ecs.read_storage::<Ranged>();
-                        let is_item_ranged = is_ranged.get(item);
-                        if let Some(is_item_ranged) = is_item_ranged {
-                            newrunstate = RunState::ShowTargeting {
-                                range: is_item_ranged.range, 
-                                item: item
-                            };
+                        let ranged = self.ecs.read_storage::<Ranged>().get(item).map(|r| r.range);
+                        if let Some(range) = ranged {
+                            newrunstate = RunState::ShowTargeting { range, item };
                         } else {
-                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                            intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem{ item, target: None }).expect("Unable to insert intent");
-                            newrunstate = RunState::PlayerTurn;
+                            newrunstate = apply_command(&mut self.ecs, PlayerCommand::UseItem { item, target: None });
                         }
                     }
                 }
@@ -168,10 +204,8 @@ impl GameState for State {
                     gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {},
                     gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
-                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToDropItem{ item: item_entity }).expect("Unable to insert intent");
-                        newrunstate = RunState::PlayerTurn;
+                        let item = result.1.unwrap();
+                        newrunstate = apply_command(&mut self.ecs, PlayerCommand::DropItem { item });
                     }
                 }
             }
@@ -182,9 +216,13 @@ impl GameState for State {
                     gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {},
                     gui::ItemMenuResult::Selected => {
-                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem{ item, target: target.1 }).expect("Unable to insert intent");
-                        newrunstate = RunState::PlayerTurn;
+                        let firing = self.ecs.read_storage::<RangedWeapon>().get(item).is_some();
+                        let command = if firing {
+                            PlayerCommand::Fire { target: target.1 }
+                        } else {
+                            PlayerCommand::UseItem { item, target: target.1 }
+                        };
+                        newrunstate = apply_command(&mut self.ecs, command);
                     }
                 }
             }
@@ -195,11 +233,17 @@ impl GameState for State {
                     gui::MainMenuResult::NoSelection { selected } => newrunstate = RunState::MainMenu { menu_selection: selected },
                     gui::MainMenuResult::Selected { selected } => {
                         match selected {
-                            gui::MainMenuSelection::NewGame => newrunstate = RunState::PreRun,
-                            gui::MainMenuSelection::LoadGame => {
-                                saveload_system::load_game(&mut self.ecs);
-                                newrunstate = RunState::AwaitingInput;
-                                saveload_system::delete_save();
+                            gui::MainMenuSelection::NewGame => {
+                                let seed = game_seed::seed_from_args().unwrap_or_else(game_seed::new_seed);
+                                self.new_game(seed);
+                                self.ecs.write_resource::<saveload_system::LoadStatus>().error = None;
+                                newrunstate = self.show_generation(RunState::PreRun);
+                            }
+                            gui::MainMenuSelection::LoadGame => newrunstate = RunState::LoadGame,
+                            gui::MainMenuSelection::Permadeath => {
+                                let mut save_mode = self.ecs.write_resource::<saveload_system::SaveMode>();
+                                save_mode.permadeath = !save_mode.permadeath;
+                                newrunstate = RunState::MainMenu { menu_selection: selected };
                             }
                             gui::MainMenuSelection::Quit => { ::std::process::exit(0); }
                         }
@@ -208,13 +252,51 @@ impl GameState for State {
             }
 
             RunState::SaveGame => {
-                saveload_system::save_game(&mut self.ecs);
-                newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::LoadGame };
+                let result = menu::save_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {},
+                    gui::ItemMenuResult::Selected => {
+                        match saveload_system::save_game(&mut self.ecs, result.1.unwrap()) {
+                            Ok(()) => newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::LoadGame },
+                            Err(e) => {
+                                self.ecs.fetch_mut::<GameLog>().entries.insert(0, format!("Unable to save: {}", e));
+                                newrunstate = RunState::AwaitingInput;
+                            }
+                        }
+                    }
+                }
             }
 
-            RunState::NextLevel => {
-                self.descend();
-                newrunstate = RunState::PreRun;
+            RunState::LoadGame => {
+                let result = menu::save_slot_menu(self, ctx, "Load Which Game", false);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::LoadGame },
+                    gui::ItemMenuResult::NoResponse => {},
+                    gui::ItemMenuResult::Selected => {
+                        let slot = result.1.unwrap();
+                        match saveload_system::load_game(&mut self.ecs, slot) {
+                            Ok(metadata) => {
+                                if metadata.permadeath {
+                                    saveload_system::delete_permadeath_copies(metadata.seed, None);
+                                }
+                                self.ecs.write_resource::<saveload_system::LoadStatus>().error = None;
+                                newrunstate = RunState::AwaitingInput;
+                            }
+                            Err(e) => {
+                                self.ecs.write_resource::<saveload_system::LoadStatus>().error = Some(format!("Unable to load: {}", e));
+                                newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::LoadGame };
+                            }
+                        }
+                    }
+                }
+            }
+
+            RunState::LevelUp => {
+                let result = gui::level_up_menu(self, ctx);
+                if let Some(attribute) = result {
+                    newrunstate = apply_command(&mut self.ecs, PlayerCommand::LevelUp { attribute });
+                }
             }
 
             RunState::GameOver => {
@@ -222,7 +304,9 @@ impl GameState for State {
                 match result {
                     gui::GameOverResult::NoSelection => {},
                     gui::GameOverResult::QuitToMenu => {
-                        self.game_over_cleanup();
+                        if self.ecs.fetch::<saveload_system::SaveMode>().permadeath {
+                            saveload_system::delete_permadeath_copies(self.ecs.fetch::<GameSeed>().seed, None);
+                        }
                         newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame };
                     }
                 }
@@ -231,6 +315,9 @@ impl GameState for State {
 
         {
             let mut runwriter = self.ecs.write_resource::<RunState>();
+            if std::mem::discriminant(&*runwriter) != std::mem::discriminant(&newrunstate) {
+                self.save_listing = None;
+            }
             *runwriter = newrunstate;
         }
 
@@ -240,11 +327,177 @@ impl GameState for State {
 }
 
 impl State {
+    /// Creates a world with every component registered and the resources
+    /// that don't depend on a particular game.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> State {
+        let mut gs = State {
+            ecs: World::new(),
+            show_mapgen: false,
+            mapgen_next_state: None,
+            mapgen_history: Vec::new(),
+            mapgen_index: 0,
+            mapgen_timer: 0.0,
+            save_listing: None,
+        };
+
+        gs.ecs.register::<Player>();
+        gs.ecs.register::<Monster>();
+        gs.ecs.register::<CombatStats>();
+        gs.ecs.register::<WantsToMelee>();
+        gs.ecs.register::<TeleportsUser>();
+        gs.ecs.register::<Position>();
+        gs.ecs.register::<Renderable>();
+        gs.ecs.register::<Item>();
+        gs.ecs.register::<Consumable>();
+        gs.ecs.register::<MeleeWeapon>();
+        gs.ecs.register::<Wearable>();
+        gs.ecs.register::<Ranged>();
+        gs.ecs.register::<InflictsStatus>();
+        gs.ecs.register::<AreaOfEffect>();
+        gs.ecs.register::<ProvidesHealing>();
+        gs.ecs.register::<InflictsDamage>();
+        gs.ecs.register::<InBackpack>();
+        gs.ecs.register::<WantsToPickupItem>();
+        gs.ecs.register::<WantsToDropItem>();
+        gs.ecs.register::<WantsToUseItem>();
+        gs.ecs.register::<Viewshed>();
+        gs.ecs.register::<Name>();
+        gs.ecs.register::<BlocksTile>();
+        gs.ecs.register::<SimpleMarker<SerializeMe>>();
+        gs.ecs.register::<SerializationHelper>();
+        gs.ecs.register::<Equippable>();
+        gs.ecs.register::<Equipped>();
+        gs.ecs.register::<ParticleLifetime>();
+        gs.ecs.register::<HungerClock>();
+        gs.ecs.register::<ProvidesFood>();
+        gs.ecs.register::<MagicMapper>();
+        gs.ecs.register::<OtherLevelPosition>();
+        gs.ecs.register::<BlocksVisibility>();
+        gs.ecs.register::<Door>();
+        gs.ecs.register::<Attributes>();
+        gs.ecs.register::<Skills>();
+        gs.ecs.register::<NaturalAttackDefense>();
+        gs.ecs.register::<Experience>();
+        gs.ecs.register::<StatusEffect>();
+        gs.ecs.register::<RangedWeapon>();
+        gs.ecs.register::<Ammunition>();
+        gs.ecs.register::<WantsToShoot>();
+
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        gs.ecs.insert(particle_system::ParticleBuilder::new());
+        gs.ecs.insert(effects::EffectQueue::new());
+        gs.ecs.insert(saveload_system::SaveMode { permadeath: true, format: saveload_system::SaveFormat::Json, autosave: true });
+        gs.ecs.insert(saveload_system::LoadStatus { error: None });
+        gs.ecs.insert(gamelog::GameLog { entries: Vec::new() });
+        gs.ecs.insert(MasterDungeonMap::new());
+
+        gs
+    }
+
+    /// Runs one step of the states that need neither input nor a display,
+    /// returning the state to move to. Other states are returned unchanged.
+    pub fn advance(&mut self, runstate: RunState) -> RunState {
+        match runstate {
+            RunState::PreRun => {
+                self.run_systems();
+                self.ecs.maintain();
+                // a new level (or game) has just started
+                saveload_system::autosave(&mut self.ecs);
+                RunState::AwaitingInput
+            }
+
+            RunState::PlayerTurn => {
+                self.run_systems();
+                self.ecs.maintain();
+                self.ecs.write_resource::<TurnCounter>().turns += 1;
+                // haste gives the player every other turn for free
+                if self.player_has(StatusKind::Haste) && self.ecs.fetch::<TurnCounter>().turns % 2 == 0 {
+                    self.autosave_if_due();
+                    return RunState::AwaitingInput;
+                }
+                RunState::MonsterTurn
+            }
+
+            RunState::MonsterTurn => {
+                self.run_systems();
+                self.ecs.maintain();
+                // hasted monsters act again once their first attack has landed
+                self.run_monster_systems(true);
+                // and slow gives the monsters every other turn twice
+                if self.player_has(StatusKind::Slow) && self.ecs.fetch::<TurnCounter>().turns % 2 == 0 {
+                    self.run_monster_systems(false);
+                    self.run_monster_systems(true);
+                }
+                self.autosave_if_due();
+                if level_up_due(&self.ecs) { RunState::LevelUp } else { RunState::AwaitingInput }
+            }
+
+            RunState::NextLevel => {
+                self.change_level(1);
+                self.show_generation(RunState::PreRun)
+            }
+
+            RunState::PreviousLevel => {
+                self.change_level(-1);
+                self.show_generation(RunState::PreRun)
+            }
+
+            _ => runstate
+        }
+    }
+
+    /// Lets the monsters (or only the hasted ones) act again without ticking
+    /// anything else (statuses, hunger, the player's items) a second time
+    fn run_monster_systems(&mut self, hasted_only: bool) {
+        let mut vis = VisibilitySystem{};
+        vis.run_now(&self.ecs);
+
+        let mut mob = MonsterAI{ hasted_only };
+        mob.run_now(&self.ecs);
+
+        let mut mapindex = MapIndexingSystem{};
+        mapindex.run_now(&self.ecs);
+
+        let mut melee = MeleeCombatSystem{};
+        melee.run_now(&self.ecs);
+
+        effects::run_effects_queue(&mut self.ecs);
+
+        let mut particles = particle_system::ParticleSpawnSystem{};
+        particles.run_now(&self.ecs);
+
+        self.ecs.maintain();
+    }
+
+    /// Autosaves at the end of every `AUTOSAVE_INTERVAL`th turn, whether
+    /// the turn ends after the monsters move or (when hasted) before
+    fn autosave_if_due(&mut self) {
+        if self.ecs.fetch::<TurnCounter>().turns % saveload_system::AUTOSAVE_INTERVAL == 0 {
+            saveload_system::autosave(&mut self.ecs);
+        }
+    }
+
+    fn player_has(&self, kind: StatusKind) -> bool {
+        has_status(&self.ecs.read_storage::<StatusEffect>(), *self.ecs.fetch::<Entity>(), kind)
+    }
+
+    /// Replays the generation of the level just built before moving on
+    /// to `next`, if the visualizer is on
+    fn show_generation(&mut self, next: RunState) -> RunState {
+        if self.mapgen_history.is_empty() { return next; }
+
+        self.mapgen_next_state = Some(next);
+        self.mapgen_index = 0;
+        self.mapgen_timer = 0.0;
+        RunState::MapGeneration
+    }
+
     fn run_systems(&mut self) {
         let mut vis = VisibilitySystem{};
         vis.run_now(&self.ecs);
 
-        let mut mob = MonsterAI{};
+        let mut mob = MonsterAI{ hasted_only: false };
         mob.run_now(&self.ecs);
 
         let mut mapindex = MapIndexingSystem{};
@@ -253,8 +506,8 @@ impl State {
         let mut melee = MeleeCombatSystem{};
         melee.run_now(&self.ecs);
 
-        let mut damage = DamageSystem{};
-        damage.run_now(&self.ecs);
+        let mut ranged = RangedCombatSystem{};
+        ranged.run_now(&self.ecs);
 
         let mut pickup = ItemCollectionSystem{};
         pickup.run_now(&self.ecs);
@@ -262,71 +515,46 @@ impl State {
         let mut drop = ItemDropSystem{};
         drop.run_now(&self.ecs);
 
+        let mut equip = ItemEquipOnUse{};
+        equip.run_now(&self.ecs);
+
         let mut consumed = ItemUseSystem{};
         consumed.run_now(&self.ecs);
 
-        let mut particles = particle_system::ParticleSpawnSystem{};
-        particles.run_now(&self.ecs);
+        let mut statuses = status_system::StatusEffectSystem{};
+        statuses.run_now(&self.ecs);
 
         let mut hunger = hunger_system::HungerSystem{};
         hunger.run_now(&self.ecs);
 
-        self.ecs.maintain();
-    }
+        effects::run_effects_queue(&mut self.ecs);
 
-    fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
-        let entities = self.ecs.entities();
-        let player = self.ecs.read_storage::<Player>();
-        let backpack = self.ecs.read_storage::<InBackpack>();
-        let player_entity = self.ecs.fetch::<Entity>();
-        let equipment = self.ecs.read_storage::<Equipped>();
-
-        let mut to_delete : Vec<Entity> = Vec::new();
-        for entity in entities.join() {
-            let mut should_delete = true;
-            
-            // don't delete the player
-            let p = player.get(entity);
-            if let Some(_p) = p { should_delete = false; }
-
-            // don't delete the player's equipment
-            let bp = backpack.get(entity);
-            if let Some(bp) = bp { 
-                if bp.owner == *player_entity { should_delete = false; }
-            }
-
-            let eq = equipment.get(entity);
-            if let Some(eq) = eq {
-                if eq.owner == *player_entity { should_delete = false; }
-            }
-
-            if should_delete { to_delete.push(entity); }
-        }
+        let mut particles = particle_system::ParticleSpawnSystem{};
+        particles.run_now(&self.ecs);
 
-        to_delete
+        self.ecs.maintain();
     }
 
-    fn descend(&mut self) {
-        // delete the entities that aren't the player or equipment
-        let to_delete = self.entities_to_remove_on_level_change();
-        for target in to_delete {
-            self.ecs.delete_entity(target).expect("Unable to delete entity");
-        }
-
-        // build a new map and place the player
-        let mut builder;
+    /// Moves the player `offset` levels down (or up, if negative). The
+    /// level being left is frozen; a level visited before comes back as it
+    /// was left, otherwise a new one is built.
+    fn change_level(&mut self, offset: i32) {
+        self.mapgen_history.clear();
+        dungeon::freeze_level_entities(&mut self.ecs);
+        let depth;
         {
-            let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            builder = map_builders::random_builder(worldmap_resource.depth + 1);
-            builder.build_map();
-            *worldmap_resource = builder.get_map();
+            let map = self.ecs.fetch::<Map>();
+            self.ecs.fetch_mut::<MasterDungeonMap>().store_map(&map);
+            depth = map.depth + offset;
         }
-        
-        // spawn some bad gusys
-        builder.spawn_entities(&mut self.ecs);
+
+        let stored_map = self.ecs.fetch::<MasterDungeonMap>().get_map(depth);
+        let start = match stored_map {
+            Some(map) => self.return_to_level(map, offset),
+            None => self.build_level(depth),
+        };
 
         // place the player and update resources
-        let start = builder.get_starting_pos();
         let mut pos = self.ecs.write_resource::<Point>();
         *pos = Point::new(start.x, start.y);
         let mut positions = self.ecs.write_storage::<Position>();
@@ -341,8 +569,44 @@ impl State {
         let mut viewsheds = self.ecs.write_storage::<Viewshed>();
         let vs = viewsheds.get_mut(*player_entity);
         if let Some(vs) = vs { vs.dirty = true; }
+    }
+
+    /// Restores a stored level and returns the stairs the player arrives on
+    fn return_to_level(&mut self, mut map: Map, offset: i32) -> Position {
+        let arrival = if offset > 0 { TileType::UpStairs } else { TileType::DownStairs };
+        let (x, y) = dungeon::find_tile(&map, arrival).expect("Stored level has no stairs");
+        let depth = map.depth;
+        let map_tile_count = (map.width * map.height) as usize;
+        map.visible = vec![false; map_tile_count];
+        map.contents = vec![Vec::new(); map_tile_count];
+        self.ecs.insert(map);
+        dungeon::thaw_level_entities(&mut self.ecs, depth);
+
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog.entries.insert(0, format!("You return to level {}", depth));
+        Position { x, y }
+    }
+
+    /// Builds and populates a level the player hasn't been to before and
+    /// returns the starting position
+    fn build_level(&mut self, depth: i32) -> Position {
+        let mut builder;
+        {
+            let mut worldmap_resource = self.ecs.write_resource::<Map>();
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            let (width, height) = map_builders::level_size(depth, &mut rng);
+            builder = map_builders::random_builder(depth, &mut rng, width, height);
+            builder.build_data.record_history = self.show_mapgen;
+            builder.build_map(&mut rng);
+            *worldmap_resource = builder.build_data.map.clone();
+        }
+        self.mapgen_history = std::mem::take(&mut builder.build_data.history);
+
+        // spawn some bad gusys
+        builder.spawn_entities(&mut self.ecs);
 
         // notify the player and give some health
+        let player_entity = self.ecs.fetch::<Entity>();
         let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
         gamelog.entries.insert(0, "You descend to the next level and take a moment to heal".to_string());
         let mut stats = self.ecs.write_storage::<CombatStats>();
@@ -350,103 +614,66 @@ impl State {
         if let Some(player_health) = player_health {
             player_health.hp = i32::max(player_health.hp, player_health.max_hp / 2);
         }
+
+        builder.build_data.starting_position.clone().expect("No starting position")
     }
 
-    fn game_over_cleanup(&mut self) {
+    /// Clears the world and builds the first level of a new game from
+    /// the given seed.
+    fn new_game(&mut self, seed: u64) {
         let mut to_delete = Vec::new();
         for e in self.ecs.entities().join() { to_delete.push(e); }
         for d in to_delete.iter() {
             self.ecs.delete_entity(*d).expect("deletion failed");
         }
 
+        self.ecs.insert(GameSeed { seed });
+        self.ecs.insert(RandomNumberGenerator::seeded(seed));
+        self.ecs.insert(TurnCounter { turns: 0 });
+        self.ecs.insert(MasterDungeonMap::new());
+
         let mut builder;
         {
-            let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            builder = map_builders::random_builder(1);
-            builder.build_map();
-            *worldmap_resource = builder.get_map();
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            let (width, height) = map_builders::level_size(1, &mut rng);
+            builder = map_builders::random_builder(1, &mut rng, width, height);
+            builder.build_data.record_history = self.show_mapgen;
+            builder.build_map(&mut rng);
         }
+        self.mapgen_history = std::mem::take(&mut builder.build_data.history);
+        self.ecs.insert(builder.build_data.map.clone());
+
+        // place the player and update resources
+        let start = builder.build_data.starting_position.clone().expect("No starting position");
+        let player = spawner::player(&mut self.ecs, start.x, start.y);
+        self.ecs.insert(player);
+        self.ecs.insert(Point::new(start.x, start.y));
 
         // Spawn bad guys
         builder.spawn_entities(&mut self.ecs);
 
-        // place the payer and update resources
-        let start = builder.get_starting_pos();
-        let player = spawner::player(&mut self.ecs, start.x, start.y);
-        let mut pos = self.ecs.write_resource::<Point>();
-        *pos = Point::new(start.x, start.y);
-        let mut positions = self.ecs.write_storage::<Position>();
-        let mut player_entity_writer = self.ecs.write_resource::<Entity>();
-        *player_entity_writer = player;
-        let player_pos_comp = positions.get_mut(player);
-        if let Some(player_pos_comp) = player_pos_comp {
-            player_pos_comp.x = start.x;
-            player_pos_comp.y = start.y;
-        }
-
-        // mark the player's visibility as dirty
-        let mut viewsheds = self.ecs.write_storage::<Viewshed>();
-        let vs = viewsheds.get_mut(player);
-        if let Some(vs) = vs { vs.dirty = true; }
+        self.ecs.insert(gamelog::GameLog{ entries: vec![
+            format!("Game seed: {}", seed),
+            "Welcome to Rusty Roguelike".to_string()] });
     }
 }
 
 fn main() {
+    if let Some(script) = headless::script_from_args() {
+        let seed = game_seed::seed_from_args().unwrap_or_else(game_seed::new_seed);
+        headless::simulate(seed, &script);
+        return;
+    }
+
     let mut context = Rltk::init_simple8x8(80, 50, "Hello Rust World!", "resources");
     context.with_post_scanlines(true);
 
-    let mut gs = State { ecs: World::new() };
-
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<CombatStats>();
-    gs.ecs.register::<WantsToMelee>();
-    gs.ecs.register::<SufferDamage>();
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Item>();
-    gs.ecs.register::<Consumable>();
-    gs.ecs.register::<MeleePowerBonus>();
-    gs.ecs.register::<DefenseBonus>();
-    gs.ecs.register::<Ranged>();
-    gs.ecs.register::<Confusion>();
-    gs.ecs.register::<AreaOfEffect>();
-    gs.ecs.register::<ProvidesHealing>();
-    gs.ecs.register::<InflictsDamage>();
-    gs.ecs.register::<InBackpack>();
-    gs.ecs.register::<WantsToPickupItem>();
-    gs.ecs.register::<WantsToDropItem>();
-    gs.ecs.register::<WantsToUseItem>();
-    gs.ecs.register::<Viewshed>();
-    gs.ecs.register::<Name>();
-    gs.ecs.register::<BlocksTile>();
-    gs.ecs.register::<SimpleMarker<SerializeMe>>();
-    gs.ecs.register::<SerializationHelper>();
-    gs.ecs.register::<Equippable>();
-    gs.ecs.register::<Equipped>();
-    gs.ecs.register::<ParticleLifetime>();
-    gs.ecs.register::<HungerClock>();
-    gs.ecs.register::<ProvidesFood>();
-    gs.ecs.register::<MagicMapper>();
-
-    gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
-
-    let mut builder = map_builders::random_builder(1);
-    builder.build_map();
-    let map = builder.get_map();
-    let start_pos = builder.get_starting_pos();
-    let (px, py) = (start_pos.x, start_pos.y);
-    let player_entity = spawner::player(&mut gs.ecs, px, py);
-
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
-    builder.spawn_entities(&mut gs.ecs);
-
-    gs.ecs.insert(map);
-    gs.ecs.insert(player_entity);
-    gs.ecs.insert(Point::new(px, py));
+    raws::load_raws();
+
+    let mut gs = State::new();
+    // watch each level being generated, for tuning the builders
+    gs.show_mapgen = std::env::args().any(|arg| arg == "--show-mapgen");
     gs.ecs.insert(RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame });
-    gs.ecs.insert(gamelog::GameLog{ entries: vec!["Welcome to Rusty Roguelike".to_string()] });
-    gs.ecs.insert(particle_system::ParticleBuilder::new());
 
     rltk::main_loop(context, gs);
 }
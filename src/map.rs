@@ -1,5 +1,5 @@
 extern crate rltk;
-use rltk::{ RGB, Rltk, Console, BaseMap, Algorithm2D, Point };
+use rltk::{ BaseMap, Algorithm2D, Point };
 
 use super::{Rect};
 
@@ -11,15 +11,39 @@ use specs::prelude::*;
 extern crate serde;
 use serde::{ Serialize, Deserialize };
 
-pub const MAPWIDTH : usize = 80;
-pub const MAPHEIGHT : usize = 50;
-pub const MAPCOUNT : usize = MAPHEIGHT * MAPWIDTH;
-
-#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
     DownStairs,
+    UpStairs,
+    ShallowWater,
+    DeepWater,
+    Grass,
+    Gravel,
+    Bridge,
+    WoodFloor,
+    Road,
+}
+
+/// True if creatures can walk on the tile
+pub fn tile_walkable(tt: TileType) -> bool {
+    !matches!(tt, TileType::Wall | TileType::DeepWater)
+}
+
+/// True if the tile blocks line of sight
+pub fn tile_opaque(tt: TileType) -> bool {
+    tt == TileType::Wall
+}
+
+/// How much moving onto the tile costs, relative to plain floor
+pub fn tile_cost(tt: TileType) -> f32 {
+    match tt {
+        TileType::Road => 0.8,
+        TileType::Grass => 1.1,
+        TileType::ShallowWater => 1.2,
+        _ => 1.0
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -36,60 +60,68 @@ pub struct Map {
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub contents : Vec<Vec<Entity>>,
+
+    /// Tiles something stands on that blocks sight
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub view_blocked : HashSet<usize>,
+
+    /// Tiles with a closed door, which monsters can path through by
+    /// opening it
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub closed_doors : HashSet<usize>,
 }
 
 impl Map {
-    /// Generates an empty map consisting entirely of solid walls
-    pub fn new(depth: i32) -> Map {
+    /// Generates an empty map of the given size consisting entirely of
+    /// solid walls
+    pub fn new(depth: i32, width: i32, height: i32) -> Map {
+        let map_tile_count = (width * height) as usize;
         Map {
-            tiles: vec![TileType::Wall; MAPCOUNT],
-            width: MAPWIDTH as i32,
-            height: MAPHEIGHT as i32,
-            revealed: vec![false; MAPCOUNT],
-            visible: vec![false; MAPCOUNT],
-            blocked: vec![false; MAPCOUNT],
+            tiles: vec![TileType::Wall; map_tile_count],
+            width,
+            height,
+            revealed: vec![false; map_tile_count],
+            visible: vec![false; map_tile_count],
+            blocked: vec![false; map_tile_count],
             depth,
             bloodstains: HashSet::new(),
-            contents: vec![Vec::new(); MAPCOUNT],
+            contents: vec![Vec::new(); map_tile_count],
+            view_blocked: HashSet::new(),
+            closed_doors: HashSet::new(),
         }
     }
 
     /// Converts coordinates into a linear index
-    pub fn xy_idx(x: i32, y: i32) -> usize {
-        (y as usize * MAPWIDTH) + x as usize
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
+        (y as usize * self.width as usize) + x as usize
     }
 
     /// Makes a map with solid walls and 400 randomly placed
     /// squares.
-    pub fn new_map_random(depth: i32) -> Map {
-        let mut map = Map {
-            tiles : vec![TileType::Wall; MAPCOUNT],
-            revealed : vec![false; MAPCOUNT],
-            visible : vec![false; MAPCOUNT],
-            blocked: vec![false; MAPCOUNT],
-            width : MAPWIDTH as i32,
-            height : MAPHEIGHT as i32,
-            depth,
-            bloodstains: HashSet::new(),
-            contents : vec![Vec::new(); MAPCOUNT],
-        };
+    pub fn new_map_random(depth: i32, width: i32, height: i32, rng: &mut crate::RandomNumberGenerator) -> Map {
+        let mut map = Map::new(depth, width, height);
 
         for x in 0..map.width {
-            map.tiles[Map::xy_idx(x, 0)] = TileType::Wall;
-            map.tiles[Map::xy_idx(x, map.height - 1)] = TileType::Wall;
+            let top = map.xy_idx(x, 0);
+            let bottom = map.xy_idx(x, map.height - 1);
+            map.tiles[top] = TileType::Wall;
+            map.tiles[bottom] = TileType::Wall;
         }
 
         for y in 0..map.height {
-            map.tiles[Map::xy_idx(0, y)] = TileType::Wall;
-            map.tiles[Map::xy_idx(map.width - 1, y)] = TileType::Wall;
+            let left = map.xy_idx(0, y);
+            let right = map.xy_idx(map.width - 1, y);
+            map.tiles[left] = TileType::Wall;
+            map.tiles[right] = TileType::Wall;
         }
 
-        let mut rng = rltk::RandomNumberGenerator::new();
         for _i in 0..400 {
             let x = rng.roll_dice(1, map.width - 1);
             let y = rng.roll_dice(1, map.height - 1);
-            let idx = Map::xy_idx(x, y);
-            if idx != Map::xy_idx(map.width / 2, map.height / 2) {
+            let idx = map.xy_idx(x, y);
+            if idx != map.xy_idx(map.width / 2, map.height / 2) {
                 map.tiles[idx] = TileType::Wall;
             }
         }
@@ -102,16 +134,21 @@ impl Map {
             return false 
         }
 
-        let idx = Map::xy_idx(x, y);
-        !self.blocked[idx]
+        let idx = self.xy_idx(x, y);
+        !self.blocked[idx] || self.closed_doors.contains(&idx)
     }
 
     pub fn populate_blocked(&mut self) {
         for (i, tile) in self.tiles.iter_mut().enumerate() {
-            self.blocked[i] = *tile == TileType::Wall;
+            self.blocked[i] = !tile_walkable(*tile);
         }
     }
 
+    /// The cost of stepping onto the tile at `idx`
+    fn exit_cost(&self, idx: i32) -> f32 {
+        tile_cost(self.tiles[idx as usize])
+    }
+
     pub fn clear_contents(&mut self) {
         for content in self.contents.iter_mut() {
             content.clear();
@@ -121,7 +158,8 @@ impl Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: i32) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        let idx = idx as usize;
+        tile_opaque(self.tiles[idx]) || self.view_blocked.contains(&idx)
     }
 
     fn get_available_exits(&self, idx: i32) -> Vec<(i32, f32)> {
@@ -129,15 +167,15 @@ impl BaseMap for Map {
         let x = idx % self.width;
         let y = idx / self.width;
 
-        if self.is_exit_valid(x-1, y) { exits.push((idx-1, 1.0)) };
-        if self.is_exit_valid(x+1, y) { exits.push((idx+1, 1.0)) };
-        if self.is_exit_valid(x, y-1) { exits.push((idx-self.width, 1.0)) };
-        if self.is_exit_valid(x, y+1) { exits.push((idx+self.width, 1.0)) };
+        if self.is_exit_valid(x-1, y) { exits.push((idx-1, self.exit_cost(idx-1))) };
+        if self.is_exit_valid(x+1, y) { exits.push((idx+1, self.exit_cost(idx+1))) };
+        if self.is_exit_valid(x, y-1) { exits.push((idx-self.width, self.exit_cost(idx-self.width))) };
+        if self.is_exit_valid(x, y+1) { exits.push((idx+self.width, self.exit_cost(idx+self.width))) };
 
-        if self.is_exit_valid(x-1, y-1) { exits.push(((idx-self.width)-1, 1.45)) };
-        if self.is_exit_valid(x+1, y-1) { exits.push(((idx-self.width)+1, 1.45)) };
-        if self.is_exit_valid(x-1, y+1) { exits.push(((idx+self.width)-1, 1.45)) };
-        if self.is_exit_valid(x+1, y+1) { exits.push(((idx+self.width)+1, 1.45)) };
+        if self.is_exit_valid(x-1, y-1) { exits.push(((idx-self.width)-1, 1.45 * self.exit_cost((idx-self.width)-1))) };
+        if self.is_exit_valid(x+1, y-1) { exits.push(((idx-self.width)+1, 1.45 * self.exit_cost((idx-self.width)+1))) };
+        if self.is_exit_valid(x-1, y+1) { exits.push(((idx+self.width)-1, 1.45 * self.exit_cost((idx+self.width)-1))) };
+        if self.is_exit_valid(x+1, y+1) { exits.push(((idx+self.width)+1, 1.45 * self.exit_cost((idx+self.width)+1))) };
 
         exits
     }
@@ -162,83 +200,3 @@ impl Algorithm2D for Map {
         Point { x: idx % self.width, y: idx / self.width }
     }
 }
-
-pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
-    let map = ecs.fetch::<Map>();
-
-    let mut x = 0;
-    let mut y = 0;
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        if map.revealed[idx] {
-            let glyph;
-            let mut fg;
-            let mut bg = RGB::from_f32(0.0, 0.0, 0.0);
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
-                },
-                TileType::Wall => {
-                    glyph = wall_glyph(&*map, x, y);
-                    fg = RGB::from_f32(0.6, 0.6, 0.0);
-                },
-                TileType::DownStairs => {
-                    glyph = rltk::to_cp437('>');
-                    fg = RGB::from_f32(0.0, 1.0, 1.0);
-                }
-            }
-            if map.bloodstains.contains(&idx) {
-                bg = RGB::from_f32(0.75, 0.0, 0.0); 
-            }
-
-            if !map.visible[idx] {
-                fg = fg.to_greyscale();
-                bg = RGB::from_f32(0.0, 0.0, 0.0);
-            }
-
-            ctx.set(x, y, fg, bg, glyph);
-        }
-
-        x += 1;
-        if x > 79 {
-            x = 0;
-            y += 1;
-        }
-    }
-}
-
-fn wall_glyph(map: &Map, x: i32, y: i32) -> u8 {
-    let mut mask: u8 = 0;
-
-    if is_revealed_and_wall(map, x, y-1) { mask += 1; }
-    if is_revealed_and_wall(map, x, y+1) { mask += 2; }
-    if is_revealed_and_wall(map, x-1, y) { mask += 4; }
-    if is_revealed_and_wall(map, x+1, y) { mask += 8; }
-    mask_to_glyph(mask)
-}
-
-fn mask_to_glyph(mask: u8) -> u8 {
-    match mask {
-        0 => 9,
-        1 => 186,
-        2 => 186,
-        3 => 186,
-        4 => 205,
-        5 => 188,
-        6 => 187,
-        7 => 185,
-        8 => 205,
-        9 => 200,
-        10 => 201,
-        11 => 204,
-        12 => 205,
-        13 => 202,
-        14 => 203,
-        _ => 35
-    }
-}
-
-fn is_revealed_and_wall(map: &Map, x: i32, y: i32) -> bool {
-    let idx = Map::xy_idx(x, y);
-    map.tiles[idx] == TileType::Wall && map.revealed[idx]
-}
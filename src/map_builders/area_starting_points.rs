@@ -0,0 +1,45 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    Position,
+    nearest_floor };
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum XStart { Left, Center, Right }
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum YStart { Top, Center, Bottom }
+
+/// Starts the player on the floor tile closest to a side, corner or the
+/// centre of the map
+pub struct AreaStartingPosition {
+    x: XStart,
+    y: YStart
+}
+
+impl MetaMapBuilder for AreaStartingPosition {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &build_data.map;
+        let seed_x = match self.x {
+            XStart::Left => 1,
+            XStart::Center => map.width / 2,
+            XStart::Right => map.width - 2
+        };
+        let seed_y = match self.y {
+            YStart::Top => 1,
+            YStart::Center => map.height / 2,
+            YStart::Bottom => map.height - 2
+        };
+
+        let idx = nearest_floor(map, seed_x, seed_y).expect("No floor to start on");
+        build_data.starting_position = Some(Position { x: idx as i32 % map.width, y: idx as i32 / map.width });
+    }
+}
+
+impl AreaStartingPosition {
+    pub fn new(x: XStart, y: YStart) -> Box<AreaStartingPosition> {
+        Box::new(AreaStartingPosition { x, y })
+    }
+}
@@ -0,0 +1,97 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    InitialMapBuilder,
+    BuilderMap,
+    Rect,
+    apply_room_to_map,
+    draw_corridor };
+
+/// Leaves smaller than twice this in both directions aren't split again
+const MIN_LEAF_SIZE : i32 = 8;
+const MIN_ROOM_SIZE : i32 = 3;
+
+/// Builds a level by binary space partition: the map is split in two
+/// again and again, each leaf gets a room, and every pair of siblings is
+/// joined by a corridor.
+pub struct BspDungeonBuilder {
+    rooms: Vec<Rect>,
+    corridors: Vec<Vec<usize>>
+}
+
+impl InitialMapBuilder for BspDungeonBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl BspDungeonBuilder {
+    pub fn new() -> Box<BspDungeonBuilder> {
+        Box::new(BspDungeonBuilder {
+            rooms: Vec::new(),
+            corridors: Vec::new()
+        })
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let whole_map = Rect::new(1, 1, build_data.map.width - 2, build_data.map.height - 2);
+        self.split(whole_map, rng, build_data);
+
+        build_data.rooms = Some(self.rooms.clone());
+        build_data.corridors = Some(self.corridors.clone());
+    }
+
+    /// Partitions `area`, carving a room in each leaf and joining the two
+    /// halves of every split. Returns the rooms carved inside `area`.
+    fn split(&mut self, area: Rect, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) -> Vec<Rect> {
+        let width = area.x2 - area.x1;
+        let height = area.y2 - area.y1;
+        let can_split_x = width >= MIN_LEAF_SIZE * 2;
+        let can_split_y = height >= MIN_LEAF_SIZE * 2;
+
+        if !can_split_x && !can_split_y {
+            let room = self.carve_room(area, rng, build_data);
+            return vec![room];
+        }
+
+        // prefer cutting across the longer side, so leaves stay squarish
+        let split_x = if can_split_x && can_split_y { width > height || (width == height && rng.range(0, 2) == 0) } else { can_split_x };
+        let (first, second) = if split_x {
+            let cut = rng.range(area.x1 + MIN_LEAF_SIZE, area.x2 - MIN_LEAF_SIZE + 1);
+            (Rect { x2: cut, ..area }, Rect { x1: cut, ..area })
+        } else {
+            let cut = rng.range(area.y1 + MIN_LEAF_SIZE, area.y2 - MIN_LEAF_SIZE + 1);
+            (Rect { y2: cut, ..area }, Rect { y1: cut, ..area })
+        };
+
+        let mut rooms = self.split(first, rng, build_data);
+        let second_rooms = self.split(second, rng, build_data);
+
+        // join the siblings through a room picked from each side
+        let from = rooms[rng.range(0, rooms.len() as i32) as usize].center();
+        let to = second_rooms[rng.range(0, second_rooms.len() as i32) as usize].center();
+        let corridor = draw_corridor(&mut build_data.map, rng, from, to);
+        self.corridors.push(corridor);
+        build_data.take_snapshot();
+
+        rooms.extend(second_rooms);
+        rooms
+    }
+
+    /// Places a randomly sized room inside the leaf, leaving a wall between
+    /// it and its neighbours
+    fn carve_room(&mut self, leaf: Rect, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) -> Rect {
+        let width = leaf.x2 - leaf.x1;
+        let height = leaf.y2 - leaf.y1;
+        let w = rng.range(MIN_ROOM_SIZE, width - 1);
+        let h = rng.range(MIN_ROOM_SIZE, height - 1);
+        let x = leaf.x1 + 1 + rng.range(0, width - w - 1);
+        let y = leaf.y1 + 1 + rng.range(0, height - h - 1);
+
+        let room = Rect::new(x, y, w - 1, h - 1);
+        apply_room_to_map(&mut build_data.map, &room);
+        build_data.take_snapshot();
+        self.rooms.push(room);
+        room
+    }
+}
@@ -0,0 +1,64 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    InitialMapBuilder,
+    BuilderMap,
+    Map,
+    TileType };
+
+const SMOOTHING_ITERATIONS : i32 = 15;
+
+/// Builds organic caves by filling the map with noise and repeatedly
+/// smoothing it: a tile becomes wall when most of its neighbours are.
+pub struct CellularAutomataBuilder {}
+
+impl InitialMapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl CellularAutomataBuilder {
+    pub fn new() -> Box<CellularAutomataBuilder> {
+        Box::new(CellularAutomataBuilder {})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // start with roughly 55% floor, keeping the outer edge solid
+        let map = &mut build_data.map;
+        for y in 1 .. map.height - 1 {
+            for x in 1 .. map.width - 1 {
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = if rng.roll_dice(1, 100) > 55 { TileType::Floor } else { TileType::Wall };
+            }
+        }
+        build_data.take_snapshot();
+
+        for _i in 0 .. SMOOTHING_ITERATIONS {
+            self.smooth(&mut build_data.map);
+            build_data.take_snapshot();
+        }
+    }
+
+    fn smooth(&mut self, map: &mut Map) {
+        let mut newtiles = map.tiles.clone();
+
+        for y in 1 .. map.height - 1 {
+            for x in 1 .. map.width - 1 {
+                let mut neighbors = 0;
+                for dy in -1 ..= 1 {
+                    for dx in -1 ..= 1 {
+                        if (dx != 0 || dy != 0) && map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall {
+                            neighbors += 1;
+                        }
+                    }
+                }
+
+                let idx = map.xy_idx(x, y);
+                newtiles[idx] = if neighbors > 4 || neighbors == 0 { TileType::Wall } else { TileType::Floor };
+            }
+        }
+
+        map.tiles = newtiles;
+    }
+}
@@ -1,26 +1,178 @@
-use super::{ Map, Rect, TileType };
+use super::{ Map, Rect, TileType, Position, tile_walkable };
 use std::cmp::{ max, min };
+use std::collections::{ BTreeMap, VecDeque };
+use rltk::BaseMap;
 
 pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
     for x in room.x1 ..= room.x2 {
         for y in room.y1 ..= room.y2 {
-            let idx = Map::xy_idx(x, y);
+            let idx = map.xy_idx(x, y);
             map.tiles[idx] = TileType::Floor;
         }
     }
 }
 
-pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) {
+/// Digs a horizontal tunnel, returning the tiles that were dug out
+pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) -> Vec<usize> {
+    let mut corridor = Vec::new();
     for x in min(x1, x2) ..= max(x1, x2) {
-        let idx = Map::xy_idx(x, y);
-        map.tiles[idx] = TileType::Floor;
+        let idx = map.xy_idx(x, y);
+        if map.tiles[idx] != TileType::Floor {
+            map.tiles[idx] = TileType::Floor;
+            corridor.push(idx);
+        }
     }
+    corridor
 }
 
-pub fn apply_vertical_tunnel(map: &mut Map, x: i32, y1: i32, y2: i32) {
+/// Digs a vertical tunnel, returning the tiles that were dug out
+pub fn apply_vertical_tunnel(map: &mut Map, x: i32, y1: i32, y2: i32) -> Vec<usize> {
+    let mut corridor = Vec::new();
     for y in min(y1, y2) ..= max(y1, y2) {
-        let idx = Map::xy_idx(x, y);
-        map.tiles[idx] = TileType::Floor;
+        let idx = map.xy_idx(x, y);
+        if map.tiles[idx] != TileType::Floor {
+            map.tiles[idx] = TileType::Floor;
+            corridor.push(idx);
+        }
+    }
+    corridor
+}
+
+/// Digs an L-shaped corridor between two points, turning at one of the
+/// two corners at random. Returns the tiles that were dug out.
+pub fn draw_corridor(map: &mut Map, rng: &mut crate::RandomNumberGenerator, from: (i32, i32), to: (i32, i32)) -> Vec<usize> {
+    let (fx, fy) = from;
+    let (tx, ty) = to;
+    let mut corridor;
+    if rng.range(0, 2) == 1 {
+        corridor = apply_horizontal_tunnel(map, fx, tx, fy);
+        corridor.extend(apply_vertical_tunnel(map, tx, fy, ty));
+    } else {
+        corridor = apply_vertical_tunnel(map, fx, fy, ty);
+        corridor.extend(apply_horizontal_tunnel(map, fx, tx, ty));
+    }
+    corridor
+}
+
+
+/// Marks the way back up at the start of every level below the first
+pub fn apply_up_stairs(map: &mut Map, start: &Position) {
+    if map.depth > 1 {
+        let idx = map.xy_idx(start.x, start.y);
+        map.tiles[idx] = TileType::UpStairs;
+    }
+}
+
+/// Walls off everything that can't be reached from `start_idx` and
+/// returns the index of the reachable tile furthest from it.
+pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    map.populate_blocked();
+    let distances = dijkstra_flood(map, start_idx);
+
+    let mut exit_tile = (start_idx, 0.0f32);
+    for (i, tile) in map.tiles.iter_mut().enumerate() {
+        if tile_walkable(*tile) {
+            let distance_to_start = distances[i];
+            if distance_to_start == f32::MAX {
+                *tile = TileType::Wall;
+            } else if *tile == TileType::Floor && distance_to_start > exit_tile.1 {
+                exit_tile = (i, distance_to_start);
+            }
+        }
+    }
+    map.populate_blocked();
+
+    exit_tile.0
+}
+
+/// Finds the floor tile closest to the given point, if there is any floor
+pub fn nearest_floor(map: &Map, x: i32, y: i32) -> Option<usize> {
+    map.tiles.iter().enumerate()
+        .filter(|(_idx, tile)| **tile == TileType::Floor)
+        .map(|(idx, _tile)| idx)
+        .min_by_key(|idx| {
+            let tile_x = *idx as i32 % map.width;
+            let tile_y = *idx as i32 / map.width;
+            (tile_x - x).abs() + (tile_y - y).abs()
+        })
+}
+
+/// Returns the number of moves from `start_idx` to every tile, or
+/// `f32::MAX` where it can't be reached. rltk's `DijkstraMap` floods
+/// depth-first, so its distances can be far longer than the real path.
+pub fn dijkstra_flood(map: &Map, start_idx: usize) -> Vec<f32> {
+    let mut distances = vec![f32::MAX; map.tiles.len()];
+    let mut open_list = VecDeque::new();
+    distances[start_idx] = 0.0;
+    open_list.push_back(start_idx);
+
+    while let Some(idx) = open_list.pop_front() {
+        let depth = distances[idx] + 1.0;
+        for (exit, _cost) in map.get_available_exits(idx as i32) {
+            if distances[exit as usize] == f32::MAX {
+                distances[exit as usize] = depth;
+                open_list.push_back(exit as usize);
+            }
+        }
+    }
+
+    distances
+}
+
+/// Splits the floor into noise-shaped regions for spawning, for maps
+/// that have no rooms. Regions are keyed by their noise cell.
+pub fn generate_voronoi_spawn_regions(map: &Map, rng: &mut crate::RandomNumberGenerator) -> BTreeMap<i32, Vec<usize>> {
+    let mut noise_areas : BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    let mut noise = rltk::FastNoise::seeded(rng.next_u64());
+    noise.set_noise_type(rltk::NoiseType::Cellular);
+    noise.set_frequency(0.08);
+    noise.set_cellular_distance_function(rltk::CellularDistanceFunction::Manhattan);
+
+    for y in 1 .. map.height - 1 {
+        for x in 1 .. map.width - 1 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor {
+                let cell_value = (noise.get_noise(x as f32, y as f32) * 10240.0) as i32;
+                noise_areas.entry(cell_value).or_default().push(idx);
+            }
+        }
+    }
+
+    noise_areas
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Symmetry { None, Horizontal, Vertical, Both }
+
+/// Carves floor at the given point with a square brush, mirrored around
+/// the centre of the map as `mode` asks.
+pub fn paint(map: &mut Map, mode: Symmetry, brush_size: i32, x: i32, y: i32) {
+    let center_x = map.width / 2;
+    let center_y = map.height / 2;
+    let xs = match mode {
+        Symmetry::Horizontal | Symmetry::Both => vec![center_x - (x - center_x).abs(), center_x + (x - center_x).abs()],
+        _ => vec![x]
+    };
+    let ys = match mode {
+        Symmetry::Vertical | Symmetry::Both => vec![center_y - (y - center_y).abs(), center_y + (y - center_y).abs()],
+        _ => vec![y]
+    };
+
+    for paint_y in ys.iter() {
+        for paint_x in xs.iter() {
+            apply_paint(map, brush_size, *paint_x, *paint_y);
+        }
     }
 }
 
+fn apply_paint(map: &mut Map, brush_size: i32, x: i32, y: i32) {
+    let half_brush = brush_size / 2;
+    for brush_y in y - half_brush ..= y + (brush_size - 1) / 2 {
+        for brush_x in x - half_brush ..= x + (brush_size - 1) / 2 {
+            if brush_x > 0 && brush_x < map.width - 1 && brush_y > 0 && brush_y < map.height - 1 {
+                let idx = map.xy_idx(brush_x, brush_y);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    tile_walkable,
+    remove_unreachable_areas_returning_most_distant };
+
+/// Walls off every part of the map the player can't walk to from the
+/// start, along with anything due to spawn there
+pub struct CullUnreachable {}
+
+impl MetaMapBuilder for CullUnreachable {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data.starting_position.as_ref().expect("Culling unreachable areas needs a starting position");
+        let start_idx = build_data.map.xy_idx(start.x, start.y);
+        remove_unreachable_areas_returning_most_distant(&mut build_data.map, start_idx);
+
+        let map = &build_data.map;
+        build_data.spawn_list.retain(|(idx, _name)| tile_walkable(map.tiles[*idx]));
+    }
+}
+
+impl CullUnreachable {
+    pub fn new() -> Box<CullUnreachable> {
+        Box::new(CullUnreachable {})
+    }
+}
@@ -0,0 +1,33 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    TileType,
+    dijkstra_flood };
+
+/// Puts the way down on the reachable tile furthest from the start
+pub struct DistantExit {}
+
+impl MetaMapBuilder for DistantExit {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data.starting_position.as_ref().expect("A distant exit needs a starting position");
+        let start_idx = build_data.map.xy_idx(start.x, start.y);
+        build_data.map.populate_blocked();
+        let distances = dijkstra_flood(&build_data.map, start_idx);
+
+        let mut exit_tile = (start_idx, 0.0f32);
+        for (i, distance) in distances.iter().enumerate() {
+            if build_data.map.tiles[i] == TileType::Floor && *distance != f32::MAX && *distance > exit_tile.1 {
+                exit_tile = (i, *distance);
+            }
+        }
+        build_data.map.tiles[exit_tile.0] = TileType::DownStairs;
+    }
+}
+
+impl DistantExit {
+    pub fn new() -> Box<DistantExit> {
+        Box::new(DistantExit {})
+    }
+}
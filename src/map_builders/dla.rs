@@ -0,0 +1,151 @@
+use rltk::{ Point, LineAlg };
+use crate::RandomNumberGenerator;
+
+use super::{
+    InitialMapBuilder,
+    BuilderMap,
+    Map,
+    TileType,
+    Symmetry,
+    paint };
+
+/// Particles released between visualizer snapshots
+const SNAPSHOT_INTERVAL : i32 = 25;
+
+/// How particles reach the growing cave: wandering in from a random
+/// point until they hit floor, wandering out from the centre until they
+/// hit wall, or flying straight at the centre.
+#[derive(PartialEq, Copy, Clone)]
+pub enum DLAAlgorithm { WalkInwards, WalkOutwards, CentralAttractor }
+
+/// Grows a level by diffusion-limited aggregation: particles are released
+/// one at a time and stick where they first touch the existing cave.
+pub struct DLABuilder {
+    algorithm: DLAAlgorithm,
+    brush_size: i32,
+    symmetry: Symmetry,
+    floor_percent: f32
+}
+
+impl InitialMapBuilder for DLABuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl DLABuilder {
+    pub fn new(algorithm: DLAAlgorithm, brush_size: i32, symmetry: Symmetry) -> Box<DLABuilder> {
+        Box::new(DLABuilder {
+            algorithm,
+            brush_size,
+            symmetry,
+            floor_percent: 0.25
+        })
+    }
+
+    pub fn walk_inwards() -> Box<DLABuilder> {
+        DLABuilder::new(DLAAlgorithm::WalkInwards, 1, Symmetry::None)
+    }
+
+    pub fn walk_outwards() -> Box<DLABuilder> {
+        DLABuilder::new(DLAAlgorithm::WalkOutwards, 2, Symmetry::None)
+    }
+
+    pub fn central_attractor() -> Box<DLABuilder> {
+        DLABuilder::new(DLAAlgorithm::CentralAttractor, 2, Symmetry::None)
+    }
+
+    /// A central attractor mirrored left to right, which tends to look
+    /// like a bug
+    pub fn insectoid() -> Box<DLABuilder> {
+        DLABuilder::new(DLAAlgorithm::CentralAttractor, 2, Symmetry::Horizontal)
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // seed the cave with a small cross at the centre
+        let map = &mut build_data.map;
+        let (sx, sy) = (map.width / 2, map.height / 2);
+        for (x, y) in [(sx, sy), (sx - 1, sy), (sx + 1, sy), (sx, sy - 1), (sx, sy + 1)].iter() {
+            let idx = map.xy_idx(*x, *y);
+            map.tiles[idx] = TileType::Floor;
+        }
+
+        let total_tiles = map.width * map.height;
+        let desired_floor_tiles = (self.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = count_floor(map);
+        let mut particles = 0;
+        while floor_tile_count < desired_floor_tiles {
+            let map = &mut build_data.map;
+            match self.algorithm {
+                DLAAlgorithm::WalkInwards => self.walk_in(rng, map),
+                DLAAlgorithm::WalkOutwards => self.walk_out(rng, map),
+                DLAAlgorithm::CentralAttractor => self.attract(rng, map),
+            }
+            floor_tile_count = count_floor(map);
+
+            particles += 1;
+            if particles % SNAPSHOT_INTERVAL == 0 {
+                build_data.take_snapshot();
+            }
+        }
+    }
+
+    fn random_point(&self, rng: &mut RandomNumberGenerator, map: &Map) -> (i32, i32) {
+        (rng.roll_dice(1, map.width - 3) + 1, rng.roll_dice(1, map.height - 3) + 1)
+    }
+
+    /// Moves one step in a random direction, staying clear of the edges
+    fn stagger(&self, x: &mut i32, y: &mut i32, rng: &mut RandomNumberGenerator, map: &Map) {
+        match rng.roll_dice(1, 4) {
+            1 => if *x > 2 { *x -= 1; }
+            2 => if *x < map.width - 2 { *x += 1; }
+            3 => if *y > 2 { *y -= 1; }
+            _ => if *y < map.height - 2 { *y += 1; }
+        }
+    }
+
+    fn walk_in(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map) {
+        let (mut digger_x, mut digger_y) = self.random_point(rng, map);
+        let (mut prev_x, mut prev_y) = (digger_x, digger_y);
+        while is_wall(map, digger_x, digger_y) {
+            prev_x = digger_x;
+            prev_y = digger_y;
+            self.stagger(&mut digger_x, &mut digger_y, rng, map);
+        }
+        paint(map, self.symmetry, self.brush_size, prev_x, prev_y);
+    }
+
+    fn walk_out(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map) {
+        let (mut digger_x, mut digger_y) = (map.width / 2, map.height / 2);
+        while !is_wall(map, digger_x, digger_y) {
+            self.stagger(&mut digger_x, &mut digger_y, rng, map);
+        }
+        paint(map, self.symmetry, self.brush_size, digger_x, digger_y);
+    }
+
+    fn attract(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map) {
+        let (mut digger_x, mut digger_y) = self.random_point(rng, map);
+        let (mut prev_x, mut prev_y) = (digger_x, digger_y);
+        let path = rltk::line2d(
+            LineAlg::Bresenham,
+            Point::new(digger_x, digger_y),
+            Point::new(map.width / 2, map.height / 2));
+
+        for step in path.iter() {
+            if !is_wall(map, digger_x, digger_y) { break; }
+            prev_x = digger_x;
+            prev_y = digger_y;
+            digger_x = step.x;
+            digger_y = step.y;
+        }
+        paint(map, self.symmetry, self.brush_size, prev_x, prev_y);
+    }
+}
+
+fn is_wall(map: &Map, x: i32, y: i32) -> bool {
+    map.tiles[map.xy_idx(x, y)] == TileType::Wall
+}
+
+fn count_floor(map: &Map) -> usize {
+    map.tiles.iter().filter(|t| **t == TileType::Floor).count()
+}
@@ -0,0 +1,58 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    TileType };
+
+/// Hangs a door at each end of every corridor, where it opens into a room
+pub struct DoorPlacement {}
+
+impl MetaMapBuilder for DoorPlacement {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let corridors = match &build_data.corridors {
+            Some(corridors) => corridors.clone(),
+            None => return
+        };
+
+        for corridor in corridors.iter() {
+            for idx in [corridor.first(), corridor.last()].iter().flatten() {
+                if door_possible(build_data, **idx) {
+                    build_data.spawn_list.push((**idx, "Door".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl DoorPlacement {
+    pub fn new() -> Box<DoorPlacement> {
+        Box::new(DoorPlacement {})
+    }
+}
+
+/// A door fits on a floor tile with walls either side and floor in
+/// front and behind, so long as nothing else (the player included) is
+/// there or next door
+fn door_possible(build_data: &BuilderMap, idx: usize) -> bool {
+    let map = &build_data.map;
+    let x = idx as i32 % map.width;
+    let y = idx as i32 / map.width;
+    if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { return false; }
+    if map.tiles[idx] != TileType::Floor { return false; }
+
+    for (spawn_idx, _name) in build_data.spawn_list.iter() {
+        let sx = *spawn_idx as i32 % map.width;
+        let sy = *spawn_idx as i32 / map.width;
+        if (sx - x).abs() <= 1 && (sy - y).abs() <= 1 { return false; }
+    }
+    if let Some(start) = &build_data.starting_position {
+        if (start.x - x).abs() <= 1 && (start.y - y).abs() <= 1 { return false; }
+    }
+
+    let wall = |dx: i32, dy: i32| map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall;
+    let floor = |dx: i32, dy: i32| map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Floor;
+
+    (wall(-1, 0) && wall(1, 0) && floor(0, -1) && floor(0, 1)) ||
+        (wall(0, -1) && wall(0, 1) && floor(-1, 0) && floor(1, 0))
+}
@@ -0,0 +1,126 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    InitialMapBuilder,
+    BuilderMap,
+    Map,
+    TileType,
+    Symmetry,
+    paint };
+
+/// Where each new digger starts: always from the centre, or anywhere on
+/// the map (the first one still starts at the centre).
+#[derive(PartialEq, Copy, Clone)]
+pub enum DrunkSpawnMode { StartingPoint, Random }
+
+pub struct DrunkardSettings {
+    pub spawn_mode: DrunkSpawnMode,
+    /// Steps each digger takes before it stops
+    pub drunken_lifetime: i32,
+    /// Diggers are sent out until this fraction of the map is floor
+    pub floor_percent: f32,
+    pub brush_size: i32,
+    pub symmetry: Symmetry,
+}
+
+/// Carves a level by sending out diggers that stumble around at random,
+/// turning everything they walk over into floor. Digging starts from the
+/// centre of the map.
+pub struct DrunkardsWalkBuilder {
+    settings: DrunkardSettings
+}
+
+impl InitialMapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(settings: DrunkardSettings) -> Box<DrunkardsWalkBuilder> {
+        Box::new(DrunkardsWalkBuilder { settings })
+    }
+
+    /// One big open cave grown from the centre
+    pub fn open_area() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::StartingPoint,
+            drunken_lifetime: 400,
+            floor_percent: 0.5,
+            brush_size: 1,
+            symmetry: Symmetry::None
+        })
+    }
+
+    /// Open caves scattered across the map
+    pub fn open_halls() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 400,
+            floor_percent: 0.5,
+            brush_size: 1,
+            symmetry: Symmetry::None
+        })
+    }
+
+    /// Lots of short-lived diggers, giving narrow twisting tunnels
+    pub fn winding_passages() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 100,
+            floor_percent: 0.4,
+            brush_size: 1,
+            symmetry: Symmetry::None
+        })
+    }
+
+    /// Wide tunnels mirrored left to right
+    pub fn fearful_symmetry() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 100,
+            floor_percent: 0.4,
+            brush_size: 2,
+            symmetry: Symmetry::Horizontal
+        })
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let (start_x, start_y) = (build_data.map.width / 2, build_data.map.height / 2);
+        let idx = build_data.map.xy_idx(start_x, start_y);
+        build_data.map.tiles[idx] = TileType::Floor;
+
+        let total_tiles = build_data.map.width * build_data.map.height;
+        let desired_floor_tiles = (self.settings.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = count_floor(&build_data.map);
+        let mut digger_count = 0;
+        while floor_tile_count < desired_floor_tiles {
+            let map = &mut build_data.map;
+            let (mut drunk_x, mut drunk_y) = match self.settings.spawn_mode {
+                DrunkSpawnMode::Random if digger_count > 0 => {
+                    (rng.roll_dice(1, map.width - 3) + 1, rng.roll_dice(1, map.height - 3) + 1)
+                }
+                _ => (start_x, start_y)
+            };
+
+            for _step in 0 .. self.settings.drunken_lifetime {
+                paint(map, self.settings.symmetry, self.settings.brush_size, drunk_x, drunk_y);
+
+                match rng.roll_dice(1, 4) {
+                    1 => if drunk_x > 2 { drunk_x -= 1; }
+                    2 => if drunk_x < map.width - 2 { drunk_x += 1; }
+                    3 => if drunk_y > 2 { drunk_y -= 1; }
+                    _ => if drunk_y < map.height - 2 { drunk_y += 1; }
+                }
+            }
+
+            digger_count += 1;
+            floor_tile_count = count_floor(map);
+            build_data.take_snapshot();
+        }
+    }
+}
+
+fn count_floor(map: &Map) -> usize {
+    map.tiles.iter().filter(|t| **t == TileType::Floor).count()
+}
@@ -1,27 +1,262 @@
-use super::{ 
+use super::{
     Map,
     World,
     Rect,
     TileType,
     Position,
+    tile_walkable,
     spawner };
 
 mod simple_map;
-use simple_map::SimpleMapBuilder;
+pub use simple_map::SimpleMapBuilder;
+
+mod bsp_dungeon;
+pub use bsp_dungeon::BspDungeonBuilder;
+
+mod cellular_automata;
+pub use cellular_automata::CellularAutomataBuilder;
+
+mod drunkard;
+pub use drunkard::DrunkardsWalkBuilder;
+
+mod dla;
+pub use dla::DLABuilder;
+
+mod waveform_collapse;
+pub use waveform_collapse::WaveformCollapseBuilder;
+
+pub mod prefab_builder;
+pub use prefab_builder::{ PrefabLevelBuilder, PrefabSectionBuilder, VaultBuilder };
+
+mod room_exploder;
+pub use room_exploder::RoomExploder;
+
+mod room_corner_rounding;
+pub use room_corner_rounding::RoomCornerRounder;
+
+mod room_corridors;
+pub use room_corridors::{ DoglegCorridors, BspCorridors, NearestCorridors, PrimCorridors };
+
+mod door_placement;
+pub use door_placement::DoorPlacement;
+
+mod room_based;
+pub use room_based::{ RoomBasedStartingPosition, RoomBasedStairs, RoomBasedSpawner };
+
+mod area_starting_points;
+pub use area_starting_points::{ AreaStartingPosition, XStart, YStart };
+
+mod terrain_features;
+pub use terrain_features::TerrainFeatures;
+
+mod cull_unreachable;
+pub use cull_unreachable::CullUnreachable;
+
+mod distant_exit;
+pub use distant_exit::DistantExit;
+
+mod voronoi_spawning;
+pub use voronoi_spawning::VoronoiSpawning;
 
 mod common;
 use common::*;
 
-use specs::prelude::*;
+use crate::RandomNumberGenerator;
 
-pub trait MapBuilder {
-    fn build_map(&mut self);
-    fn spawn_entities(&mut self, ecs: &mut World);
-    fn get_map(&self) -> Map;
-    fn get_starting_pos(&self) -> Position;
+/// Everything a builder chain has worked out about the level so far.
+/// Steps fill in what they know, and later steps read it back.
+pub struct BuilderMap {
+    /// Map index and name of everything to spawn on the level
+    pub spawn_list: Vec<(usize, String)>,
+    pub map: Map,
+    pub starting_position: Option<Position>,
+    /// The rooms, for builders that make them
+    pub rooms: Option<Vec<Rect>>,
+    /// The tiles dug for each corridor, for builders that make them
+    pub corridors: Option<Vec<Vec<usize>>>,
+    /// Snapshots of the map as it was built, for the visualizer
+    pub history: Vec<Map>,
+    /// Snapshots are only taken when this is set
+    pub record_history: bool,
 }
 
-pub fn random_builder(depth: i32) -> Box<dyn MapBuilder> {
-    Box::new(SimpleMapBuilder::new(depth))
+impl BuilderMap {
+    /// Records the map as it stands, fully revealed, if the history is
+    /// being kept and the map has changed since the last snapshot
+    pub fn take_snapshot(&mut self) {
+        let unchanged = self.history.last().is_some_and(|last| last.tiles == self.map.tiles);
+        if self.record_history && !unchanged {
+            let mut snapshot = self.map.clone();
+            for v in snapshot.revealed.iter_mut() { *v = true; }
+            for v in snapshot.visible.iter_mut() { *v = true; }
+            self.history.push(snapshot);
+        }
+    }
 }
 
+/// A level generator assembled from parts: one initial builder lays out
+/// the map, then each meta builder reworks it in turn.
+pub struct BuilderChain {
+    starter: Option<Box<dyn InitialMapBuilder>>,
+    builders: Vec<Box<dyn MetaMapBuilder>>,
+    pub build_data: BuilderMap
+}
+
+impl BuilderChain {
+    pub fn new(depth: i32, width: i32, height: i32) -> BuilderChain {
+        BuilderChain {
+            starter: None,
+            builders: Vec::new(),
+            build_data: BuilderMap {
+                spawn_list: Vec::new(),
+                map: Map::new(depth, width, height),
+                starting_position: None,
+                rooms: None,
+                corridors: None,
+                history: Vec::new(),
+                record_history: false
+            }
+        }
+    }
+
+    pub fn start_with(&mut self, starter: Box<dyn InitialMapBuilder>) {
+        match self.starter {
+            None => self.starter = Some(starter),
+            Some(_) => panic!("You can only have one starting builder.")
+        };
+    }
+
+    pub fn with(&mut self, metabuilder: Box<dyn MetaMapBuilder>) {
+        self.builders.push(metabuilder);
+    }
+
+    /// Runs the chain. The chain has to place the player somewhere; the
+    /// way back up goes where they start.
+    pub fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        match &mut self.starter {
+            None => panic!("Cannot run a map builder chain without a starting build system"),
+            Some(starter) => starter.build_map(rng, &mut self.build_data)
+        }
+        self.build_data.take_snapshot();
+
+        for metabuilder in self.builders.iter_mut() {
+            metabuilder.build_map(rng, &mut self.build_data);
+            self.build_data.take_snapshot();
+        }
+
+        let start = self.build_data.starting_position.as_ref().expect("Map builder chain never set a starting position");
+        apply_up_stairs(&mut self.build_data.map, start);
+        self.build_data.take_snapshot();
+    }
+
+    pub fn spawn_entities(&mut self, ecs: &mut World) {
+        for entity in self.build_data.spawn_list.iter() {
+            spawner::spawn_entity(ecs, entity);
+        }
+    }
+}
+
+/// Lays out a map from nothing
+pub trait InitialMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+/// Reworks a map laid out by earlier builders
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+/// Picks a random way of laying out rooms and joining them up
+fn random_room_builder(rng: &mut RandomNumberGenerator, builder: &mut BuilderChain) {
+    if rng.roll_dice(1, 3) == 1 {
+        // BSP joins its own rooms as it splits the map
+        builder.start_with(BspDungeonBuilder::new());
+    } else {
+        builder.start_with(SimpleMapBuilder::new());
+        match rng.roll_dice(1, 4) {
+            1 => builder.with(RoomExploder::new()),
+            2 => builder.with(RoomCornerRounder::new()),
+            _ => {}
+        }
+        match rng.roll_dice(1, 4) {
+            1 => builder.with(DoglegCorridors::new()),
+            2 => builder.with(BspCorridors::new()),
+            3 => builder.with(NearestCorridors::new()),
+            _ => builder.with(PrimCorridors::new())
+        }
+    }
+}
+
+/// Picks one of the builders that carve a single open shape
+fn random_shape_builder(rng: &mut RandomNumberGenerator, builder: &mut BuilderChain) {
+    match rng.roll_dice(1, 9) {
+        1 => builder.start_with(CellularAutomataBuilder::new()),
+        2 => builder.start_with(DrunkardsWalkBuilder::open_area()),
+        3 => builder.start_with(DrunkardsWalkBuilder::open_halls()),
+        4 => builder.start_with(DrunkardsWalkBuilder::winding_passages()),
+        5 => builder.start_with(DrunkardsWalkBuilder::fearful_symmetry()),
+        6 => builder.start_with(DLABuilder::walk_inwards()),
+        7 => builder.start_with(DLABuilder::walk_outwards()),
+        8 => builder.start_with(DLABuilder::central_attractor()),
+        _ => builder.start_with(DLABuilder::insectoid())
+    }
+}
+
+/// Picks the size of the map for a new level: levels grow the deeper
+/// they are, up to 120x70, and vary a little at any depth
+pub fn level_size(depth: i32, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    let width = i32::min(64 + depth * 4, 112) + rng.roll_dice(1, 9) - 1;
+    let height = i32::min(40 + depth * 3, 62) + rng.roll_dice(1, 9) - 1;
+    (width, height)
+}
+
+/// Assembles a random level generator for a map of the given size
+pub fn random_builder(depth: i32, rng: &mut RandomNumberGenerator, width: i32, height: i32) -> BuilderChain {
+    let mut builder = BuilderChain::new(depth, width, height);
+    let warren = prefab_builder::prefab_levels::GOBLIN_WARREN;
+    if rng.roll_dice(1, 20) == 1 && PrefabLevelBuilder::fits(&warren, width, height) {
+        builder.start_with(PrefabLevelBuilder::new(warren));
+        return builder;
+    }
+
+    let room_based = rng.roll_dice(1, 2) == 1;
+    if room_based {
+        random_room_builder(rng, &mut builder);
+    } else {
+        random_shape_builder(rng, &mut builder);
+    }
+
+    // sometimes remix the layout into something that only looks like it
+    let remixed = rng.roll_dice(1, 6) == 1;
+    if remixed {
+        builder.with(WaveformCollapseBuilder::new());
+    }
+
+    // open caves get terrain to suit the level's theme; room layouts
+    // are left alone, since their starts and spawns assume plain floor
+    if !room_based {
+        builder.with(TerrainFeatures::new());
+    }
+
+    if !room_based && rng.roll_dice(1, 4) == 1 {
+        builder.with(PrefabSectionBuilder::new(prefab_builder::prefab_sections::ORC_FORT));
+    }
+
+    if room_based && !remixed {
+        builder.with(RoomBasedStartingPosition::new());
+        builder.with(RoomBasedStairs::new());
+        builder.with(RoomBasedSpawner::new());
+    } else {
+        builder.with(AreaStartingPosition::new(XStart::Center, YStart::Center));
+        builder.with(CullUnreachable::new());
+        builder.with(DistantExit::new());
+        builder.with(VoronoiSpawning::new());
+    }
+    builder.with(VaultBuilder::new());
+    // doors go in last, so they can keep clear of everything spawned
+    if room_based {
+        builder.with(DoorPlacement::new());
+    }
+
+    builder
+}
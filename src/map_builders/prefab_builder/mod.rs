@@ -0,0 +1,253 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    InitialMapBuilder,
+    MetaMapBuilder,
+    BuilderMap,
+    TileType,
+    Position };
+
+pub mod prefab_levels;
+pub mod prefab_sections;
+pub mod prefab_rooms;
+
+use prefab_levels::PrefabLevel;
+use prefab_sections::{ PrefabSection, HorizontalPlacement, VerticalPlacement };
+use prefab_rooms::PrefabRoom;
+
+/// The most vaults placed on one level
+const MAX_VAULTS : i32 = 3;
+
+/// Vaults keep at least this far from the player's start, out of sight
+const VAULT_START_CLEARANCE : i32 = 12;
+
+/// A prefab's glyphs, read from its ASCII template. The template starts
+/// on the line after the opening quote; short lines are padded with
+/// floor, so trailing spaces don't matter.
+struct PrefabTiles {
+    width: i32,
+    height: i32,
+    glyphs: Vec<char>
+}
+
+impl PrefabTiles {
+    fn parse(template: &str) -> PrefabTiles {
+        let lines : Vec<&str> = template.lines().skip_while(|line| line.is_empty()).collect();
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        let mut glyphs = Vec::with_capacity(width * lines.len());
+        for line in lines.iter() {
+            let mut row : Vec<char> = line.chars().collect();
+            row.resize(width, ' ');
+            glyphs.extend(row);
+        }
+
+        PrefabTiles { width: width as i32, height: lines.len() as i32, glyphs }
+    }
+
+    fn glyph(&self, x: i32, y: i32) -> char {
+        self.glyphs[(y * self.width + x) as usize]
+    }
+
+    /// Whether the prefab fits inside an area of the given size
+    fn fits_in(&self, width: i32, height: i32) -> bool {
+        self.width <= width && self.height <= height
+    }
+}
+
+/// The thing a prefab glyph spawns, if any. Names match the raw files.
+fn glyph_spawn(glyph: char) -> Option<&'static str> {
+    match glyph {
+        'g' => Some("Goblin"),
+        'o' => Some("Orc"),
+        '!' => Some("Health Potion"),
+        '%' => Some("Rations"),
+        '?' => Some("Magic Missile Scroll"),
+        '/' => Some("Dagger"),
+        ')' => Some("Shield"),
+        _ => None
+    }
+}
+
+/// The terrain a prefab glyph stands for, if it is a terrain glyph
+fn glyph_tile(glyph: char) -> Option<TileType> {
+    match glyph {
+        ' ' => Some(TileType::Floor),
+        '#' => Some(TileType::Wall),
+        '>' => Some(TileType::DownStairs),
+        '~' => Some(TileType::ShallowWater),
+        'W' => Some(TileType::DeepWater),
+        ',' => Some(TileType::Grass),
+        ':' => Some(TileType::Gravel),
+        '=' => Some(TileType::Bridge),
+        '_' => Some(TileType::WoodFloor),
+        '-' => Some(TileType::Road),
+        _ => None
+    }
+}
+
+/// The glyphs in a template that stand for nothing, which would be laid
+/// down as plain floor
+pub fn unknown_glyphs(template: &str) -> Vec<char> {
+    let mut unknown : Vec<char> = PrefabTiles::parse(template).glyphs.into_iter()
+        .filter(|glyph| *glyph != '@' && glyph_spawn(*glyph).is_none() && glyph_tile(*glyph).is_none())
+        .collect();
+    unknown.sort();
+    unknown.dedup();
+    unknown
+}
+
+/// Copies a prefab onto the map with its top left corner at the given
+/// point, replacing whatever was due to spawn there. Any part that falls
+/// off the map is left out. Returns where the template's `@` was, if it
+/// has one.
+fn stamp(build_data: &mut BuilderMap, prefab: &PrefabTiles, left: i32, top: i32) -> Option<Position> {
+    let mut start = None;
+    for y in 0 .. prefab.height {
+        for x in 0 .. prefab.width {
+            let (map_x, map_y) = (left + x, top + y);
+            if map_x < 0 || map_x >= build_data.map.width || map_y < 0 || map_y >= build_data.map.height { continue; }
+            let idx = build_data.map.xy_idx(left + x, top + y);
+            build_data.spawn_list.retain(|(spawn_idx, _name)| *spawn_idx != idx);
+
+            let glyph = prefab.glyph(x, y);
+            build_data.map.tiles[idx] = glyph_tile(glyph).unwrap_or(TileType::Floor);
+
+            if glyph == '@' {
+                start = Some(Position { x: left + x, y: top + y });
+            } else if let Some(name) = glyph_spawn(glyph) {
+                build_data.spawn_list.push((idx, name.to_string()));
+            }
+        }
+    }
+    start
+}
+
+/// Lays out a whole hand-authored level, centred on the map. The player
+/// starts on the template's `@`. Check the level `fits` before using it:
+/// on a smaller map only its top left corner is laid out.
+pub struct PrefabLevelBuilder {
+    level: PrefabLevel
+}
+
+impl InitialMapBuilder for PrefabLevelBuilder {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let prefab = PrefabTiles::parse(self.level.template);
+        let left = i32::max(0, (build_data.map.width - prefab.width) / 2);
+        let top = i32::max(0, (build_data.map.height - prefab.height) / 2);
+        build_data.starting_position = stamp(build_data, &prefab, left, top);
+    }
+}
+
+impl PrefabLevelBuilder {
+    pub fn new(level: PrefabLevel) -> Box<PrefabLevelBuilder> {
+        Box::new(PrefabLevelBuilder { level })
+    }
+
+    /// Whether the level fits on a map of the given size
+    pub fn fits(level: &PrefabLevel, width: i32, height: i32) -> bool {
+        PrefabTiles::parse(level.template).fits_in(width, height)
+    }
+}
+
+/// Stamps a hand-authored section over part of the map built so far,
+/// keeping the map's outer wall. A map too small for the section is left
+/// as it was.
+pub struct PrefabSectionBuilder {
+    section: PrefabSection
+}
+
+impl MetaMapBuilder for PrefabSectionBuilder {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let prefab = PrefabTiles::parse(self.section.template);
+        if !prefab.fits_in(build_data.map.width - 2, build_data.map.height - 2) { return; }
+        let left = match self.section.placement.0 {
+            HorizontalPlacement::Left => 1,
+            HorizontalPlacement::Center => (build_data.map.width - prefab.width) / 2,
+            HorizontalPlacement::Right => build_data.map.width - 1 - prefab.width
+        };
+        let top = match self.section.placement.1 {
+            VerticalPlacement::Top => 1,
+            VerticalPlacement::Center => (build_data.map.height - prefab.height) / 2,
+            VerticalPlacement::Bottom => build_data.map.height - 1 - prefab.height
+        };
+        stamp(build_data, &prefab, left, top);
+    }
+}
+
+impl PrefabSectionBuilder {
+    pub fn new(section: PrefabSection) -> Box<PrefabSectionBuilder> {
+        Box::new(PrefabSectionBuilder { section })
+    }
+}
+
+/// Drops a few small set pieces, suited to the depth, into open floor.
+/// Run it last: a vault only goes where every tile it covers is plain
+/// floor, so it never covers the stairs, and it keeps clear of the
+/// player's start.
+pub struct VaultBuilder {}
+
+impl MetaMapBuilder for VaultBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl VaultBuilder {
+    pub fn new() -> Box<VaultBuilder> {
+        Box::new(VaultBuilder {})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let depth = build_data.map.depth;
+        let mut vaults : Vec<&PrefabRoom> = prefab_rooms::ALL_VAULTS.iter()
+            .filter(|vault| depth >= vault.first_depth && depth <= vault.last_depth)
+            .collect();
+
+        let mut used = vec![false; build_data.map.tiles.len()];
+        if let Some(start) = build_data.starting_position.as_ref() {
+            for (idx, used) in used.iter_mut().enumerate() {
+                let x = idx as i32 % build_data.map.width;
+                let y = idx as i32 / build_data.map.width;
+                if (x - start.x).abs() < VAULT_START_CLEARANCE && (y - start.y).abs() < VAULT_START_CLEARANCE {
+                    *used = true;
+                }
+            }
+        }
+
+        let n_vaults = i32::min(rng.roll_dice(1, MAX_VAULTS + 1) - 1, vaults.len() as i32);
+        for _i in 0 .. n_vaults {
+            let vault = vaults.remove((rng.roll_dice(1, vaults.len() as i32) - 1) as usize);
+            let prefab = PrefabTiles::parse(vault.template);
+
+            let mut candidates : Vec<(i32, i32)> = Vec::new();
+            for top in 1 .. build_data.map.height - prefab.height {
+                for left in 1 .. build_data.map.width - prefab.width {
+                    if fits(build_data, &used, &prefab, left, top) {
+                        candidates.push((left, top));
+                    }
+                }
+            }
+            if candidates.is_empty() { continue; }
+
+            let (left, top) = candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize];
+            stamp(build_data, &prefab, left, top);
+            build_data.take_snapshot();
+            for y in top .. top + prefab.height {
+                for x in left .. left + prefab.width {
+                    used[build_data.map.xy_idx(x, y)] = true;
+                }
+            }
+        }
+    }
+}
+
+/// True if the prefab would cover only plain, unused floor
+fn fits(build_data: &BuilderMap, used: &[bool], prefab: &PrefabTiles, left: i32, top: i32) -> bool {
+    for y in top .. top + prefab.height {
+        for x in left .. left + prefab.width {
+            let idx = build_data.map.xy_idx(x, y);
+            if used[idx] || build_data.map.tiles[idx] != TileType::Floor { return false; }
+        }
+    }
+    true
+}
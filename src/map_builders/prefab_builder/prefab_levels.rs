@@ -0,0 +1,26 @@
+/// A whole hand-authored level. `@` marks the player's start.
+pub struct PrefabLevel {
+    pub template: &'static str
+}
+
+/// A goblin warren: a long hall with side chambers, and the way down
+/// behind the chieftain's guards
+pub const GOBLIN_WARREN : PrefabLevel = PrefabLevel {
+    template: GOBLIN_WARREN_MAP
+};
+
+const GOBLIN_WARREN_MAP : &str = "
+##########################################################
+#        #          #          #          #       #      #
+#   @    #    g     #    %     #   g  g   #   !   #  ?   #
+#        #          #          #          #       #      #
+####  ##########  ########  ########  ######### ####  ####
+#                                                        #
+#   g                   g                     g          #
+#                                                        #
+####  ##########  ########  ########  ######### ####  ####
+#        #          #          #          #       #      #
+#   !    #    /     #    g     #    o     #  o o  #  >   #
+#        #          #          #          #       #      #
+##########################################################
+";
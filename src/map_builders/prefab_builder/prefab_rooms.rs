@@ -0,0 +1,92 @@
+/// A small set piece dropped into open floor, between the given depths
+pub struct PrefabRoom {
+    pub template: &'static str,
+    pub first_depth: i32,
+    pub last_depth: i32
+}
+
+pub const ALL_VAULTS : [PrefabRoom; 5] = [GUARDED_CACHE, PILLARED_HALL, SILLY_SMILE, ORC_BARRACKS, FLOODED_SHRINE];
+
+/// A little store room with a goblin either side of the door
+pub const GUARDED_CACHE : PrefabRoom = PrefabRoom {
+    template: GUARDED_CACHE_MAP,
+    first_depth: 1,
+    last_depth: 5
+};
+
+const GUARDED_CACHE_MAP : &str = "
+       
+ ##### 
+ #!%?# 
+ #   # 
+ #g g# 
+ ## ## 
+       
+";
+
+pub const PILLARED_HALL : PrefabRoom = PrefabRoom {
+    template: PILLARED_HALL_MAP,
+    first_depth: 1,
+    last_depth: 100
+};
+
+const PILLARED_HALL_MAP : &str = "
+         
+ # # # # 
+  g  !   
+ # # # # 
+   %  g  
+ # # # # 
+         
+";
+
+pub const SILLY_SMILE : PrefabRoom = PrefabRoom {
+    template: SILLY_SMILE_MAP,
+    first_depth: 1,
+    last_depth: 100
+};
+
+const SILLY_SMILE_MAP : &str = "
+           
+  g     g  
+     #     
+ #       # 
+  #######  
+           
+";
+
+/// Four orcs bunking around a healing potion; only found deeper down
+pub const ORC_BARRACKS : PrefabRoom = PrefabRoom {
+    template: ORC_BARRACKS_MAP,
+    first_depth: 4,
+    last_depth: 100
+};
+
+const ORC_BARRACKS_MAP : &str = "
+           
+ ######### 
+ #o     o# 
+ #   !   # 
+ #o     o# 
+ #### #### 
+           
+";
+
+/// A potion on a grassy island in a pool, reached over a bridge
+pub const FLOODED_SHRINE : PrefabRoom = PrefabRoom {
+    template: FLOODED_SHRINE_MAP,
+    first_depth: 1,
+    last_depth: 100
+};
+
+const FLOODED_SHRINE_MAP : &str = "
+           
+ ~~~~~~~~~ 
+ ~WWWWWWW~ 
+ ~W,,,,,W~ 
+ ~W,,!,,W~ 
+ ~W,,,,,W~ 
+ ~WWW=WWW~ 
+ ~~~~~~~~~ 
+           
+";
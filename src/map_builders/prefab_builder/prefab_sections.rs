@@ -0,0 +1,36 @@
+#[derive(PartialEq, Copy, Clone)]
+pub enum HorizontalPlacement { Left, Center, Right }
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VerticalPlacement { Top, Center, Bottom }
+
+/// A hand-authored chunk of level, stamped over a generated map
+pub struct PrefabSection {
+    pub template: &'static str,
+    pub placement: (HorizontalPlacement, VerticalPlacement)
+}
+
+/// An orc outpost built into the east side of the level, entered through
+/// a gate in its west wall along a stone road
+pub const ORC_FORT : PrefabSection = PrefabSection {
+    template: ORC_FORT_MAP,
+    placement: (HorizontalPlacement::Right, VerticalPlacement::Center)
+};
+
+const ORC_FORT_MAP : &str = "
+   #################
+   #   #       #   #
+   # o     !     o #
+   #   #       #   #
+   ### ######### ###
+   #               #
+  g#  o    %    o  #
+-------------------#
+  g#  o    ?    o  #
+   #               #
+   ### ######### ###
+   #   #       #   #
+   # o     )     o #
+   #   #       #   #
+   #################
+";
@@ -0,0 +1,61 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    TileType,
+    Position,
+    spawner };
+
+/// Starts the player in the middle of the first room
+pub struct RoomBasedStartingPosition {}
+
+impl MetaMapBuilder for RoomBasedStartingPosition {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data.rooms.as_ref().expect("Room Based Starting Position only works after rooms have been created");
+        let (x, y) = rooms[0].center();
+        build_data.starting_position = Some(Position { x, y });
+    }
+}
+
+impl RoomBasedStartingPosition {
+    pub fn new() -> Box<RoomBasedStartingPosition> {
+        Box::new(RoomBasedStartingPosition {})
+    }
+}
+
+/// Puts the way down in the middle of the last room
+pub struct RoomBasedStairs {}
+
+impl MetaMapBuilder for RoomBasedStairs {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data.rooms.as_ref().expect("Room Based Stairs only works after rooms have been created");
+        let (x, y) = rooms[rooms.len() - 1].center();
+        let idx = build_data.map.xy_idx(x, y);
+        build_data.map.tiles[idx] = TileType::DownStairs;
+    }
+}
+
+impl RoomBasedStairs {
+    pub fn new() -> Box<RoomBasedStairs> {
+        Box::new(RoomBasedStairs {})
+    }
+}
+
+/// Fills every room but the first, where the player starts
+pub struct RoomBasedSpawner {}
+
+impl MetaMapBuilder for RoomBasedSpawner {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data.rooms.as_ref().expect("Room Based Spawning only works after rooms have been created");
+        for room in rooms.iter().skip(1) {
+            spawner::spawn_room(&build_data.map, rng, room, build_data.map.depth, &mut build_data.spawn_list);
+        }
+    }
+}
+
+impl RoomBasedSpawner {
+    pub fn new() -> Box<RoomBasedSpawner> {
+        Box::new(RoomBasedSpawner {})
+    }
+}
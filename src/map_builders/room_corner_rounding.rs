@@ -0,0 +1,50 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    Map,
+    TileType,
+    Rect };
+
+/// Fills in the corners of every room, so rooms look less boxy. Run it
+/// before the corridors are dug, or it may block them.
+pub struct RoomCornerRounder {}
+
+impl MetaMapBuilder for RoomCornerRounder {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(build_data);
+    }
+}
+
+impl RoomCornerRounder {
+    pub fn new() -> Box<RoomCornerRounder> {
+        Box::new(RoomCornerRounder {})
+    }
+
+    fn build(&mut self, build_data: &mut BuilderMap) {
+        let rooms : Vec<Rect> = build_data.rooms.as_ref().expect("Room Rounding requires a builder with room structures").clone();
+
+        for room in rooms.iter() {
+            fill_if_corner(&mut build_data.map, room.x1, room.y1);
+            fill_if_corner(&mut build_data.map, room.x2, room.y1);
+            fill_if_corner(&mut build_data.map, room.x1, room.y2);
+            fill_if_corner(&mut build_data.map, room.x2, room.y2);
+        }
+    }
+}
+
+/// Turns a floor tile into wall if it has walls on exactly two sides
+fn fill_if_corner(map: &mut Map, x: i32, y: i32) {
+    if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { return; }
+
+    let mut neighbor_walls = 0;
+    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+        if map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall { neighbor_walls += 1; }
+    }
+
+    if neighbor_walls == 2 {
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::Wall;
+    }
+}
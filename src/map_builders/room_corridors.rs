@@ -0,0 +1,137 @@
+use rltk::{ DistanceAlg, Point };
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    Rect,
+    draw_corridor };
+
+use std::collections::HashSet;
+
+/// Joins each room to the one built before it with an L-shaped corridor
+/// between their centres.
+pub struct DoglegCorridors {}
+
+impl MetaMapBuilder for DoglegCorridors {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = rooms_to_join(build_data);
+        let mut corridors = Vec::new();
+        for pair in rooms.windows(2) {
+            corridors.push(draw_corridor(&mut build_data.map, rng, pair[0].center(), pair[1].center()));
+            build_data.take_snapshot();
+        }
+        build_data.corridors = Some(corridors);
+    }
+}
+
+impl DoglegCorridors {
+    pub fn new() -> Box<DoglegCorridors> {
+        Box::new(DoglegCorridors {})
+    }
+}
+
+/// Joins each room to the one built before it, leaving from and arriving
+/// at random points inside the rooms rather than their centres.
+pub struct BspCorridors {}
+
+impl MetaMapBuilder for BspCorridors {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = rooms_to_join(build_data);
+        let mut corridors = Vec::new();
+        for pair in rooms.windows(2) {
+            let from = random_point_in(&pair[0], rng);
+            let to = random_point_in(&pair[1], rng);
+            corridors.push(draw_corridor(&mut build_data.map, rng, from, to));
+            build_data.take_snapshot();
+        }
+        build_data.corridors = Some(corridors);
+    }
+}
+
+impl BspCorridors {
+    pub fn new() -> Box<BspCorridors> {
+        Box::new(BspCorridors {})
+    }
+}
+
+/// Joins each room to the nearest room that hasn't been joined up yet.
+/// Every room links to one joined after it, so they all end up connected.
+pub struct NearestCorridors {}
+
+impl MetaMapBuilder for NearestCorridors {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = rooms_to_join(build_data);
+        let mut corridors = Vec::new();
+        let mut connected : HashSet<usize> = HashSet::new();
+        for (i, room) in rooms.iter().enumerate() {
+            connected.insert(i);
+            let nearest = (0 .. rooms.len())
+                .filter(|j| !connected.contains(j))
+                .min_by(|a, b| room_distance(room, &rooms[*a]).partial_cmp(&room_distance(room, &rooms[*b])).unwrap());
+
+            if let Some(j) = nearest {
+                corridors.push(draw_corridor(&mut build_data.map, rng, room.center(), rooms[j].center()));
+                build_data.take_snapshot();
+            }
+        }
+        build_data.corridors = Some(corridors);
+    }
+}
+
+impl NearestCorridors {
+    pub fn new() -> Box<NearestCorridors> {
+        Box::new(NearestCorridors {})
+    }
+}
+
+/// Joins the rooms along a minimum spanning tree, grown with Prim's
+/// algorithm: the shortest possible set of corridors that connects them all.
+pub struct PrimCorridors {}
+
+impl MetaMapBuilder for PrimCorridors {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = rooms_to_join(build_data);
+        let mut corridors = Vec::new();
+        let mut in_tree = vec![false; rooms.len()];
+        if !rooms.is_empty() { in_tree[0] = true; }
+
+        for _edge in 1 .. rooms.len() {
+            let mut shortest : Option<(usize, usize, f32)> = None;
+            for from in (0 .. rooms.len()).filter(|i| in_tree[*i]) {
+                for to in (0 .. rooms.len()).filter(|i| !in_tree[*i]) {
+                    let distance = room_distance(&rooms[from], &rooms[to]);
+                    if shortest.is_none_or(|(_, _, best)| distance < best) {
+                        shortest = Some((from, to, distance));
+                    }
+                }
+            }
+
+            let (from, to, _) = shortest.expect("Rooms left outside the tree");
+            in_tree[to] = true;
+            corridors.push(draw_corridor(&mut build_data.map, rng, rooms[from].center(), rooms[to].center()));
+            build_data.take_snapshot();
+        }
+        build_data.corridors = Some(corridors);
+    }
+}
+
+impl PrimCorridors {
+    pub fn new() -> Box<PrimCorridors> {
+        Box::new(PrimCorridors {})
+    }
+}
+
+fn rooms_to_join(build_data: &BuilderMap) -> Vec<Rect> {
+    build_data.rooms.as_ref().expect("Corridors require a builder with room structures").clone()
+}
+
+fn room_distance(a: &Rect, b: &Rect) -> f32 {
+    let (ax, ay) = a.center();
+    let (bx, by) = b.center();
+    DistanceAlg::Pythagoras.distance2d(Point::new(ax, ay), Point::new(bx, by))
+}
+
+fn random_point_in(room: &Rect, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    (rng.range(room.x1, room.x2 + 1), rng.range(room.y1, room.y2 + 1))
+}
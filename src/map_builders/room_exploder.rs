@@ -0,0 +1,50 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    TileType,
+    Rect };
+
+/// Steps each digger takes
+const DIGGER_LIFETIME : i32 = 20;
+
+/// Roughens rooms by sending a handful of drunken diggers out from the
+/// centre of each, so rooms grow ragged edges and little side passages.
+pub struct RoomExploder {}
+
+impl MetaMapBuilder for RoomExploder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl RoomExploder {
+    pub fn new() -> Box<RoomExploder> {
+        Box::new(RoomExploder {})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms : Vec<Rect> = build_data.rooms.as_ref().expect("Room Explosions require a builder with room structures").clone();
+
+        for room in rooms.iter() {
+            let (start_x, start_y) = room.center();
+            let n_diggers = rng.roll_dice(1, 20) - 5;
+            for _i in 0 .. n_diggers {
+                let (mut drunk_x, mut drunk_y) = (start_x, start_y);
+                for _step in 0 .. DIGGER_LIFETIME {
+                    let idx = build_data.map.xy_idx(drunk_x, drunk_y);
+                    build_data.map.tiles[idx] = TileType::Floor;
+
+                    match rng.roll_dice(1, 4) {
+                        1 => if drunk_x > 2 { drunk_x -= 1; }
+                        2 => if drunk_x < build_data.map.width - 2 { drunk_x += 1; }
+                        3 => if drunk_y > 2 { drunk_y -= 1; }
+                        _ => if drunk_y < build_data.map.height - 2 { drunk_y += 1; }
+                    }
+                }
+            }
+            build_data.take_snapshot();
+        }
+    }
+}
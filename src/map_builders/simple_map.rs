@@ -1,96 +1,50 @@
-use rltk::{ RandomNumberGenerator };
+use crate::RandomNumberGenerator;
 
 use super::{
-    Map,
-    MapBuilder,
-    TileType,
+    InitialMapBuilder,
+    BuilderMap,
     Rect,
-    Position,
-    spawner,
-    apply_room_to_map,
-    apply_horizontal_tunnel, 
-    apply_vertical_tunnel };
+    apply_room_to_map };
 
-use specs::prelude::*;
+/// Scatters non-overlapping rectangular rooms over the map. The rooms are
+/// left unconnected; follow it with one of the corridor builders.
+pub struct SimpleMapBuilder {}
 
-pub struct SimpleMapBuilder {
-    map: Map,
-    starting_pos: Position,
-    depth: i32,
-    rooms: Vec<Rect>
-}
-
-impl MapBuilder for SimpleMapBuilder {
-    fn build_map(&mut self) {
-        self.rooms_and_corridors();
-    }
-
-    fn spawn_entities(&mut self, ecs: &mut World) {
-        for room in self.rooms.iter().skip(1) {
-            spawner::spawn_room(ecs, room, self.depth);
-        }
-    }
-
-    fn get_map(&self) -> Map {
-        self.map.clone()
-    }
-
-    fn get_starting_pos(&self) -> Position {
-        self.starting_pos.clone()
+impl InitialMapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build_rooms(rng, build_data);
     }
 }
 
 impl SimpleMapBuilder {
-    pub fn new(depth: i32) -> SimpleMapBuilder {
-        SimpleMapBuilder {
-            map: Map::new(depth),
-            starting_pos: Position{ x: 0, y: 0 },
-            depth: depth,
-            rooms: Vec::new()
-        }
+    pub fn new() -> Box<SimpleMapBuilder> {
+        Box::new(SimpleMapBuilder {})
     }
 
-    fn rooms_and_corridors(&mut self) {
+    fn build_rooms(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
         const MAX_ROOMS: i32 = 30;
         const MIN_SIZE: i32 = 6;
         const MAX_SIZE: i32 = 10;
-
-        let mut rng = RandomNumberGenerator::new();
+        let mut rooms : Vec<Rect> = Vec::new();
 
         for _i in 0..MAX_ROOMS {
             let w = rng.range(MIN_SIZE, MAX_SIZE);
             let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.range(1, self.map.width - w);
-            let y = rng.range(1, self.map.height - h);
+            let x = rng.range(1, build_data.map.width - w - 1);
+            let y = rng.range(1, build_data.map.height - h - 1);
             let room = Rect::new(x, y, w, h);
             let mut ok = true;
-            for other in self.rooms.iter() { 
+            for other in rooms.iter() { 
                 if room.intersect(other) { ok = false; }
             }
 
             if ok {
-                apply_room_to_map(&mut self.map, &room);
-                if !self.rooms.is_empty() {
-                    let (nx, ny) = room.center();
-                    let (px, py) = self.rooms[self.rooms.len()-1].center();
-                    if rng.range(0, 2) == 1 {
-                        apply_horizontal_tunnel(&mut self.map, px, nx, ny);
-                        apply_vertical_tunnel(&mut self.map, nx, py, ny);
-                    } else {
-                        apply_vertical_tunnel(&mut self.map, px, py, ny);
-                        apply_horizontal_tunnel(&mut self.map, px, nx, ny);
-                    }
-                }
-
-                self.rooms.push(room);
+                apply_room_to_map(&mut build_data.map, &room);
+                build_data.take_snapshot();
+                rooms.push(room);
             }
         }
 
-        let stairs = self.rooms[self.rooms.len()-1].center();
-        let stairs_idx = Map::xy_idx(stairs.0, stairs.1);
-        self.map.tiles[stairs_idx] = TileType::DownStairs;
-
-        let start_pos = self.rooms[0].center();
-        self.starting_pos = Position { x: start_pos.0, y: start_pos.1 }
+        build_data.rooms = Some(rooms);
     }
 }
@@ -0,0 +1,103 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    Map,
+    TileType };
+
+use crate::themes::{ Theme, theme_for_depth };
+
+/// One feature for every this many tiles of map
+const TILES_PER_FEATURE : usize = 300;
+
+/// Dresses the open floor in terrain to suit the level's theme: grassy
+/// glades in the forest, pools with gravel shores in the limestone
+/// caverns. Other levels are left bare. Only plain floor is changed, and
+/// deep water only goes where there is room to wade round it.
+pub struct TerrainFeatures {}
+
+impl MetaMapBuilder for TerrainFeatures {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl TerrainFeatures {
+    pub fn new() -> Box<TerrainFeatures> {
+        Box::new(TerrainFeatures {})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        let theme = theme_for_depth(map.depth);
+        if theme == Theme::Dungeon { return; }
+
+        for _feature in 0 .. map.tiles.len() / TILES_PER_FEATURE {
+            let floor : Vec<usize> = (0 .. map.tiles.len()).filter(|idx| map.tiles[*idx] == TileType::Floor).collect();
+            if floor.is_empty() { return; }
+            let centre = floor[(rng.roll_dice(1, floor.len() as i32) - 1) as usize];
+            let radius = rng.roll_dice(1, 3) + 1;
+
+            match theme {
+                Theme::Forest => glade(map, centre, radius),
+                _ => pool(rng, map, centre, radius),
+            }
+        }
+    }
+}
+
+/// Grass over the floor within `radius` of the centre
+fn glade(map: &mut Map, centre: usize, radius: i32) {
+    for idx in within(map, centre, radius) {
+        if map.tiles[idx] == TileType::Floor { map.tiles[idx] = TileType::Grass; }
+    }
+}
+
+/// Deep water in the middle, shallows round it and a scattering of gravel
+/// on the shore
+fn pool(rng: &mut RandomNumberGenerator, map: &mut Map, centre: usize, radius: i32) {
+    for idx in within(map, centre, radius + 1) {
+        if map.tiles[idx] != TileType::Floor { continue; }
+        let from_centre = distance(map, centre, idx);
+        if from_centre <= (radius - 1) as f32 && open_around(map, idx) {
+            map.tiles[idx] = TileType::DeepWater;
+        } else if from_centre <= radius as f32 {
+            map.tiles[idx] = TileType::ShallowWater;
+        } else if rng.roll_dice(1, 2) == 1 {
+            map.tiles[idx] = TileType::Gravel;
+        }
+    }
+}
+
+/// The tiles inside the map within `radius` of the centre
+fn within(map: &Map, centre: usize, radius: i32) -> Vec<usize> {
+    let (cx, cy) = (centre as i32 % map.width, centre as i32 / map.width);
+    let mut tiles = Vec::new();
+    for y in i32::max(1, cy - radius) ..= i32::min(map.height - 2, cy + radius) {
+        for x in i32::max(1, cx - radius) ..= i32::min(map.width - 2, cx + radius) {
+            let idx = map.xy_idx(x, y);
+            if distance(map, centre, idx) <= radius as f32 { tiles.push(idx); }
+        }
+    }
+    tiles
+}
+
+fn distance(map: &Map, a: usize, b: usize) -> f32 {
+    rltk::DistanceAlg::Pythagoras.distance2d(
+        rltk::Point::new(a as i32 % map.width, a as i32 / map.width),
+        rltk::Point::new(b as i32 % map.width, b as i32 / map.width))
+}
+
+/// True if there's no wall within two tiles, so deep water here leaves
+/// a way round
+fn open_around(map: &Map, idx: usize) -> bool {
+    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+    if x < 2 || x > map.width - 3 || y < 2 || y > map.height - 3 { return false; }
+    for dy in -2 ..= 2 {
+        for dx in -2 ..= 2 {
+            if map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall { return false; }
+        }
+    }
+    true
+}
@@ -0,0 +1,35 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    spawner,
+    generate_voronoi_spawn_regions };
+
+use std::collections::HashSet;
+
+/// Fills maps that have no rooms, treating noise-shaped regions of floor
+/// as rooms. Nothing is placed where the player starts, or where something
+/// is already due to spawn.
+pub struct VoronoiSpawning {}
+
+impl MetaMapBuilder for VoronoiSpawning {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let mut taken : HashSet<usize> = build_data.spawn_list.iter().map(|(idx, _name)| *idx).collect();
+        if let Some(start) = build_data.starting_position.as_ref() {
+            taken.insert(build_data.map.xy_idx(start.x, start.y));
+        }
+
+        let noise_areas = generate_voronoi_spawn_regions(&build_data.map, rng);
+        for area in noise_areas.values() {
+            let area : Vec<usize> = area.iter().filter(|idx| !taken.contains(idx)).cloned().collect();
+            spawner::spawn_region(rng, &area, build_data.map.depth, &mut build_data.spawn_list);
+        }
+    }
+}
+
+impl VoronoiSpawning {
+    pub fn new() -> Box<VoronoiSpawning> {
+        Box::new(VoronoiSpawning {})
+    }
+}
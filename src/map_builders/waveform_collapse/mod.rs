@@ -0,0 +1,78 @@
+use crate::RandomNumberGenerator;
+
+use super::{
+    MetaMapBuilder,
+    BuilderMap,
+    Map,
+    TileType,
+    nearest_floor,
+    dijkstra_flood };
+
+mod solver;
+use solver::{ Solver, extract_patterns };
+
+/// Size of the tile windows learned from the sample
+const PATTERN_SIZE : i32 = 3;
+
+/// Attempts at solving before settling for the sample itself
+const MAX_ATTEMPTS : i32 = 10;
+
+/// A result must have at least 1/N of its tiles reachable from the
+/// centre to be kept
+const MIN_FLOOR_FRACTION : usize = 8;
+
+/// Replaces the map so far with a new one that looks locally like it,
+/// using wave function collapse over its 3x3 tile patterns. Rooms,
+/// corridors, spawns and the start no longer fit the new map, so they are
+/// dropped; place them again afterwards.
+pub struct WaveformCollapseBuilder {}
+
+impl MetaMapBuilder for WaveformCollapseBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl WaveformCollapseBuilder {
+    pub fn new() -> Box<WaveformCollapseBuilder> {
+        Box::new(WaveformCollapseBuilder {})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        let patterns = extract_patterns(map, PATTERN_SIZE);
+        for _attempt in 0 .. MAX_ATTEMPTS {
+            let mut solver = Solver::new(&patterns, PATTERN_SIZE, map.width, map.height);
+            if let Some(tiles) = solver.solve(rng) {
+                let mut candidate = map.clone();
+                candidate.tiles = tiles;
+                if reachable_from_center(&mut candidate) >= candidate.tiles.len() / MIN_FLOOR_FRACTION {
+                    map.tiles = candidate.tiles;
+                    break;
+                }
+            }
+        }
+
+        // whether or not a new layout was found, the sample's stairs go;
+        // any other terrain is kept
+        for tile in map.tiles.iter_mut() {
+            if *tile == TileType::DownStairs || *tile == TileType::UpStairs { *tile = TileType::Floor; }
+        }
+
+        build_data.spawn_list.clear();
+        build_data.starting_position = None;
+        build_data.rooms = None;
+        build_data.corridors = None;
+    }
+}
+
+/// Counts the tiles that can be reached from the floor nearest the centre
+fn reachable_from_center(map: &mut Map) -> usize {
+    match nearest_floor(map, map.width / 2, map.height / 2) {
+        None => 0,
+        Some(start_idx) => {
+            map.populate_blocked();
+            dijkstra_flood(map, start_idx).iter().filter(|d| **d < f32::MAX).count()
+        }
+    }
+}
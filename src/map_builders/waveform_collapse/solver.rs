@@ -0,0 +1,314 @@
+use crate::RandomNumberGenerator;
+use std::collections::HashMap;
+
+use super::{ Map, TileType };
+
+/// Offsets to the neighbouring cell in each direction
+const DIRECTIONS : [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// An NxN block of tiles seen in the sample, and how often it was seen
+pub struct Pattern {
+    pub tiles: Vec<TileType>,
+    pub weight: u32,
+}
+
+/// Cuts every NxN window out of the sample. Stairs are treated as floor,
+/// since the generated level places its own; other terrain is kept.
+pub fn extract_patterns(sample: &Map, n: i32) -> Vec<Pattern> {
+    let mut seen : HashMap<Vec<TileType>, usize> = HashMap::new();
+    let mut patterns : Vec<Pattern> = Vec::new();
+
+    for y in 0 ..= sample.height - n {
+        for x in 0 ..= sample.width - n {
+            let mut tiles = Vec::with_capacity((n * n) as usize);
+            for py in 0 .. n {
+                for px in 0 .. n {
+                    let tile = match sample.tiles[sample.xy_idx(x + px, y + py)] {
+                        TileType::DownStairs | TileType::UpStairs => TileType::Floor,
+                        tile => tile,
+                    };
+                    tiles.push(tile);
+                }
+            }
+
+            match seen.get(&tiles) {
+                Some(index) => patterns[*index].weight += 1,
+                None => {
+                    seen.insert(tiles.clone(), patterns.len());
+                    patterns.push(Pattern { tiles, weight: 1 });
+                }
+            }
+        }
+    }
+
+    patterns
+}
+
+/// Can `b` sit one step in `direction` from `a`? True when the tiles the
+/// two windows share agree.
+fn overlaps(a: &Pattern, b: &Pattern, n: i32, direction: (i32, i32)) -> bool {
+    let (dx, dy) = direction;
+    for y in 0 .. n {
+        for x in 0 .. n {
+            let (bx, by) = (x - dx, y - dy);
+            if bx < 0 || bx >= n || by < 0 || by >= n { continue; }
+            if a.tiles[(y * n + x) as usize] != b.tiles[(by * n + bx) as usize] {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// One pattern choice, and where the removal trail stood before it, so it
+/// can be undone.
+struct Decision {
+    trail_len: usize,
+    cell: usize,
+    pattern: usize,
+}
+
+/// An overlapping-model wave function collapse. Each cell of the output
+/// starts able to hold any pattern; the cell with the fewest options left
+/// is collapsed to one pattern at a time, and the choice is propagated to
+/// its neighbours. A contradiction (a cell with no options) undoes the
+/// most recent choice and bans it.
+pub struct Solver<'a> {
+    patterns: &'a [Pattern],
+    n: i32,
+    width: i32,
+    height: i32,
+    compatible: Vec<[Vec<usize>; 4]>,
+    wave: Vec<bool>,
+    options: Vec<usize>,
+    /// How many patterns in the neighbour behind each direction still
+    /// allow a pattern here; at zero the pattern is impossible
+    supports: Vec<[i32; 4]>,
+    /// Every removal so far, and whether it has been propagated yet
+    trail: Vec<(usize, usize, bool)>,
+    pending: Vec<usize>,
+    decisions: Vec<Decision>,
+}
+
+/// Give up on an attempt after this many undone choices
+const MAX_BACKTRACKS : i32 = 500;
+
+impl<'a> Solver<'a> {
+    /// `width` and `height` are the size of the map to generate
+    pub fn new(patterns: &'a [Pattern], n: i32, width: i32, height: i32) -> Solver<'a> {
+        let count = patterns.len();
+        let mut compatible : Vec<[Vec<usize>; 4]> = Vec::with_capacity(count);
+        for a in patterns.iter() {
+            let mut allowed : [Vec<usize>; 4] = Default::default();
+            for (d, direction) in DIRECTIONS.iter().enumerate() {
+                for (j, b) in patterns.iter().enumerate() {
+                    if overlaps(a, b, n, *direction) { allowed[d].push(j); }
+                }
+            }
+            compatible.push(allowed);
+        }
+
+        let cell_width = width - n + 1;
+        let cell_height = height - n + 1;
+        let cells = (cell_width * cell_height) as usize;
+
+        let mut initial_support = vec![[0i32; 4]; count];
+        for allowed in compatible.iter() {
+            for d in 0 .. 4 {
+                for b in allowed[d].iter() {
+                    initial_support[*b][d] += 1;
+                }
+            }
+        }
+        let mut supports = Vec::with_capacity(cells * count);
+        for _cell in 0 .. cells {
+            supports.extend(initial_support.iter().cloned());
+        }
+
+        Solver {
+            patterns,
+            n,
+            width: cell_width,
+            height: cell_height,
+            compatible,
+            wave: vec![true; cells * count],
+            options: vec![count; cells],
+            supports,
+            trail: Vec::new(),
+            pending: Vec::new(),
+            decisions: Vec::new(),
+        }
+    }
+
+    /// Runs the solver to completion, returning the generated tiles, or
+    /// None if it kept running into contradictions.
+    pub fn solve(&mut self, rng: &mut RandomNumberGenerator) -> Option<Vec<TileType>> {
+        if !self.restrict_edges() { return None; }
+
+        let mut backtracks = 0;
+        while let Some(cell) = self.lowest_entropy_cell(rng) {
+            let pattern = self.pick_pattern(cell, rng);
+            self.decisions.push(Decision { trail_len: self.trail.len(), cell, pattern });
+            for other in 0 .. self.patterns.len() {
+                if other != pattern && self.is_possible(cell, other) {
+                    self.remove(cell, other);
+                }
+            }
+
+            while !self.propagate() {
+                backtracks += 1;
+                if backtracks > MAX_BACKTRACKS || !self.backtrack() { return None; }
+            }
+        }
+
+        Some(self.render())
+    }
+
+    fn count(&self) -> usize {
+        self.patterns.len()
+    }
+
+    fn is_possible(&self, cell: usize, pattern: usize) -> bool {
+        self.wave[cell * self.count() + pattern]
+    }
+
+    fn remove(&mut self, cell: usize, pattern: usize) {
+        let count = self.count();
+        self.wave[cell * count + pattern] = false;
+        self.options[cell] -= 1;
+        self.trail.push((cell, pattern, false));
+        self.pending.push(self.trail.len() - 1);
+    }
+
+    fn neighbour(&self, cell: usize, direction: usize) -> Option<usize> {
+        let x = cell as i32 % self.width + DIRECTIONS[direction].0;
+        let y = cell as i32 / self.width + DIRECTIONS[direction].1;
+        if x < 0 || x >= self.width || y < 0 || y >= self.height { return None; }
+        Some((y * self.width + x) as usize)
+    }
+
+    /// Applies pending removals to the neighbours' supports, removing
+    /// anything left unsupported. Returns false on a contradiction.
+    fn propagate(&mut self) -> bool {
+        let count = self.count();
+        while let Some(entry) = self.pending.pop() {
+            let (cell, pattern, _) = self.trail[entry];
+            if self.options[cell] == 0 {
+                self.pending.clear();
+                return false;
+            }
+            self.trail[entry].2 = true;
+
+            for d in 0 .. 4 {
+                let neighbour = match self.neighbour(cell, d) { Some(n) => n, None => continue };
+                for i in 0 .. self.compatible[pattern][d].len() {
+                    let other = self.compatible[pattern][d][i];
+                    let support = &mut self.supports[neighbour * count + other][d];
+                    *support -= 1;
+                    if *support == 0 && self.wave[neighbour * count + other] {
+                        self.remove(neighbour, other);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Undoes the most recent choice and bans it. Returns false when there
+    /// is nothing left to undo.
+    fn backtrack(&mut self) -> bool {
+        let decision = match self.decisions.pop() { Some(d) => d, None => return false };
+        self.undo_to(decision.trail_len);
+        if self.is_possible(decision.cell, decision.pattern) {
+            self.remove(decision.cell, decision.pattern);
+        }
+        true
+    }
+
+    fn undo_to(&mut self, trail_len: usize) {
+        let count = self.count();
+        self.pending.clear();
+        while self.trail.len() > trail_len {
+            let (cell, pattern, propagated) = self.trail.pop().unwrap();
+            self.wave[cell * count + pattern] = true;
+            self.options[cell] += 1;
+            if !propagated { continue; }
+
+            for d in 0 .. 4 {
+                if let Some(neighbour) = self.neighbour(cell, d) {
+                    for other in self.compatible[pattern][d].iter() {
+                        self.supports[neighbour * count + *other][d] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The map's border must be wall, so cells on the edge may only hold
+    /// patterns whose outer side is solid.
+    fn restrict_edges(&mut self) -> bool {
+        let n = self.n;
+        for cell in 0 .. self.options.len() {
+            let x = cell as i32 % self.width;
+            let y = cell as i32 / self.width;
+            for pattern in 0 .. self.count() {
+                let tiles = &self.patterns[pattern].tiles;
+                let solid = |px: i32, py: i32| tiles[(py * n + px) as usize] == TileType::Wall;
+                let ok = (x != 0 || (0 .. n).all(|py| solid(0, py)))
+                    && (x != self.width - 1 || (0 .. n).all(|py| solid(n - 1, py)))
+                    && (y != 0 || (0 .. n).all(|px| solid(px, 0)))
+                    && (y != self.height - 1 || (0 .. n).all(|px| solid(px, n - 1)));
+                if !ok && self.is_possible(cell, pattern) {
+                    self.remove(cell, pattern);
+                }
+            }
+        }
+        self.propagate()
+    }
+
+    /// The undecided cell with the fewest options, ties broken at random
+    fn lowest_entropy_cell(&self, rng: &mut RandomNumberGenerator) -> Option<usize> {
+        let fewest = self.options.iter().filter(|o| **o > 1).min()?;
+        let candidates : Vec<usize> = (0 .. self.options.len()).filter(|c| self.options[*c] == *fewest).collect();
+        Some(candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize])
+    }
+
+    /// Picks one of the cell's remaining patterns, weighted by how often
+    /// it appeared in the sample
+    fn pick_pattern(&self, cell: usize, rng: &mut RandomNumberGenerator) -> usize {
+        let possible : Vec<usize> = (0 .. self.count()).filter(|p| self.is_possible(cell, *p)).collect();
+        let total : u32 = possible.iter().map(|p| self.patterns[*p].weight).sum();
+        let mut roll = rng.roll_dice(1, total as i32) as u32;
+        for p in possible.iter() {
+            let weight = self.patterns[*p].weight;
+            if roll <= weight { return *p; }
+            roll -= weight;
+        }
+        possible[possible.len() - 1]
+    }
+
+    /// Lays the chosen patterns out as map tiles
+    fn render(&self) -> Vec<TileType> {
+        let n = self.n;
+        let map_width = self.width + n - 1;
+        let map_height = self.height + n - 1;
+        let mut tiles = vec![TileType::Wall; (map_width * map_height) as usize];
+
+        for cell in 0 .. self.options.len() {
+            let pattern = match (0 .. self.count()).find(|p| self.is_possible(cell, *p)) {
+                Some(p) => &self.patterns[p],
+                None => continue,
+            };
+            let x = cell as i32 % self.width;
+            let y = cell as i32 / self.width;
+            for py in 0 .. n {
+                for px in 0 .. n {
+                    tiles[((y + py) * map_width + x + px) as usize] = pattern.tiles[(py * n + px) as usize];
+                }
+            }
+        }
+
+        tiles
+    }
+}
@@ -1,7 +1,7 @@
 extern crate specs;
 use specs::prelude::*;
 
-use super::{Map, Position, BlocksTile};
+use super::{Map, Position, BlocksTile, Door};
 
 pub struct MapIndexingSystem {}
 
@@ -9,15 +9,17 @@ impl<'a> System<'a> for MapIndexingSystem {
     type SystemData = ( WriteExpect<'a, Map>,
                         ReadStorage<'a, Position>,
                         ReadStorage<'a, BlocksTile>,
+                        ReadStorage<'a, Door>,
                         Entities<'a> );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, position, blockers, doors, entities) = data;
 
         map.populate_blocked();
         map.clear_contents();
+        map.closed_doors.clear();
         for (entity, position) in (&entities, &position).join() {
-            let idx = Map::xy_idx(position.x, position.y);
+            let idx = map.xy_idx(position.x, position.y);
 
             // if they block, update the blocking list
             let _p : Option<&BlocksTile> = blockers.get(entity);
@@ -25,6 +27,10 @@ impl<'a> System<'a> for MapIndexingSystem {
                 map.blocked[idx] = true;
             }
 
+            if let Some(door) = doors.get(entity) {
+                if !door.open { map.closed_doors.insert(idx); }
+            }
+
             // push the entity to the appropriate index slot. 
             // it's a copy type, so we don't need to clone it
             map.contents[idx].push(entity);
@@ -1,21 +1,27 @@
 extern crate rltk;
 use rltk::{ RGB };
+use crate::RandomNumberGenerator;
 
 extern crate specs;
 use specs::prelude::*;
-use super::{ 
+use super::{
     CombatStats,
     WantsToMelee,
     Name,
-    SufferDamage,
     GameLog,
-    MeleePowerBonus,
-    DefenseBonus,
+    Attributes,
+    Skill,
+    Skills,
+    MeleeWeapon,
+    WeaponAttribute,
+    Wearable,
+    NaturalAttackDefense,
     Equipped,
+    EquipmentSlot,
     HungerClock,
     HungerState,
-    particle_system::ParticleBuilder,
-    Position, };
+    gamesystem::{ attr_bonus, skill_bonus, base_armor_class, attack_hits },
+    effects::{ EffectQueue, EffectType, Targets }, };
 
 pub struct MeleeCombatSystem {}
 
@@ -25,72 +31,110 @@ impl<'a> System<'a> for MeleeCombatSystem {
                         WriteStorage<'a, WantsToMelee>,
                         ReadStorage<'a, Name>,
                         ReadStorage<'a, CombatStats>,
-                        WriteStorage<'a, SufferDamage>,
-                        ReadStorage<'a, MeleePowerBonus>,
-                        ReadStorage<'a, DefenseBonus>,
+                        ReadStorage<'a, Attributes>,
+                        ReadStorage<'a, Skills>,
+                        ReadStorage<'a, MeleeWeapon>,
+                        ReadStorage<'a, Wearable>,
+                        ReadStorage<'a, NaturalAttackDefense>,
                         ReadStorage<'a, Equipped>,
-                        WriteExpect<'a, ParticleBuilder>,
+                        WriteExpect<'a, EffectQueue>,
                         ReadStorage<'a, HungerClock>,
-                        ReadStorage<'a, Position> );
+                        WriteExpect<'a, RandomNumberGenerator> );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (entities, 
-            mut log, 
-            mut wants_melee, 
-            names, 
-            combat_stats, 
-            mut inflict_damage,
-            melee_bonuses,
-            defense_bonuses,
+        let (entities,
+            mut log,
+            mut wants_melee,
+            names,
+            combat_stats,
+            attributes,
+            skills,
+            melee_weapons,
+            wearables,
+            natural,
             equipment,
-            mut particle_builder, 
+            mut effects,
             hunger_clocks,
-            positions ) = data;
+            mut rng ) = data;
 
         for (entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
             if stats.hp > 0 {
-                let mut offensive_bonus = 0;
-                for (_ie, power_bonus, equipped_by) in (&entities, &melee_bonuses, &equipment).join() {
-                    if equipped_by.owner == entity {
-                        offensive_bonus += power_bonus.power
-                    }
-                }
-
-                let hc = hunger_clocks.get(entity);
-                if let Some(hc) = hc {
-                    if hc.state == HungerState::WellFed {
-                        offensive_bonus += 1;
-                    }
-                }
-
                 let target_stats = combat_stats.get(wants_melee.target).unwrap();
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_melee.target).unwrap();
 
-                    let mut defensive_bonus = 0;
-                    for (_ie, defense_bonus, equipped_by) in (&entities, &defense_bonuses, &equipment).join() {
-                        if equipped_by.owner == wants_melee.target {
-                            defensive_bonus += defense_bonus.defense;
+                    // bare hands, unless there's a weapon or natural attack to use instead
+                    let mut weapon = MeleeWeapon {
+                        attribute: WeaponAttribute::Might,
+                        damage_n_dice: 1,
+                        damage_die_type: 4,
+                        damage_bonus: 0,
+                        hit_bonus: 0,
+                    };
+                    if let Some(nat) = natural.get(entity) {
+                        if !nat.attacks.is_empty() {
+                            let attack = &nat.attacks[rng.roll_dice(1, nat.attacks.len() as i32) as usize - 1];
+                            weapon.damage_n_dice = attack.damage_n_dice;
+                            weapon.damage_die_type = attack.damage_die_type;
+                            weapon.damage_bonus = attack.damage_bonus;
+                            weapon.hit_bonus = attack.hit_bonus;
+                        }
+                    }
+                    for (wielded, equipped_by) in (&melee_weapons, &equipment).join() {
+                        if equipped_by.owner == entity && equipped_by.slot == EquipmentSlot::Melee {
+                            weapon = wielded.clone();
                         }
                     }
 
+                    let attacker_attributes = attributes.get(entity);
+                    let might_bonus = attacker_attributes.map_or(0, |a| attr_bonus(a.might.value()));
+                    let attribute_bonus = match weapon.attribute {
+                        WeaponAttribute::Might => might_bonus,
+                        WeaponAttribute::Quickness => attacker_attributes.map_or(0, |a| attr_bonus(a.quickness.value())),
+                    };
+                    let melee_skill = skills.get(entity).map_or(0, |s| skill_bonus(Skill::Melee, s));
+                    let mut hit_bonus = attribute_bonus + melee_skill + weapon.hit_bonus;
+                    if let Some(hc) = hunger_clocks.get(entity) {
+                        if hc.state == HungerState::WellFed {
+                            hit_bonus += 1;
+                        }
+                    }
 
-                    let damage = i32::max(0, stats.power + offensive_bonus - target_stats.defense - defensive_bonus);
-                    if damage == 0 {
-                        log.entries.insert(0, format!("{} is unable to hurt {}", &name.name, &target_name.name));
-                    } else {
-                        let pos = positions.get(wants_melee.target);
-                        if let Some(pos) = pos {
-                            particle_builder.requests(
-                                pos.x, 
-                                pos.y, 
-                                RGB::named(rltk::ORANGE),
-                                RGB::named(rltk::BLACK),
-                                rltk::to_cp437('‼'),
-                                200.0);
+                    let mut armor_class = base_armor_class(
+                        natural.get(wants_melee.target),
+                        attributes.get(wants_melee.target),
+                        skills.get(wants_melee.target));
+                    for (wearable, equipped_by) in (&wearables, &equipment).join() {
+                        if equipped_by.owner == wants_melee.target {
+                            armor_class += wearable.armor_class;
                         }
+                    }
+
+                    let natural_roll = rng.roll_dice(1, 20);
+                    let target = Targets::Single { target: wants_melee.target };
+                    if attack_hits(natural_roll, hit_bonus, armor_class) {
+                        let damage_roll = rng.roll_dice(weapon.damage_n_dice, weapon.damage_die_type);
+                        let damage = i32::max(1, damage_roll + weapon.damage_bonus + might_bonus);
+                        effects.add(
+                            Some(entity),
+                            EffectType::Particle {
+                                glyph: rltk::to_cp437('‼'),
+                                fg: RGB::named(rltk::ORANGE),
+                                bg: RGB::named(rltk::BLACK),
+                                lifetime: 200.0 },
+                            target.clone());
                         log.entries.insert(0, format!("{} hits {} for {} damage", &name.name, &target_name.name, damage));
-                        inflict_damage.insert(wants_melee.target, SufferDamage { amount: damage }).expect("Unable to do damage");
+                        effects.add(Some(entity), EffectType::Damage { amount: damage }, target);
+                    } else {
+                        effects.add(
+                            Some(entity),
+                            EffectType::Particle {
+                                glyph: rltk::to_cp437('‼'),
+                                fg: RGB::named(rltk::CYAN),
+                                bg: RGB::named(rltk::BLACK),
+                                lifetime: 200.0 },
+                            target);
+                        log.entries.insert(0, format!("{} attacks {}, but misses", &name.name, &target_name.name));
                     }
                 }
             }
@@ -4,12 +4,21 @@ use rltk::{ VirtualKeyCode, RGB, Rltk, Console };
 use super::{
     State,
     RunState,
+    saveload_system,
+    gui::ItemMenuResult,
     gui::MainMenuSelection,
     gui::MainMenuResult, };
 
 
+/// The save slots, read from disk the first time a menu needs them after
+/// it's entered rather than on every frame
+fn saves(gs: &mut State) -> &saveload_system::SaveListing {
+    gs.save_listing.get_or_insert_with(saveload_system::list_saves)
+}
+
 pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
-    let save_exists = super::saveload_system::does_save_exist();
+    let save_exists = saves(gs).iter().any(|s| s.is_some());
+    let permadeath = gs.ecs.fetch::<saveload_system::SaveMode>().permadeath;
     let runstate = gs.ecs.fetch::<RunState>();
     ctx.print_color_centered(15, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Rust Roguelike Tutorial");
 
@@ -18,7 +27,13 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
         if save_exists {
             toggle_text(ctx, selection, MainMenuSelection::LoadGame, 25, "Load Game");
         }
-        toggle_text(ctx, selection, MainMenuSelection::Quit, 26, "Quit");
+        let mode = if permadeath { "Permadeath: On" } else { "Permadeath: Off" };
+        toggle_text(ctx, selection, MainMenuSelection::Permadeath, 26, mode);
+        toggle_text(ctx, selection, MainMenuSelection::Quit, 27, "Quit");
+
+        if let Some(error) = &gs.ecs.fetch::<saveload_system::LoadStatus>().error {
+            ctx.print_color_centered(30, RGB::named(rltk::RED), RGB::named(rltk::BLACK), error);
+        }
 
         match ctx.key {
             None => return MainMenuResult::NoSelection { selected: selection },
@@ -30,7 +45,8 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                         match selection {
                             MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
                             MainMenuSelection::LoadGame => newselection = MainMenuSelection::NewGame,
-                            MainMenuSelection::Quit => newselection = MainMenuSelection::LoadGame
+                            MainMenuSelection::Permadeath => newselection = MainMenuSelection::LoadGame,
+                            MainMenuSelection::Quit => newselection = MainMenuSelection::Permadeath
                         }
 
                         if !save_exists && newselection == MainMenuSelection::LoadGame {
@@ -42,12 +58,13 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                         let mut newselection;
                         match selection {
                             MainMenuSelection::NewGame => newselection = MainMenuSelection::LoadGame,
-                            MainMenuSelection::LoadGame => newselection = MainMenuSelection::Quit,
+                            MainMenuSelection::LoadGame => newselection = MainMenuSelection::Permadeath,
+                            MainMenuSelection::Permadeath => newselection = MainMenuSelection::Quit,
                             MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame
                         }
 
                         if !save_exists && newselection == MainMenuSelection::LoadGame {
-                            newselection = MainMenuSelection::Quit;
+                            newselection = MainMenuSelection::Permadeath;
                         }
                         return MainMenuResult::NoSelection { selected: newselection }
                     }
@@ -61,6 +78,75 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
     MainMenuResult::NoSelection { selected: MainMenuSelection::NewGame }
 }
 
+/// Lists the save slots and returns the one picked. Empty slots can only
+/// be picked when `allow_empty` is set (i.e. when saving).
+pub fn save_slot_menu(gs: &mut State, ctx: &mut Rltk, title: &str, allow_empty: bool) -> (ItemMenuResult, Option<usize>) {
+    let mut saves = &saves(gs)[..];
+    if allow_empty {
+        // the autosave slot is written by the game, not the player
+        saves = &saves[.. saveload_system::AUTOSAVE_SLOT];
+    }
+    let count = saves.len();
+
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(10, top-2, 60, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(13, top-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    ctx.print_color(13, top+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+
+    for (j, save) in saves.iter().enumerate() {
+        let y = top + j as i32;
+        ctx.set(12, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(13, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
+        ctx.set(14, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+        let label = if j == saveload_system::AUTOSAVE_SLOT { "Auto: " } else { "" };
+        match save {
+            None => ctx.print_color(16, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), &format!("{}Empty", label)),
+            Some(Ok(metadata)) => ctx.print(16, y, &format!("{}{}", label, metadata.describe())),
+            Some(Err(_)) => ctx.print_color(16, y, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &format!("{}Unreadable save", label)),
+        }
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        let slot = selection as usize;
+                        if allow_empty || saves[slot].is_some() {
+                            return (ItemMenuResult::Selected, Some(slot));
+                        }
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// The save slot picker, plus a line showing the format the game will be
+/// saved in; TAB switches between them.
+pub fn save_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<usize>) {
+    let format = {
+        let mut save_mode = gs.ecs.fetch_mut::<saveload_system::SaveMode>();
+        if ctx.key == Some(VirtualKeyCode::Tab) {
+            save_mode.format = match save_mode.format {
+                saveload_system::SaveFormat::Json => saveload_system::SaveFormat::Binary,
+                saveload_system::SaveFormat::Binary => saveload_system::SaveFormat::Json,
+            };
+        }
+        save_mode.format
+    };
+
+    let result = save_slot_menu(gs, ctx, "Save to Which Slot", true);
+    let bottom = (25 - (saveload_system::SAVE_SLOTS / 2) + saveload_system::SAVE_SLOTS) as i32 + 1;
+    ctx.print_color(40, bottom, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK),
+        &format!("TAB format: {}", format.name()));
+    result
+}
+
 fn toggle_text(ctx: &mut Rltk, selection: MainMenuSelection, current: MainMenuSelection, y: i32, label: &str) {
     if selection == current {
         ctx.print_color_centered(
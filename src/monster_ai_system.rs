@@ -7,14 +7,27 @@ use super::{
     Monster,
     RunState,
     Position,
-    Confusion,
+    StatusEffect,
+    StatusKind,
+    TurnCounter,
     WantsToMelee,
-    particle_system::ParticleBuilder };
+    Door,
+    BlocksTile,
+    BlocksVisibility,
+    Renderable,
+    effects::{ EffectQueue, EffectType, Targets },
+    status_system::{ has_status, random_direction } };
 
 extern crate rltk;
 use rltk::{Point};
+use crate::RandomNumberGenerator;
 
-pub struct MonsterAI {}
+/// Gives each monster one action. Hasted monsters get a second, in a pass
+/// of their own once the first has been resolved, so that each of their
+/// attacks lands.
+pub struct MonsterAI {
+    pub hasted_only: bool,
+}
 
 impl<'a> System<'a> for MonsterAI {
     type SystemData = ( WriteExpect<'a, Map>,
@@ -26,8 +39,14 @@ impl<'a> System<'a> for MonsterAI {
                         ReadStorage<'a, Monster>,
                         WriteStorage<'a, Position>,
                         WriteStorage<'a, WantsToMelee>,
-                        WriteStorage<'a, Confusion>,
-                        WriteExpect<'a, ParticleBuilder>, );
+                        ReadStorage<'a, StatusEffect>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteStorage<'a, Door>,
+                        WriteStorage<'a, BlocksTile>,
+                        WriteStorage<'a, BlocksVisibility>,
+                        WriteStorage<'a, Renderable>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, RandomNumberGenerator>, );
 
     fn run(&mut self, data : Self::SystemData) {
         let (mut map,
@@ -39,56 +58,100 @@ impl<'a> System<'a> for MonsterAI {
             monster, 
             mut position, 
             mut wants_to_melee,
-            mut confused,
-            mut particle_builder) = data;
+            statuses,
+            mut effects,
+            mut doors,
+            mut blocks_movement,
+            mut blocks_visibility,
+            mut renderables,
+            turns,
+            mut rng) = data;
 
         if *runstate != RunState::MonsterTurn { return; }
 
+        let mut opened_door = false;
+
         for (entity, mut viewshed, _m, mut pos) in (&entities, &mut viewshed, &monster, &mut position).join() {
-            let mut can_act = true;
-            let is_confused = confused.get_mut(entity);
-            if let Some(i_am_confused) = is_confused {
-                i_am_confused.turns -= 1;
-                if i_am_confused.turns < 1 { confused.remove(entity); }
-                can_act = false;
-                particle_builder.requests(
-                    pos.x, 
-                    pos.y,
-                    rltk::RGB::named(rltk::MAGENTA),
-                    rltk::RGB::named(rltk::BLACK),
-                    rltk::to_cp437('?'),
-                    200.0);
+            if has_status(&statuses, entity, StatusKind::Paralysis) { continue; }
+            // slowed monsters lose every other turn
+            if has_status(&statuses, entity, StatusKind::Slow) && turns.turns % 2 == 0 { continue; }
+            if self.hasted_only && !has_status(&statuses, entity, StatusKind::Haste) { continue; }
+
+            if has_status(&statuses, entity, StatusKind::Confusion) {
+                effects.add(
+                    Some(entity),
+                    EffectType::Particle {
+                        glyph: rltk::to_cp437('?'),
+                        fg: rltk::RGB::named(rltk::MAGENTA),
+                        bg: rltk::RGB::named(rltk::BLACK),
+                        lifetime: 200.0 },
+                    Targets::Tile { tile_idx: map.xy_idx(pos.x, pos.y) });
+
+                let (delta_x, delta_y) = random_direction(&mut rng);
+                let (x, y) = (pos.x + delta_x, pos.y + delta_y);
+                if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { continue; }
+                let dest_idx = map.xy_idx(x, y);
+                if !map.blocked[dest_idx] {
+                    let idx = map.xy_idx(pos.x, pos.y);
+                    map.blocked[idx] = false;
+                    pos.x = x;
+                    pos.y = y;
+                    map.blocked[dest_idx] = true;
+                    viewshed.dirty = true;
+                }
+                continue;
             }
 
-            if can_act {
-                let distance = rltk::DistanceAlg::Pythagoras.distance2d(
-                    Point::new(pos.x, pos.y), 
-                    *player_pos);
-
-                if distance < 1.5 {
-                    wants_to_melee.
-                        insert(entity, WantsToMelee { target: *player }).
-                        expect("Unable to insert attack");
-                } else if viewshed.tiles.contains(&*player_pos) {
-
-                    // path to the player
-                    let path = rltk::a_star_search(
-                        Map::xy_idx(pos.x, pos.y) as i32,
-                        Map::xy_idx(player_pos.x, player_pos.y) as i32,
-                        &mut *map);
-
-                    if path.success && path.steps.len() > 1 {
-                        let mut idx = Map::xy_idx(pos.x, pos.y);
-                        map.blocked[idx] = false;
-
-                        pos.x = path.steps[1] % map.width;
-                        pos.y = path.steps[1] / map.width;
-                        idx = Map::xy_idx(pos.x, pos.y);
-                        map.blocked[idx] = true;
-                        viewshed.dirty = true;
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(
+                Point::new(pos.x, pos.y), 
+                *player_pos);
+
+            if distance < 1.5 {
+                wants_to_melee.
+                    insert(entity, WantsToMelee { target: *player }).
+                    expect("Unable to insert attack");
+            } else if viewshed.tiles.contains(&*player_pos) {
+
+                // path to the player
+                let path = rltk::a_star_search(
+                    map.xy_idx(pos.x, pos.y) as i32,
+                    map.xy_idx(player_pos.x, player_pos.y) as i32,
+                    &mut *map);
+
+                if path.success && path.steps.len() > 1 && map.closed_doors.contains(&(path.steps[1] as usize)) {
+                    // a closed door in the way; opening it takes the turn
+                    let door_idx = path.steps[1] as usize;
+                    for potential_door in map.contents[door_idx].iter() {
+                        if let Some(door) = doors.get_mut(*potential_door) {
+                            door.open = true;
+                            blocks_movement.remove(*potential_door);
+                            blocks_visibility.remove(*potential_door);
+                            if let Some(glyph) = renderables.get_mut(*potential_door) {
+                                glyph.glyph = rltk::to_cp437('/');
+                            }
+                        }
                     }
+                    map.closed_doors.remove(&door_idx);
+                    map.blocked[door_idx] = false;
+                    opened_door = true;
+                } else if path.success && path.steps.len() > 1 {
+                    let mut idx = map.xy_idx(pos.x, pos.y);
+                    map.blocked[idx] = false;
+
+                    pos.x = path.steps[1] % map.width;
+                    pos.y = path.steps[1] / map.width;
+                    idx = map.xy_idx(pos.x, pos.y);
+                    map.blocked[idx] = true;
+                    viewshed.dirty = true;
                 }
             }
         }
+
+        // everyone might see through the doorway now
+        if opened_door {
+            for viewshed in (&mut viewshed).join() {
+                viewshed.dirty = true;
+            }
+        }
     }
 }
@@ -2,7 +2,7 @@ extern crate specs;
 use specs::prelude::*;
 
 extern crate rltk;
-use rltk::{ Rltk, RGB };
+use rltk::{ RGB };
 
 use super::{ 
     ParticleLifetime,
@@ -60,13 +60,13 @@ impl<'a> System<'a> for ParticleSpawnSystem {
     }
 }
 
-pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
+pub fn cull_dead_particles(ecs: &mut World, frame_time_ms: f32) {
     let mut dead_particles : Vec<Entity> = Vec::new();
     {
         let mut particles = ecs.write_storage::<ParticleLifetime>();
         let entities = ecs.entities();
         for (entity, mut particle) in (&entities, &mut particles).join() {
-            particle.lifetime_ms -= ctx.frame_time_ms;
+            particle.lifetime_ms -= frame_time_ms;
             if particle.lifetime_ms < 0.0 {
                 dead_particles.push(entity);
             }
@@ -16,8 +16,26 @@ use super::{
     Viewshed, 
     CombatStats, 
     WantsToPickupItem,
+    WantsToUseItem,
+    WantsToDropItem,
+    WantsToShoot,
+    RangedWeapon,
+    Ammunition,
+    Equipped,
+    EquipmentSlot,
     GameLog,
-    WantsToMelee};
+    WantsToMelee,
+    Door,
+    BlocksTile,
+    BlocksVisibility,
+    Attributes,
+    AttributeName,
+    Experience,
+    StatusEffect,
+    StatusKind,
+    gamesystem::{ player_hp_at_level, xp_to_level_up },
+    status_system::{ has_status, random_direction },
+    Renderable};
 use std::cmp::{min, max};
 
 /// Check the map to see if the player can move into the 
@@ -30,6 +48,11 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let combat_stats = ecs.read_storage::<CombatStats>();
     let map = ecs.fetch::<Map>();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut blocks_movement = ecs.write_storage::<BlocksTile>();
+    let mut blocks_visibility = ecs.write_storage::<BlocksVisibility>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let mut opened_door = false;
 
     for (entity, _play, pos, viewshed) in (&entities, &players, &mut positions, &mut viewsheds).join() {
         if pos.x + delta_x < 1 || 
@@ -37,7 +60,7 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             pos.y + delta_y < 1 ||
             pos.y + delta_y > map.height -1 { return; }
 
-        let dest_idx = Map::xy_idx(pos.x + delta_x, pos.y + delta_y);
+        let dest_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
         for potential_target in map.contents[dest_idx].iter() {
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
@@ -46,11 +69,24 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                     expect("Add target failed!");
                 return;
             }
+
+            // bumping a closed door opens it, and that's the move
+            if let Some(door) = doors.get_mut(*potential_target) {
+                if !door.open {
+                    door.open = true;
+                    blocks_movement.remove(*potential_target);
+                    blocks_visibility.remove(*potential_target);
+                    if let Some(glyph) = renderables.get_mut(*potential_target) {
+                        glyph.glyph = rltk::to_cp437('/');
+                    }
+                    opened_door = true;
+                }
+            }
         }
 
-        if !map.blocked[dest_idx] {
-            pos.x = min(79, max(0, pos.x + delta_x));
-            pos.y = min(49, max(0, pos.y + delta_y));
+        if !opened_door && !map.blocked[dest_idx] {
+            pos.x = min(map.width - 1, max(0, pos.x + delta_x));
+            pos.y = min(map.height - 1, max(0, pos.y + delta_y));
             
             // update the resource for this user's position
             viewshed.dirty = true;
@@ -59,61 +95,280 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             ppos.y = pos.y;
         }
     }
+
+    // everyone might see through the doorway now
+    if opened_door {
+        for viewshed in (&mut viewsheds).join() {
+            viewshed.dirty = true;
+        }
+    }
+}
+
+/// A single action the player can take on their turn, independent of
+/// how it was entered.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum PlayerCommand {
+    Move { delta_x: i32, delta_y: i32 },
+    PickUp,
+    Wait,
+    Descend,
+    Ascend,
+    UseItem { item: Entity, target: Option<Point> },
+    DropItem { item: Entity },
+    /// Shoots the equipped ranged weapon at the tile, or at the nearest
+    /// monster in range if no tile is given
+    Fire { target: Option<Point> },
+    /// Only accepted while the level up screen is showing
+    LevelUp { attribute: AttributeName },
+}
+
+impl PlayerCommand {
+    /// Maps a script character to a command, using the same letters as the
+    /// keyboard controls ('>' descends, '<' ascends, 's' waits and 'f'
+    /// fires at the nearest monster). The
+    /// capitals M, F, Q and I pick the attribute to raise on a level up.
+    pub fn from_char(c: char) -> Option<PlayerCommand> {
+        match c {
+            'h' => Some(PlayerCommand::Move { delta_x: -1, delta_y: 0 }),
+            'l' => Some(PlayerCommand::Move { delta_x: 1, delta_y: 0 }),
+            'k' => Some(PlayerCommand::Move { delta_x: 0, delta_y: -1 }),
+            'j' => Some(PlayerCommand::Move { delta_x: 0, delta_y: 1 }),
+            'y' => Some(PlayerCommand::Move { delta_x: 1, delta_y: -1 }),
+            'u' => Some(PlayerCommand::Move { delta_x: -1, delta_y: -1 }),
+            'n' => Some(PlayerCommand::Move { delta_x: 1, delta_y: 1 }),
+            'b' => Some(PlayerCommand::Move { delta_x: -1, delta_y: 1 }),
+            'g' => Some(PlayerCommand::PickUp),
+            's' => Some(PlayerCommand::Wait),
+            'f' => Some(PlayerCommand::Fire { target: None }),
+            '>' => Some(PlayerCommand::Descend),
+            '<' => Some(PlayerCommand::Ascend),
+            'M' => Some(PlayerCommand::LevelUp { attribute: AttributeName::Might }),
+            'F' => Some(PlayerCommand::LevelUp { attribute: AttributeName::Fitness }),
+            'Q' => Some(PlayerCommand::LevelUp { attribute: AttributeName::Quickness }),
+            'I' => Some(PlayerCommand::LevelUp { attribute: AttributeName::Intelligence }),
+            _ => None
+        }
+    }
+}
+
+/// Applies a command for the player and returns the state the game
+/// should move to.
+pub fn apply_command(ecs: &mut World, command: PlayerCommand) -> RunState {
+    let player = *ecs.fetch::<Entity>();
+    let paralysed = has_status(&ecs.read_storage::<StatusEffect>(), player, StatusKind::Paralysis);
+    let confused = has_status(&ecs.read_storage::<StatusEffect>(), player, StatusKind::Confusion);
+    if paralysed && !matches!(command, PlayerCommand::LevelUp { .. }) {
+        ecs.fetch_mut::<GameLog>().entries.insert(0, "You are paralysed and can't move!".to_string());
+        return RunState::PlayerTurn;
+    }
+
+    match command {
+        PlayerCommand::Move { .. } if confused => {
+            let (delta_x, delta_y) = random_direction(&mut ecs.fetch_mut::<crate::RandomNumberGenerator>());
+            try_move_player(delta_x, delta_y, ecs);
+        }
+        PlayerCommand::Move { delta_x, delta_y } => try_move_player(delta_x, delta_y, ecs),
+        PlayerCommand::PickUp => get_item(ecs),
+        PlayerCommand::Wait => return skip_turn(ecs),
+        PlayerCommand::Descend => {
+            if try_next_level(ecs) {
+                return RunState::NextLevel;
+            }
+        }
+        PlayerCommand::Ascend => {
+            if try_previous_level(ecs) {
+                return RunState::PreviousLevel;
+            }
+        }
+        PlayerCommand::UseItem { item, target } => {
+            let mut intent = ecs.write_storage::<WantsToUseItem>();
+            intent.insert(*ecs.fetch::<Entity>(), WantsToUseItem{ item, target }).expect("Unable to insert intent");
+        }
+        PlayerCommand::DropItem { item } => {
+            let mut intent = ecs.write_storage::<WantsToDropItem>();
+            intent.insert(*ecs.fetch::<Entity>(), WantsToDropItem{ item }).expect("Unable to insert intent");
+        }
+        PlayerCommand::Fire { target } => {
+            let range = match ready_to_fire(ecs) {
+                Some((_, range)) => range,
+                None => return RunState::AwaitingInput
+            };
+            match target.or_else(|| nearest_target(ecs, range)) {
+                Some(target) if target != *ecs.fetch::<Point>() => {
+                    if !in_line_of_fire(ecs, target, range) {
+                        ecs.fetch_mut::<GameLog>().entries.insert(0, "You can't shoot that from here".to_string());
+                        return RunState::AwaitingInput;
+                    }
+                    let mut intent = ecs.write_storage::<WantsToShoot>();
+                    intent.insert(player, WantsToShoot { target }).expect("Unable to insert intent");
+                }
+                _ => {
+                    ecs.fetch_mut::<GameLog>().entries.insert(0, "There is nothing in range to shoot at".to_string());
+                    return RunState::AwaitingInput;
+                }
+            }
+        }
+        PlayerCommand::LevelUp { attribute } => return level_up(ecs, attribute),
+    }
+
+    RunState::PlayerTurn
+}
+
+/// The player's ranged weapon and its range, if they have one equipped
+/// and the ammunition for it; logs what's missing if not
+pub fn ready_to_fire(ecs: &World) -> Option<(Entity, i32)> {
+    let player = *ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let weapons = ecs.read_storage::<RangedWeapon>();
+    let ammunition = ecs.read_storage::<Ammunition>();
+    let equipment = ecs.read_storage::<Equipped>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let weapon = (&entities, &weapons, &equipment).join()
+        .find(|(_, _, equipped_by)| equipped_by.owner == player && equipped_by.slot == EquipmentSlot::Ranged);
+    let (weapon_entity, weapon) = match weapon {
+        Some((weapon_entity, weapon, _)) => (weapon_entity, weapon),
+        None => {
+            gamelog.entries.insert(0, "You have nothing to shoot with".to_string());
+            return None;
+        }
+    };
+
+    let loaded = (&ammunition, &equipment).join()
+        .any(|(ammo, equipped_by)| equipped_by.owner == player && equipped_by.slot == EquipmentSlot::Ammo && ammo.kind == weapon.ammo && ammo.count > 0);
+    if !loaded {
+        gamelog.entries.insert(0, "You have nothing to shoot".to_string());
+        return None;
+    }
+    Some((weapon_entity, weapon.range))
+}
+
+/// Whether the player can see `target` and it is within `range`, the
+/// same tiles the targeting screen offers
+fn in_line_of_fire(ecs: &World, target: Point, range: i32) -> bool {
+    let player = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let visible = match viewsheds.get(*player) {
+        Some(viewshed) => viewshed.tiles.contains(&target),
+        None => false
+    };
+    visible && rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, target) <= range as f32
+}
+
+/// The closest monster the player can see within `range`
+fn nearest_target(ecs: &World, range: i32) -> Option<Point> {
+    let player = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let monsters = ecs.read_storage::<Monster>();
+    let map = ecs.fetch::<Map>();
+
+    let mut nearest : Option<(f32, Point)> = None;
+    for tile in viewsheds.get(*player)?.tiles.iter() {
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *tile);
+        if distance > range as f32 { continue; }
+        let idx = map.xy_idx(tile.x, tile.y);
+        let occupied = map.contents[idx].iter().any(|e| monsters.get(*e).is_some());
+        if occupied && nearest.is_none_or(|(best, _)| distance < best) {
+            nearest = Some((distance, *tile));
+        }
+    }
+    nearest.map(|(_, tile)| tile)
+}
+
+/// Whether the player has enough experience to go up a level
+pub fn level_up_due(ecs: &World) -> bool {
+    let player = ecs.fetch::<Entity>();
+    let experience = ecs.read_storage::<Experience>();
+    match experience.get(*player) {
+        Some(exp) => exp.xp >= xp_to_level_up(exp.level),
+        None => false
+    }
+}
+
+/// Takes the player up a level, raising the chosen attribute and
+/// restoring them to their new maximum hit points. Stays on the level up
+/// screen if there's enough experience for another.
+pub fn level_up(ecs: &mut World, attribute: AttributeName) -> RunState {
+    if !level_up_due(ecs) { return RunState::AwaitingInput; }
+
+    let player = *ecs.fetch::<Entity>();
+    let mut experience = ecs.write_storage::<Experience>();
+    let mut attributes = ecs.write_storage::<Attributes>();
+    let mut stats = ecs.write_storage::<CombatStats>();
+    let exp = experience.get_mut(player).unwrap();
+    exp.level += 1;
+    if let Some(attributes) = attributes.get_mut(player) {
+        attributes.get_mut(attribute).base += 1;
+        if let Some(stats) = stats.get_mut(player) {
+            stats.max_hp = player_hp_at_level(attributes.fitness.value(), exp.level);
+            stats.hp = stats.max_hp;
+        }
+    }
+
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    gamelog.entries.insert(0, format!("Congratulations, you are now level {}!", exp.level));
+    if exp.xp >= xp_to_level_up(exp.level) { RunState::LevelUp } else { RunState::AwaitingInput }
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    match ctx.key {
+    let command = match ctx.key {
         None => { return RunState::AwaitingInput },
         Some(key) => match key {
             VirtualKeyCode::Left |
             VirtualKeyCode::Numpad4 |
-            VirtualKeyCode::H => try_move_player(-1, 0, &mut gs.ecs),
+            VirtualKeyCode::H => PlayerCommand::Move { delta_x: -1, delta_y: 0 },
 
             VirtualKeyCode::Right |
             VirtualKeyCode::Numpad6 |
-            VirtualKeyCode::L => try_move_player(1, 0, &mut gs.ecs),
+            VirtualKeyCode::L => PlayerCommand::Move { delta_x: 1, delta_y: 0 },
 
             VirtualKeyCode::Up |
             VirtualKeyCode::Numpad8 |
-            VirtualKeyCode::K => try_move_player(0, -1, &mut gs.ecs),
+            VirtualKeyCode::K => PlayerCommand::Move { delta_x: 0, delta_y: -1 },
 
             VirtualKeyCode::Down |
             VirtualKeyCode::Numpad2 |
-            VirtualKeyCode::J => try_move_player(0, 1, &mut gs.ecs),
+            VirtualKeyCode::J => PlayerCommand::Move { delta_x: 0, delta_y: 1 },
 
             VirtualKeyCode::Numpad9 |
-            VirtualKeyCode::Y => try_move_player(1, -1, &mut gs.ecs),
+            VirtualKeyCode::Y => PlayerCommand::Move { delta_x: 1, delta_y: -1 },
 
             VirtualKeyCode::Numpad7 |
-            VirtualKeyCode::U => try_move_player(-1, -1, &mut gs.ecs),
+            VirtualKeyCode::U => PlayerCommand::Move { delta_x: -1, delta_y: -1 },
 
             VirtualKeyCode::Numpad3 |
-            VirtualKeyCode::N => try_move_player(1, 1, &mut gs.ecs),
+            VirtualKeyCode::N => PlayerCommand::Move { delta_x: 1, delta_y: 1 },
 
             VirtualKeyCode::Numpad1 |
-            VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
+            VirtualKeyCode::B => PlayerCommand::Move { delta_x: -1, delta_y: 1 },
 
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
+            VirtualKeyCode::G => PlayerCommand::PickUp,
             VirtualKeyCode::D => return RunState::ShowDropItem,
 
             VirtualKeyCode::I => return RunState::ShowInventory,
 
+            VirtualKeyCode::F => {
+                return match ready_to_fire(&gs.ecs) {
+                    Some((weapon, range)) => RunState::ShowTargeting { range, item: weapon },
+                    None => RunState::AwaitingInput
+                };
+            }
+
             VirtualKeyCode::Escape => return RunState::SaveGame,
 
-            VirtualKeyCode::Numpad5 => return skip_turn(&mut gs.ecs),
-            VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
+            VirtualKeyCode::Numpad5 => PlayerCommand::Wait,
+            VirtualKeyCode::Space => PlayerCommand::Wait,
 
-            VirtualKeyCode::Period => {
-                if try_next_level(&mut gs.ecs) {
-                    return RunState::NextLevel;
-                }
-            },
+            VirtualKeyCode::Period => PlayerCommand::Descend,
+            VirtualKeyCode::Comma => PlayerCommand::Ascend,
 
             _ => { return RunState::AwaitingInput }
         },
-    }
+    };
 
-    RunState::PlayerTurn
+    apply_command(&mut gs.ecs, command)
 }
 
 fn skip_turn(ecs: &mut World) -> RunState {
@@ -125,7 +380,7 @@ fn skip_turn(ecs: &mut World) -> RunState {
     let mut can_heal = true;
     let viewshed = viewsheds.get(*player).unwrap();
     for tile in viewshed.tiles.iter() {
-        let idx = Map::xy_idx(tile.x, tile.y);
+        let idx = worldmap.xy_idx(tile.x, tile.y);
         for entity_id in worldmap.contents[idx].iter() {
             let mob = monsters.get(*entity_id);
             match mob {
@@ -147,7 +402,7 @@ fn skip_turn(ecs: &mut World) -> RunState {
 fn try_next_level(ecs: &mut World) -> bool {
     let player_pos = ecs.fetch::<Point>();
     let map = ecs.fetch::<Map>();
-    let player_idx = Map::xy_idx(player_pos.x, player_pos.y);
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
     if map.tiles[player_idx] == TileType::DownStairs {
         true
     } else {
@@ -157,6 +412,19 @@ fn try_next_level(ecs: &mut World) -> bool {
     }
 }
 
+fn try_previous_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::UpStairs {
+        true
+    } else {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog.entries.insert(0, "There is no way up from here".to_string());
+        false
+    }
+}
+
 fn get_item(ecs: &mut World) {
     let player_pos = ecs.fetch::<Point>();
     let player_entity = ecs.fetch::<Entity>();
@@ -1,4 +1,4 @@
-use rltk::RandomNumberGenerator;
+use crate::RandomNumberGenerator;
 
 #[derive(Debug)]
 pub struct RandomEntry {
@@ -0,0 +1,173 @@
+extern crate rltk;
+use rltk::{ RGB, Point };
+use crate::RandomNumberGenerator;
+
+extern crate specs;
+use specs::prelude::*;
+
+extern crate bresenham;
+use bresenham::Bresenham;
+
+use super::{
+    Map,
+    CombatStats,
+    WantsToShoot,
+    Name,
+    Position,
+    GameLog,
+    Attributes,
+    Skill,
+    Skills,
+    RangedWeapon,
+    Ammunition,
+    WeaponAttribute,
+    Wearable,
+    NaturalAttackDefense,
+    Equipped,
+    EquipmentSlot,
+    tile_opaque,
+    tile_walkable,
+    gamesystem::{ attr_bonus, skill_bonus, base_armor_class, range_penalty, attack_hits },
+    effects::{ EffectQueue, EffectType, Targets }, };
+
+/// The tiles a shot from `start` at `target` passes through, in order. It
+/// stops short of the first wall, and at the first thing in the way.
+pub fn line_of_fire(map: &Map, start: Point, target: Point) -> Vec<Point> {
+    let mut path = Vec::new();
+    let line = Bresenham::new((start.x as isize, start.y as isize), (target.x as isize, target.y as isize))
+        .skip(1)
+        .map(|(x, y)| Point::new(x as i32, y as i32))
+        .chain(std::iter::once(target));
+    for point in line {
+        let idx = map.xy_idx(point.x, point.y);
+        if tile_opaque(map.tiles[idx]) { break; }
+        path.push(point);
+        if map.blocked[idx] && tile_walkable(map.tiles[idx]) { break; }
+    }
+    path
+}
+
+fn particle(glyph: char, fg: (u8, u8, u8), lifetime: f32) -> EffectType {
+    EffectType::Particle {
+        glyph: rltk::to_cp437(glyph),
+        fg: RGB::named(fg),
+        bg: RGB::named(rltk::BLACK),
+        lifetime
+    }
+}
+
+/// Shoots the equipped ranged weapon. Hitting gets harder the further
+/// away the target is, and every shot leaves its arrow, bolt or stone on
+/// the floor where it landed.
+pub struct RangedCombatSystem {}
+
+impl<'a> System<'a> for RangedCombatSystem {
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, WantsToShoot>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Attributes>,
+                        ReadStorage<'a, Skills>,
+                        ReadStorage<'a, RangedWeapon>,
+                        WriteStorage<'a, Ammunition>,
+                        ReadStorage<'a, Wearable>,
+                        ReadStorage<'a, NaturalAttackDefense>,
+                        ReadStorage<'a, Equipped>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteExpect<'a, RandomNumberGenerator> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities,
+            mut log,
+            mut wants_shoot,
+            names,
+            positions,
+            combat_stats,
+            attributes,
+            skills,
+            ranged_weapons,
+            mut ammunition,
+            wearables,
+            natural,
+            equipment,
+            map,
+            mut effects,
+            mut rng) = data;
+
+        for (entity, wants_shoot, name, pos) in (&entities, &wants_shoot, &names, &positions).join() {
+            let weapon = (&ranged_weapons, &equipment).join()
+                .find(|(_, equipped_by)| equipped_by.owner == entity && equipped_by.slot == EquipmentSlot::Ranged)
+                .map(|(weapon, _)| weapon.clone());
+            let weapon = match weapon { Some(weapon) => weapon, None => continue };
+            let ammo_entity = (&entities, &ammunition, &equipment).join()
+                .find(|(_, ammo, equipped_by)| equipped_by.owner == entity && equipped_by.slot == EquipmentSlot::Ammo && ammo.kind == weapon.ammo && ammo.count > 0)
+                .map(|(ammo_entity, _, _)| ammo_entity);
+            let ammo_entity = match ammo_entity { Some(ammo_entity) => ammo_entity, None => continue };
+
+            let ammo_name = names.get(ammo_entity).map_or(String::new(), |n| n.name.clone());
+            let ammo = ammunition.get_mut(ammo_entity).unwrap();
+            ammo.count -= 1;
+            if ammo.count < 1 {
+                entities.delete(ammo_entity).expect("Unable to delete ammunition");
+            }
+
+            let start = Point::new(pos.x, pos.y);
+            let path = line_of_fire(&map, start, wants_shoot.target);
+            let trail : Vec<usize> = path.iter().map(|p| map.xy_idx(p.x, p.y)).collect();
+            effects.add(Some(entity), particle('∙', rltk::WHITE, 100.0), Targets::Tiles { tiles: trail.clone() });
+
+            let end = path.last().copied().unwrap_or(start);
+            let end_idx = map.xy_idx(end.x, end.y);
+            let victim = map.contents[end_idx].iter()
+                .find(|e| **e != entity && combat_stats.get(**e).is_some())
+                .copied();
+
+            let mut landing = end_idx;
+            match victim {
+                Some(victim) => {
+                    let victim_name = &names.get(victim).unwrap().name;
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(start, end);
+                    let attribute_bonus = attributes.get(entity).map_or(0, |a| match weapon.attribute {
+                        WeaponAttribute::Might => attr_bonus(a.might.value()),
+                        WeaponAttribute::Quickness => attr_bonus(a.quickness.value()),
+                    });
+                    let ranged_skill = skills.get(entity).map_or(0, |s| skill_bonus(Skill::Ranged, s));
+                    let hit_bonus = attribute_bonus + ranged_skill + weapon.hit_bonus - range_penalty(distance);
+
+                    let mut armor_class = base_armor_class(natural.get(victim), attributes.get(victim), skills.get(victim));
+                    for (wearable, equipped_by) in (&wearables, &equipment).join() {
+                        if equipped_by.owner == victim {
+                            armor_class += wearable.armor_class;
+                        }
+                    }
+
+                    let target = Targets::Single { target: victim };
+                    if attack_hits(rng.roll_dice(1, 20), hit_bonus, armor_class) {
+                        let damage_roll = rng.roll_dice(weapon.damage_n_dice, weapon.damage_die_type);
+                        let damage = i32::max(1, damage_roll + weapon.damage_bonus);
+                        effects.add(Some(entity), particle('‼', rltk::ORANGE, 200.0), target.clone());
+                        effects.add(Some(entity), EffectType::Damage { amount: damage }, target);
+                        log.entries.insert(0, format!("{} shoots {} for {} damage", &name.name, victim_name, damage));
+                    } else {
+                        effects.add(Some(entity), particle('‼', rltk::CYAN, 200.0), target);
+                        log.entries.insert(0, format!("{} shoots at {}, but misses", &name.name, victim_name));
+                    }
+                }
+                None => {
+                    // a shot that ends against a door or the like drops in front of it
+                    if map.blocked[end_idx] && end != start {
+                        landing = if trail.len() > 1 { trail[trail.len() - 2] } else { map.xy_idx(start.x, start.y) };
+                    }
+                    log.entries.insert(0, format!("{} shoots, but hits nothing", &name.name));
+                }
+            }
+
+            effects.add(Some(entity), EffectType::DropAmmo { name: ammo_name }, Targets::Tile { tile_idx: landing });
+        }
+
+        wants_shoot.clear();
+    }
+}
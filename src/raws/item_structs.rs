@@ -0,0 +1,46 @@
+use serde::{ Deserialize };
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub struct Item {
+    pub name: String,
+    pub renderable: Option<Renderable>,
+    pub consumable: Option<Consumable>,
+    pub weapon: Option<Weapon>,
+    pub shield: Option<Shield>,
+    pub ammo: Option<Ammo>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Renderable {
+    pub glyph: String,
+    pub fg: String,
+    pub bg: String,
+    pub order: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Consumable {
+    pub effects: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Weapon {
+    pub range: String,
+    pub attribute: String,
+    pub base_damage: String,
+    pub hit_bonus: i32,
+    /// What a ranged weapon shoots
+    pub ammo: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Ammo {
+    pub kind: String,
+    pub count: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Shield {
+    pub armor_class: i32,
+}
@@ -0,0 +1,38 @@
+use serde::{ Deserialize };
+use std::collections::HashMap;
+use super::Renderable;
+
+#[derive(Deserialize, Debug)]
+pub struct Mob {
+    pub name: String,
+    pub renderable: Option<Renderable>,
+    pub blocks_tile: bool,
+    pub hp: i32,
+    pub level: Option<i32>,
+    pub attributes: MobAttributes,
+    pub skills: Option<HashMap<String, i32>>,
+    pub natural: Option<MobNatural>,
+    pub vision_range: i32,
+}
+
+/// Attribute scores; anything left out is an average 11
+#[derive(Deserialize, Debug)]
+pub struct MobAttributes {
+    pub might: Option<i32>,
+    pub fitness: Option<i32>,
+    pub quickness: Option<i32>,
+    pub intelligence: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobNatural {
+    pub armor_class: Option<i32>,
+    pub attacks: Option<Vec<NaturalAttack>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NaturalAttack {
+    pub name: String,
+    pub hit_bonus: i32,
+    pub damage: String,
+}
@@ -0,0 +1,48 @@
+use serde::{ Deserialize };
+use std::fs;
+use std::sync::Mutex;
+
+mod item_structs;
+use item_structs::*;
+
+mod mob_structs;
+use mob_structs::*;
+
+mod prop_structs;
+use prop_structs::*;
+
+mod spawn_table_structs;
+use spawn_table_structs::*;
+
+mod rawmaster;
+pub use rawmaster::*;
+
+/// Path checked at startup so content can be edited without recompiling
+const RAW_PATH : &str = "./raws/spawns.json";
+
+/// Copy of the raw file baked into the binary, used when no file is found on disk
+const RAW_FILE : &str = include_str!("../../raws/spawns.json");
+
+#[derive(Deserialize, Debug)]
+pub struct Raws {
+    pub items: Vec<Item>,
+    pub mobs: Vec<Mob>,
+    pub props: Vec<Prop>,
+    pub spawn_table: Vec<SpawnTableEntry>,
+}
+
+lazy_static! {
+    pub static ref RAWS : Mutex<RawMaster> = Mutex::new(RawMaster::empty());
+}
+
+/// Loads the entity definitions, preferring the file on disk over
+/// the embedded copy.
+pub fn load_raws() {
+    let raw_string = match fs::read_to_string(RAW_PATH) {
+        Ok(contents) => contents,
+        Err(_) => RAW_FILE.to_string(),
+    };
+
+    let decoder : Raws = serde_json::from_str(&raw_string).expect("Unable to parse raw file");
+    RAWS.lock().unwrap().load(decoder);
+}
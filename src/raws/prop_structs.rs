@@ -0,0 +1,11 @@
+use serde::{ Deserialize };
+use super::Renderable;
+
+#[derive(Deserialize, Debug)]
+pub struct Prop {
+    pub name: String,
+    pub renderable: Option<Renderable>,
+    pub blocks_tile: Option<bool>,
+    pub blocks_visibility: Option<bool>,
+    pub door_open: Option<bool>,
+}
@@ -0,0 +1,423 @@
+use rltk::{ RGB };
+use specs::prelude::*;
+use specs::saveload::{ MarkedBuilder, SimpleMarker };
+use std::collections::HashMap;
+
+use super::{ Raws };
+use crate::components::*;
+use crate::random_table::RandomTable;
+
+pub enum SpawnType {
+    AtPosition { x: i32, y: i32 }
+}
+
+pub struct RawMaster {
+    raws: Raws,
+    item_index: HashMap<String, usize>,
+    mob_index: HashMap<String, usize>,
+    prop_index: HashMap<String, usize>,
+}
+
+impl RawMaster {
+    pub fn empty() -> RawMaster {
+        RawMaster {
+            raws: Raws { items: Vec::new(), mobs: Vec::new(), props: Vec::new(), spawn_table: Vec::new() },
+            item_index: HashMap::new(),
+            mob_index: HashMap::new(),
+            prop_index: HashMap::new(),
+        }
+    }
+
+    /// Replaces the loaded definitions and rebuilds the name lookups.
+    /// Panics, naming the entry, if anything in them can't be spawned.
+    pub fn load(&mut self, raws: Raws) {
+        if let Err(problem) = validate(&raws) {
+            panic!("Invalid raw file: {}", problem);
+        }
+        self.raws = raws;
+        self.item_index = HashMap::new();
+        self.mob_index = HashMap::new();
+        self.prop_index = HashMap::new();
+        for (i, item) in self.raws.items.iter().enumerate() {
+            self.item_index.insert(item.name.clone(), i);
+        }
+        for (i, mob) in self.raws.mobs.iter().enumerate() {
+            self.mob_index.insert(mob.name.clone(), i);
+        }
+        for (i, prop) in self.raws.props.iter().enumerate() {
+            self.prop_index.insert(prop.name.clone(), i);
+        }
+    }
+}
+
+/// Reads dice written as "1d6", "2d4+1" or "1d8-1" into the number of
+/// dice, the die type and the bonus
+pub fn parse_dice_string(dice: &str) -> (i32, i32, i32) {
+    read_dice(dice).unwrap_or_else(|problem| panic!("{}", problem))
+}
+
+fn read_dice(dice: &str) -> Result<(i32, i32, i32), String> {
+    let invalid = || format!("{} is not a dice string", dice);
+    let split = dice.find('d').ok_or_else(invalid)?;
+    let n_dice = &dice[..split];
+    let rest = &dice[split + 1..];
+    let (die_type, bonus) = match rest.find(['+', '-']) {
+        Some(sign) => (&rest[..sign], rest[sign..].trim_start_matches('+')),
+        None => (rest, "0")
+    };
+    let parse = |value: &str| value.parse::<i32>().map_err(|_| invalid());
+    Ok((parse(n_dice)?, parse(die_type)?, parse(bonus)?))
+}
+
+fn spawn_position(pos: SpawnType, new_entity: EntityBuilder) -> EntityBuilder {
+    match pos {
+        SpawnType::AtPosition { x, y } => new_entity.with(Position { x, y })
+    }
+}
+
+fn get_renderable_component(renderable: &super::item_structs::Renderable) -> Renderable {
+    // the raws were validated when they loaded, so these can't fail
+    Renderable {
+        glyph: rltk::to_cp437(renderable.glyph.chars().next().expect("Glyphs are validated on load")),
+        fg: RGB::from_hex(&renderable.fg).expect("Colours are validated on load"),
+        bg: RGB::from_hex(&renderable.bg).expect("Colours are validated on load"),
+        render_order: renderable.order,
+    }
+}
+
+fn check_renderable(renderable: &Option<super::item_structs::Renderable>) -> Result<(), String> {
+    let renderable = match renderable {
+        Some(renderable) => renderable,
+        None => return Ok(())
+    };
+    if renderable.glyph.chars().count() != 1 {
+        return Err(format!("glyph {:?} is not a single character", renderable.glyph));
+    }
+    for colour in [&renderable.fg, &renderable.bg].iter() {
+        if RGB::from_hex(colour).is_err() {
+            return Err(format!("{} is not a colour", colour));
+        }
+    }
+    Ok(())
+}
+
+fn parse_number(value: &str) -> Result<i32, String> {
+    value.parse::<i32>().map_err(|_| format!("{} is not a number", value))
+}
+
+/// Reads a status effect written as its duration in turns, with the damage
+/// or healing per turn after a slash if it has one: "5" or "5/2"
+fn parse_status(kind: StatusKind, value: &str) -> Result<InflictsStatus, String> {
+    let mut parts = value.split('/');
+    let turns = parse_number(parts.next().unwrap_or(""))?;
+    let magnitude = match parts.next() {
+        Some(magnitude) => parse_number(magnitude)?,
+        None => 0
+    };
+    Ok(InflictsStatus { kind, turns, magnitude })
+}
+
+fn status_kind(effect_name: &str) -> Option<StatusKind> {
+    match effect_name {
+        "confusion" => Some(StatusKind::Confusion),
+        "poison" => Some(StatusKind::Poison),
+        "regeneration" => Some(StatusKind::Regeneration),
+        "slow" => Some(StatusKind::Slow),
+        "haste" => Some(StatusKind::Haste),
+        "paralysis" => Some(StatusKind::Paralysis),
+        "blindness" => Some(StatusKind::Blindness),
+        _ => None
+    }
+}
+
+/// Consumable effects that take a number
+const NUMERIC_EFFECTS : &[&str] = &["provides_healing", "ranged", "damage", "area_of_effect"];
+
+/// Consumable effects that take no value
+const FLAG_EFFECTS : &[&str] = &["magic_mapping", "food", "teleport"];
+
+fn check_effect(effect_name: &str, effect_value: &str) -> Result<(), String> {
+    if NUMERIC_EFFECTS.contains(&effect_name) {
+        parse_number(effect_value).map(|_| ())
+    } else if let Some(kind) = status_kind(effect_name) {
+        parse_status(kind, effect_value).map(|_| ())
+    } else if FLAG_EFFECTS.contains(&effect_name) {
+        Ok(())
+    } else {
+        Err("is not a known effect".to_string())
+    }
+}
+
+fn skill_named(name: &str) -> Option<Skill> {
+    match name {
+        "Melee" => Some(Skill::Melee),
+        "Defense" => Some(Skill::Defense),
+        "Magic" => Some(Skill::Magic),
+        "Ranged" => Some(Skill::Ranged),
+        _ => None
+    }
+}
+
+fn weapon_attribute(name: &str) -> Option<WeaponAttribute> {
+    match name {
+        "Might" => Some(WeaponAttribute::Might),
+        "Quickness" => Some(WeaponAttribute::Quickness),
+        _ => None
+    }
+}
+
+/// Checks everything the spawners read from the raws, so that a typo fails
+/// when the file loads rather than part way into a game
+pub fn validate(raws: &Raws) -> Result<(), String> {
+    for item in raws.items.iter() {
+        check_renderable(&item.renderable).map_err(|problem| format!("{}: {}", item.name, problem))?;
+        if let Some(consumable) = &item.consumable {
+            for (effect_name, effect_value) in consumable.effects.iter() {
+                check_effect(effect_name, effect_value)
+                    .map_err(|problem| format!("{}: effect {} {}", item.name, effect_name, problem))?;
+            }
+        }
+        if let Some(weapon) = &item.weapon {
+            if weapon_attribute(&weapon.attribute).is_none() {
+                return Err(format!("{}: unknown weapon attribute {}", item.name, weapon.attribute));
+            }
+            read_dice(&weapon.base_damage).map_err(|problem| format!("{}: base damage {}", item.name, problem))?;
+            if weapon.range != "melee" {
+                parse_number(&weapon.range).map_err(|problem| format!("{}: range {}", item.name, problem))?;
+                if ammo_kind(weapon.ammo.as_deref().unwrap_or("")).is_none() {
+                    return Err(format!("{}: ranged weapons need an arrow, bolt or stone ammo", item.name));
+                }
+            }
+        }
+        if let Some(ammo) = &item.ammo {
+            if ammo_kind(&ammo.kind).is_none() {
+                return Err(format!("{}: unknown ammunition {}", item.name, ammo.kind));
+            }
+        }
+    }
+    for mob in raws.mobs.iter() {
+        check_renderable(&mob.renderable).map_err(|problem| format!("{}: {}", mob.name, problem))?;
+        for name in mob.skills.iter().flat_map(|skills| skills.keys()) {
+            if skill_named(name).is_none() {
+                return Err(format!("{}: unknown skill {}", mob.name, name));
+            }
+        }
+        let attacks = mob.natural.iter().flat_map(|natural| natural.attacks.iter().flatten());
+        for attack in attacks {
+            read_dice(&attack.damage).map_err(|problem| format!("{}: {} damage {}", mob.name, attack.name, problem))?;
+        }
+    }
+    for prop in raws.props.iter() {
+        check_renderable(&prop.renderable).map_err(|problem| format!("{}: {}", prop.name, problem))?;
+    }
+    for entry in raws.spawn_table.iter() {
+        let known = raws.items.iter().any(|item| item.name == entry.name) ||
+            raws.mobs.iter().any(|mob| mob.name == entry.name) ||
+            raws.props.iter().any(|prop| prop.name == entry.name);
+        if !known {
+            return Err(format!("spawn table: {} is not an item, mob or prop", entry.name));
+        }
+    }
+    Ok(())
+}
+
+/// Spawns the named item, mob or prop at the given position; returns None if
+/// the name is not present in the raw files.
+pub fn spawn_named_entity(raws: &RawMaster, ecs: &mut World, key: &str, pos: SpawnType) -> Option<Entity> {
+    if raws.item_index.contains_key(key) {
+        return spawn_named_item(raws, ecs, key, pos);
+    } else if raws.mob_index.contains_key(key) {
+        return spawn_named_mob(raws, ecs, key, pos);
+    } else if raws.prop_index.contains_key(key) {
+        return spawn_named_prop(raws, ecs, key, pos);
+    }
+
+    None
+}
+
+/// Reads the kind of ammunition a weapon shoots or an item holds
+fn ammo_kind(kind: &str) -> Option<AmmoKind> {
+    match kind {
+        "Arrow" => Some(AmmoKind::Arrow),
+        "Bolt" => Some(AmmoKind::Bolt),
+        "Stone" => Some(AmmoKind::Stone),
+        _ => None
+    }
+}
+
+pub fn spawn_named_item(raws: &RawMaster, ecs: &mut World, key: &str, pos: SpawnType) -> Option<Entity> {
+    if !raws.item_index.contains_key(key) { return None; }
+
+    let item_template = &raws.raws.items[raws.item_index[key]];
+    let mut eb = ecs.create_entity().marked::<SimpleMarker<SerializeMe>>();
+    eb = spawn_position(pos, eb);
+
+    if let Some(renderable) = &item_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+
+    eb = eb.with(Name { name: item_template.name.clone() });
+    eb = eb.with(Item {});
+
+    if let Some(consumable) = &item_template.consumable {
+        eb = eb.with(Consumable {});
+        for (effect_name, effect_value) in consumable.effects.iter() {
+            // the raws were validated when they loaded, so these can't fail
+            let number = || parse_number(effect_value).expect("Consumable effects are validated on load");
+            match effect_name.as_str() {
+                "provides_healing" => eb = eb.with(ProvidesHealing { heal_amount: number() }),
+                "ranged" => eb = eb.with(Ranged { range: number() }),
+                "damage" => eb = eb.with(InflictsDamage { damage: number() }),
+                "area_of_effect" => eb = eb.with(AreaOfEffect { radius: number() }),
+                "magic_mapping" => eb = eb.with(MagicMapper {}),
+                "food" => eb = eb.with(ProvidesFood {}),
+                "teleport" => eb = eb.with(TeleportsUser {}),
+                _ => {
+                    if let Some(kind) = status_kind(effect_name) {
+                        eb = eb.with(parse_status(kind, effect_value).expect("Consumable effects are validated on load"));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(weapon) = &item_template.weapon {
+        let (n_dice, die_type, bonus) = parse_dice_string(&weapon.base_damage);
+        let attribute = weapon_attribute(&weapon.attribute).expect("Weapons are validated on load");
+        if weapon.range == "melee" {
+            eb = eb.with(Equippable { slot: EquipmentSlot::Melee });
+            eb = eb.with(MeleeWeapon {
+                attribute,
+                damage_n_dice: n_dice,
+                damage_die_type: die_type,
+                damage_bonus: bonus,
+                hit_bonus: weapon.hit_bonus,
+            });
+        } else {
+            eb = eb.with(Equippable { slot: EquipmentSlot::Ranged });
+            eb = eb.with(RangedWeapon {
+                range: parse_number(&weapon.range).expect("Weapons are validated on load"),
+                ammo: ammo_kind(weapon.ammo.as_deref().unwrap_or("")).expect("Weapons are validated on load"),
+                attribute,
+                damage_n_dice: n_dice,
+                damage_die_type: die_type,
+                damage_bonus: bonus,
+                hit_bonus: weapon.hit_bonus,
+            });
+        }
+    }
+
+    if let Some(ammo) = &item_template.ammo {
+        eb = eb.with(Equippable { slot: EquipmentSlot::Ammo });
+        eb = eb.with(Ammunition { kind: ammo_kind(&ammo.kind).expect("Ammunition is validated on load"), count: ammo.count });
+    }
+
+    if let Some(shield) = &item_template.shield {
+        eb = eb.with(Equippable { slot: EquipmentSlot::Shield });
+        eb = eb.with(Wearable { armor_class: shield.armor_class });
+    }
+
+    Some(eb.build())
+}
+
+pub fn spawn_named_mob(raws: &RawMaster, ecs: &mut World, key: &str, pos: SpawnType) -> Option<Entity> {
+    if !raws.mob_index.contains_key(key) { return None; }
+
+    let mob_template = &raws.raws.mobs[raws.mob_index[key]];
+    let mut eb = ecs.create_entity().marked::<SimpleMarker<SerializeMe>>();
+    eb = spawn_position(pos, eb);
+
+    if let Some(renderable) = &mob_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+
+    eb = eb.with(Name { name: mob_template.name.clone() });
+    eb = eb.with(Monster {});
+    if mob_template.blocks_tile {
+        eb = eb.with(BlocksTile {});
+    }
+    eb = eb.with(CombatStats { max_hp: mob_template.hp, hp: mob_template.hp });
+    eb = eb.with(Experience { level: mob_template.level.unwrap_or(1), xp: 0 });
+
+    let raw_attributes = &mob_template.attributes;
+    eb = eb.with(Attributes {
+        might: Attribute::new(raw_attributes.might.unwrap_or(11)),
+        fitness: Attribute::new(raw_attributes.fitness.unwrap_or(11)),
+        quickness: Attribute::new(raw_attributes.quickness.unwrap_or(11)),
+        intelligence: Attribute::new(raw_attributes.intelligence.unwrap_or(11)),
+    });
+
+    let mut skills = Skills { skills: HashMap::new() };
+    if let Some(raw_skills) = &mob_template.skills {
+        for (name, value) in raw_skills.iter() {
+            if let Some(skill) = skill_named(name) {
+                skills.skills.insert(skill, *value);
+            }
+        }
+    }
+    eb = eb.with(skills);
+
+    if let Some(natural) = &mob_template.natural {
+        let mut nature = NaturalAttackDefense { armor_class: natural.armor_class, attacks: Vec::new() };
+        if let Some(attacks) = &natural.attacks {
+            for attack in attacks.iter() {
+                let (n_dice, die_type, bonus) = parse_dice_string(&attack.damage);
+                nature.attacks.push(NaturalAttack {
+                    name: attack.name.clone(),
+                    damage_n_dice: n_dice,
+                    damage_die_type: die_type,
+                    damage_bonus: bonus,
+                    hit_bonus: attack.hit_bonus,
+                });
+            }
+        }
+        eb = eb.with(nature);
+    }
+    eb = eb.with(Viewshed { tiles: Vec::new(), range: mob_template.vision_range, dirty: true });
+
+    Some(eb.build())
+}
+
+pub fn spawn_named_prop(raws: &RawMaster, ecs: &mut World, key: &str, pos: SpawnType) -> Option<Entity> {
+    if !raws.prop_index.contains_key(key) { return None; }
+
+    let prop_template = &raws.raws.props[raws.prop_index[key]];
+    let mut eb = ecs.create_entity().marked::<SimpleMarker<SerializeMe>>();
+    eb = spawn_position(pos, eb);
+
+    if let Some(renderable) = &prop_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+
+    eb = eb.with(Name { name: prop_template.name.clone() });
+    if prop_template.blocks_tile == Some(true) {
+        eb = eb.with(BlocksTile {});
+    }
+    if prop_template.blocks_visibility == Some(true) {
+        eb = eb.with(BlocksVisibility {});
+    }
+    if let Some(open) = prop_template.door_open {
+        eb = eb.with(Door { open });
+    }
+
+    Some(eb.build())
+}
+
+/// Builds the weighted spawn table for the given depth from the raw files
+pub fn get_spawn_table_for_depth(raws: &RawMaster, depth: i32) -> RandomTable {
+    let available_options : Vec<&super::SpawnTableEntry> = raws.raws.spawn_table
+        .iter()
+        .filter(|a| depth >= a.min_depth && depth <= a.max_depth)
+        .collect();
+
+    let mut rt = RandomTable::new();
+    for e in available_options.iter() {
+        let mut weight = e.weight;
+        if e.add_map_depth_to_weight == Some(true) {
+            weight += depth;
+        }
+        rt = rt.add(e.name.clone(), weight);
+    }
+
+    rt
+}
@@ -5,64 +5,461 @@ use specs::saveload::{
     SimpleMarkerAllocator,
     SerializeComponents,
     DeserializeComponents,
-    MarkedBuilder, };
+    MarkedBuilder,
+    ConvertSaveload, };
 use specs::error::NoError;
 
 use super::components::*;
+use super::game_seed::GameSeed;
+use super::RandomNumberGenerator;
+use super::{ TurnCounter, MasterDungeonMap, gamelog::GameLog };
 
 use std::fs::File;
 use std::path::Path;
 use std::fs;
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::io::{ Cursor, Read, Write };
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use serde::{ Serialize, Serializer, Deserialize, Deserializer };
+use serde::ser::SerializeMap;
+use serde::de::{ self as serde_de, Visitor, SeqAccess, MapAccess };
+use serde_json::{ json, Map as JsonMap, Value };
 
 macro_rules! serialize_individually {
-    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+    ($ecs:expr, $storages:expr, $( $type:ty),*) => {
         $(
-        SerializeComponents::<NoError, SimpleMarker<SerializeMe>>::serialize(
-            &( $ecs.read_storage::<$type>(), ),
-            &$data.0,
-            &$data.1,
-            &mut $ser,
-        )
-        .unwrap();
+        $storages.serialize_entry(stringify!($type), &StorageEntries::<$type> { ecs: $ecs, storage: PhantomData })?;
         )*
     };
 }
 
-pub fn delete_save() {
-    if Path::new("./savegame.json").exists() {
-        std::fs::remove_file("./savegame.json").expect("Unable to delete file");
+pub const SAVE_SLOTS : usize = 3;
+
+/// The slot written by `autosave`; it follows the player's own slots
+pub const AUTOSAVE_SLOT : usize = SAVE_SLOTS;
+
+/// Number of turns between autosaves
+pub const AUTOSAVE_INTERVAL : i32 = 100;
+
+/// Bumped whenever the layout of a save changes; see `MIGRATIONS`
+pub const SAVE_VERSION : u32 = 13;
+
+/// Binary saves start with this; anything else is read as JSON
+const BINARY_MAGIC : &[u8] = b"RRSAVE";
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum SaveFormat { Json, Binary }
+
+impl SaveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            SaveFormat::Json => "json",
+            SaveFormat::Binary => "sav",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SaveFormat::Json => "JSON",
+            SaveFormat::Binary => "Binary",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(String),
+    NewerVersion(u32),
+    Missing(&'static str),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "unable to read save: {}", e),
+            LoadError::Parse(e) => write!(f, "save is damaged: {}", e),
+            LoadError::NewerVersion(v) => write!(f, "save is from a newer version ({})", v),
+            LoadError::Missing(what) => write!(f, "save has no {}", what),
+        }
+    }
+}
+
+fn parse_error<E: fmt::Display>(e: E) -> LoadError {
+    LoadError::Parse(e.to_string())
+}
+
+/// The message from the last failed load, shown on the main menu
+pub struct LoadStatus {
+    pub error: Option<String>,
+}
+
+/// Whether loading a save deletes it (roguelike permadeath) or leaves it
+/// in place. New games take the mode chosen on the main menu; loaded
+/// games keep the mode they were saved with. `format` is the encoding
+/// used for the next save; loading detects it from the file. `autosave`
+/// is off when there is no one to recover a game for (headless runs).
+pub struct SaveMode {
+    pub permadeath: bool,
+    pub format: SaveFormat,
+    pub autosave: bool,
+}
+
+/// Summary written at the start of every save file so the load menu can
+/// describe a slot without reading the whole world.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveMetadata {
+    #[serde(default)]
+    pub version: u32,
+    pub name: String,
+    pub depth: i32,
+    pub turns: i32,
+    pub timestamp: u64,
+    pub permadeath: bool,
+    /// The game's seed, which tells saves of the same game apart from others
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl SaveMetadata {
+    pub fn describe(&self) -> String {
+        format!("{}, depth {}, turn {}, {}", self.name, self.depth, self.turns, format_timestamp(self.timestamp))
     }
 }
 
+/// Formats seconds since the epoch as a UTC "YYYY-MM-DD HH:MM" string
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let minutes = (timestamp % 86400) / 60;
+
+    // civil date from a day count, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
+
+fn slot_path(slot: usize, format: SaveFormat) -> String {
+    if slot == AUTOSAVE_SLOT {
+        format!("./autosave.{}", format.extension())
+    } else {
+        format!("./savegame{}.{}", slot + 1, format.extension())
+    }
+}
+
+/// Returns the file holding the slot, whichever format it was saved in
+fn find_save(slot: usize) -> Option<String> {
+    [SaveFormat::Json, SaveFormat::Binary].iter()
+        .map(|format| slot_path(slot, *format))
+        .find(|path| Path::new(path).exists())
+}
+
+pub fn delete_save(slot: usize) {
+    while let Some(path) = find_save(slot) {
+        std::fs::remove_file(&path).expect("Unable to delete file");
+    }
+}
+
+/// Deletes the permadeath saves of the game with this seed from every
+/// slot but `keep`, so that a permadeath game never has more than one
+/// save of the player's own (plus its autosave) to go back to
+pub fn delete_permadeath_copies(seed: u64, keep: Option<usize>) {
+    for slot in (0 ..= AUTOSAVE_SLOT).filter(|slot| Some(*slot) != keep) {
+        if let Some(Ok(metadata)) = read_metadata(slot) {
+            if metadata.permadeath && metadata.seed == seed {
+                delete_save(slot);
+            }
+        }
+    }
+}
+
+/// What the load menu shows for each slot: None for an empty slot
+pub type SaveListing = Vec<Option<Result<SaveMetadata, LoadError>>>;
+
+/// How much of the start of a save is read to find its header, which is
+/// written first and is only ever a few hundred bytes
+pub const HEADER_BYTES : usize = 4096;
+
+/// Reads the metadata from the start of a save; `prefix` needn't hold
+/// any more of the save than that
+pub fn metadata_from_header(prefix: &[u8]) -> Result<SaveMetadata, LoadError> {
+    let header = decode_values(prefix, Some(1))?.into_iter().next().ok_or(LoadError::Missing("header"))?;
+    let metadata = serde_json::from_value::<SaveMetadata>(header).map_err(parse_error)?;
+    if metadata.version > SAVE_VERSION { Err(LoadError::NewerVersion(metadata.version)) } else { Ok(metadata) }
+}
+
 #[cfg(target_arch = "wasm32")]
-pub fn does_save_exist() -> bool {
-    false
+pub fn read_metadata(_slot: usize) -> Option<Result<SaveMetadata, LoadError>> {
+    None
 }
 
+/// Reads just the header of a slot. Returns None if the slot is empty.
 #[cfg(not(target_arch = "wasm32"))]
-pub fn does_save_exist() -> bool {
-    Path::new("./savegame.json").exists()
+pub fn read_metadata(slot: usize) -> Option<Result<SaveMetadata, LoadError>> {
+    let path = find_save(slot)?;
+    let mut prefix = Vec::new();
+    let read = File::open(&path).and_then(|file| file.take(HEADER_BYTES as u64).read_to_end(&mut prefix));
+    Some(read.map_err(LoadError::Io).and_then(|_| metadata_from_header(&prefix)))
+}
+
+/// Returns the metadata for every slot including the autosave, with None
+/// for empty slots
+pub fn list_saves() -> SaveListing {
+    (0 ..= AUTOSAVE_SLOT).map(read_metadata).collect()
 }
 
 #[cfg(target_arch = "wasm32")]
-pub fn save_game(_ecs: &mut World) { }
+pub fn save_game(_ecs: &mut World, _slot: usize) -> std::io::Result<()> { Ok(()) }
 
+/// Writes the save to a temporary file and renames it over the slot, so
+/// the previous save survives a crash or a failed write part way through.
+/// A permadeath game keeps only the newest of the player's own saves; an
+/// autosave leaves them alone.
 #[cfg(not(target_arch = "wasm32"))]
-pub fn save_game(ecs: &mut World) {
+pub fn save_game(ecs: &mut World, slot: usize) -> std::io::Result<()> {
+    let format = ecs.fetch::<SaveMode>().format;
+    let path = slot_path(slot, format);
+    let temp_path = format!("{}.tmp", path);
+
+    let mut writer = std::io::BufWriter::new(File::create(&temp_path)?);
+    write_save(ecs, &mut writer, format)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, &path)?;
+
+    // a slot only holds one format
+    for other in [SaveFormat::Json, SaveFormat::Binary].iter().filter(|f| **f != format) {
+        let other_path = slot_path(slot, *other);
+        if Path::new(&other_path).exists() {
+            fs::remove_file(&other_path)?;
+        }
+    }
+
+    if ecs.fetch::<SaveMode>().permadeath && slot != AUTOSAVE_SLOT {
+        delete_permadeath_copies(ecs.fetch::<GameSeed>().seed, Some(slot));
+    }
+    Ok(())
+}
+
+/// Saves to the autosave slot, unless the player is dead or autosaving
+/// is turned off. A failed autosave is logged rather than ending the game.
+pub fn autosave(ecs: &mut World) {
+    let player_dead = {
+        let player = ecs.fetch::<Entity>();
+        let stats = ecs.read_storage::<CombatStats>();
+        matches!(stats.get(*player), Some(stats) if stats.hp < 1)
+    };
+    if !ecs.fetch::<SaveMode>().autosave || player_dead { return; }
+    if let Err(e) = save_game(ecs, AUTOSAVE_SLOT) {
+        ecs.fetch_mut::<GameLog>().entries.insert(0, format!("Unable to autosave: {}", e));
+    }
+}
+
+/// Compresses the map for the save helper; the map is by far the largest
+/// thing in a save.
+fn compress_map(map: &super::map::Map) -> Vec<u8> {
+    let packed = rmp_serde::to_vec_named(map).expect("Unable to encode map");
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&packed).expect("Unable to compress map");
+    encoder.finish().expect("Unable to compress map")
+}
+
+fn decompress_map(data: &[u8]) -> Result<super::map::Map, LoadError> {
+    let mut packed = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut packed).map_err(parse_error)?;
+    rmp_serde::from_slice(&packed).map_err(parse_error)
+}
+
+/// Serde adapter for a compressed map in the save helper: a byte string in
+/// binary saves and base64 in JSON ones, where it would otherwise be an
+/// array of numbers. Saves from before version 13 have the array, which
+/// still loads.
+pub mod packed_bytes {
+    use super::{ PackedBytes, UnpackedBytes };
+    use serde::{ Serialize, Serializer, Deserialize, Deserializer };
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        PackedBytes(bytes).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        UnpackedBytes::deserialize(deserializer).map(|bytes| bytes.0)
+    }
+}
+
+/// `packed_bytes` for a list of compressed maps
+pub mod packed_bytes_list {
+    use super::{ PackedBytes, UnpackedBytes };
+    use serde::{ Serializer, Deserialize, Deserializer };
+
+    pub fn serialize<S: Serializer>(list: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().map(|bytes| PackedBytes(bytes)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        let list = Vec::<UnpackedBytes>::deserialize(deserializer)?;
+        Ok(list.into_iter().map(|bytes| bytes.0).collect())
+    }
+}
+
+struct PackedBytes<'a>(&'a [u8]);
+
+impl Serialize for PackedBytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64_encode(self.0))
+        } else {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+}
+
+struct UnpackedBytes(Vec<u8>);
+
+impl<'de> Deserialize<'de> for UnpackedBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UnpackedBytesVisitor)
+    }
+}
+
+struct UnpackedBytesVisitor;
+
+impl<'de> Visitor<'de> for UnpackedBytesVisitor {
+    type Value = UnpackedBytes;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bytes, as base64 or an array of numbers")
+    }
+
+    fn visit_str<E: serde_de::Error>(self, v: &str) -> Result<UnpackedBytes, E> {
+        base64_decode(v).map(UnpackedBytes).ok_or_else(|| E::custom("invalid base64"))
+    }
+
+    fn visit_bytes<E: serde_de::Error>(self, v: &[u8]) -> Result<UnpackedBytes, E> {
+        Ok(UnpackedBytes(v.to_vec()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<UnpackedBytes, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(byte) = seq.next_element::<u8>()? { bytes.push(byte); }
+        Ok(UnpackedBytes(bytes))
+    }
+}
+
+const BASE64_ALPHABET : &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0 .. 4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+pub fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 { return None; }
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let digit = BASE64_ALPHABET.iter().position(|a| a == c)? as u32;
+            n |= digit << (18 - 6 * i);
+        }
+        for i in 0 .. chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Turns a failure to encode the save into the same error as failing to
+/// write it
+fn save_error<E: fmt::Display>(e: E) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+pub fn write_save<W: Write>(ecs: &mut World, writer: W, format: SaveFormat) -> std::io::Result<()> {
     let mapcopy = ecs.get_mut::<super::map::Map>().unwrap().clone();
+    let levels : Vec<Vec<u8>> = ecs.fetch::<MasterDungeonMap>().levels().map(compress_map).collect();
+    let seed = ecs.fetch::<GameSeed>().seed;
+    let rng = (*ecs.fetch::<RandomNumberGenerator>()).clone();
+    let turns = ecs.fetch::<TurnCounter>().turns;
+    let metadata = SaveMetadata {
+        version: SAVE_VERSION,
+        name: player_name(ecs),
+        depth: mapcopy.depth,
+        turns,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        permadeath: ecs.fetch::<SaveMode>().permadeath,
+        seed,
+    };
     let savehelper = ecs.
         create_entity().
-        with(SerializationHelper { map: mapcopy }).
+        with(SerializationHelper { map: compress_map(&mapcopy), levels, seed, rng, turns }).
         marked::<SimpleMarker<SerializeMe>>().
         build();
 
-    {
-        let data = ( ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>() );
-        let writer = File::create("./savegame.json").unwrap();
-        let mut serializer = serde_json::Serializer::new(writer);
-        serialize_individually!(ecs,
-            serializer,
-            data, 
+    let written = match format {
+        SaveFormat::Json => {
+            let mut serializer = serde_json::Serializer::new(writer);
+            write_components(ecs, &metadata, &mut serializer)
+        }
+        SaveFormat::Binary => {
+            let mut writer = writer;
+            writer.write_all(BINARY_MAGIC).and_then(|_| {
+                let mut serializer = rmp_serde::Serializer::new(writer).with_struct_map();
+                write_components(ecs, &metadata, &mut serializer)
+            })
+        }
+    };
+
+    ecs.delete_entity(savehelper).expect("Crash on cleanup");
+    written
+}
+
+/// Writes the header followed by every component storage, keyed by the
+/// component's name
+fn write_components<S>(ecs: &World, metadata: &SaveMetadata, serializer: &mut S) -> std::io::Result<()>
+    where for<'a> &'a mut S: serde::Serializer
+{
+    metadata.serialize(&mut *serializer).map_err(save_error)?;
+    SaveStorages { ecs }.serialize(&mut *serializer).map_err(save_error)?;
+    Ok(())
+}
+
+/// Every component storage in the world, written straight to the save
+/// as a map from the component's name to its entries
+struct SaveStorages<'a> {
+    ecs: &'a World,
+}
+
+impl Serialize for SaveStorages<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut storages = serializer.serialize_map(None)?;
+        serialize_individually!(self.ecs,
+            storages,
             Position,
             Renderable,
             Player,
@@ -71,13 +468,13 @@ pub fn save_game(ecs: &mut World) {
             Name,
             BlocksTile,
             CombatStats,
-            SufferDamage,
+            TeleportsUser,
             Item,
             Consumable,
             Ranged,
             InflictsDamage,
             AreaOfEffect,
-            Confusion,
+            InflictsStatus,
             ProvidesHealing,
             InBackpack,
             WantsToMelee,
@@ -87,47 +484,495 @@ pub fn save_game(ecs: &mut World) {
             SerializationHelper,
             Equippable,
             Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
+            MeleeWeapon,
+            Wearable,
             ParticleLifetime,
             HungerClock,
             ProvidesFood,
-            MagicMapper
+            MagicMapper,
+            OtherLevelPosition,
+            BlocksVisibility,
+            Door,
+            Attributes,
+            Skills,
+            NaturalAttackDefense,
+            Experience,
+            StatusEffect,
+            RangedWeapon,
+            Ammunition,
+            WantsToShoot
         );
-
+        storages.end()
     }
+}
 
-    ecs.delete_entity(savehelper).expect("Crash on cleanup");
+/// The entries of one component storage, for every entity marked to be saved
+struct StorageEntries<'a, T> {
+    ecs: &'a World,
+    storage: PhantomData<T>,
+}
+
+impl<T> Serialize for StorageEntries<'_, T>
+    where T: Component + ConvertSaveload<SimpleMarker<SerializeMe>>, NoError: From<T::Error>
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializeComponents::<NoError, SimpleMarker<SerializeMe>>::serialize(
+            &( self.ecs.read_storage::<T>(), ),
+            &self.ecs.entities(),
+            &self.ecs.read_storage::<SimpleMarker<SerializeMe>>(),
+            serializer,
+        )
+    }
 }
 
 macro_rules! deserialize_individually {
-    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+    ($ecs:expr, $storages:expr, $data:expr, $( $type:ty),*) => {
         $(
+        // a storage added since the save was written is just empty
+        let entries = $storages.remove(stringify!($type)).unwrap_or_else(|| Value::Array(Vec::new()));
         DeserializeComponents::<NoError, _>::deserialize(
             &mut ( &mut $ecs.write_storage::<$type>(), ),
             &mut $data.0, // entities
             &mut $data.1, // marker
             &mut $data.2, // allocator
-            &mut $de,
+            entries,
         )
-        .unwrap();
+        .map_err(parse_error)?;
         )*
     };
 }
 
-pub fn load_game(ecs: &mut World) {
-    {
-        let mut to_delete = Vec::new();
-        for e in ecs.entities().join() {
-            to_delete.push(e);
+fn player_name(ecs: &World) -> String {
+    let player = ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    names.get(*player).map(|n| n.name.clone()).unwrap_or_else(|| "Unknown".to_string())
+}
+
+fn delete_all_entities(ecs: &mut World) {
+    let mut to_delete = Vec::new();
+    for e in ecs.entities().join() {
+        to_delete.push(e);
+    }
+    for del in to_delete.iter() {
+        ecs.delete_entity(*del).expect("Deletion failed");
+    }
+}
+
+/// Upgrades a save from `version` to `version + 1`. The entry at index N
+/// upgrades version N saves.
+type Migration = fn(&mut Vec<Value>);
+
+const MIGRATIONS : [Migration; SAVE_VERSION as usize] = [
+    migrate_v0,
+    migrate_v1,
+    migrate_v2,
+    migrate_v3,
+    migrate_v4,
+    migrate_v5,
+    migrate_v6,
+    migrate_v7,
+    migrate_v8,
+    migrate_v9,
+    migrate_v10,
+    migrate_v11,
+    migrate_v12,
+];
+
+/// Version 0 saves predate the version field; the layout is otherwise
+/// unchanged, so only the header needs stamping (done by `migrate`).
+fn migrate_v0(_values: &mut Vec<Value>) {}
+
+/// Version 1 stored the helper's map uncompressed
+#[allow(clippy::ptr_arg)] // must match `Migration`
+fn migrate_v1(values: &mut Vec<Value>) {
+    for storage in values.iter_mut().skip(1) {
+        let entries = match storage.as_array_mut() { Some(entries) => entries, None => continue };
+        for entry in entries.iter_mut() {
+            let map = entry.pointer_mut("/components/0/map");
+            if let Some(map) = map {
+                if let Ok(old_map) = serde_json::from_value::<super::map::Map>(map.clone()) {
+                    *map = Value::from(compress_map(&old_map));
+                }
+            }
         }
-        for del in to_delete.iter() {
-            ecs.delete_entity(*del).expect("Deletion failed");
+    }
+}
+
+/// Version 2 predates `OtherLevelPosition`, the last storage in the save
+fn migrate_v2(values: &mut Vec<Value>) {
+    values.push(Value::Array(Vec::new()));
+}
+
+/// Version 3 predates doors, so it has no `BlocksVisibility` or `Door`
+/// storages at the end
+fn migrate_v3(values: &mut Vec<Value>) {
+    values.push(Value::Array(Vec::new()));
+    values.push(Value::Array(Vec::new()));
+}
+
+/// Up to version 9 a save held one array per storage, in a fixed order,
+/// so the migrations for those versions find storages by position. These
+/// are positions in those layouts (counting the header as 0), which never
+/// change; note that a few slots were reused when a component was
+/// replaced, as the migrations below describe.
+const V4_PLAYER : usize = 3;
+const V4_COMBAT_STATS : usize = 8;
+const V4_SUFFER_DAMAGE : usize = 9;
+const V4_ITEM : usize = 10;
+const V4_CONFUSION : usize = 15;
+const V4_MELEE_POWER_BONUS : usize = 25;
+const V4_DEFENSE_BONUS : usize = 26;
+
+/// Where skills have been kept since the version 4 migration added them
+const V5_SKILLS : usize = 35;
+
+/// How many storages a save of each positional version holds
+const POSITIONAL_STORAGE_COUNTS : [usize; 10] = [30, 30, 30, 31, 33, 36, 37, 38, 38, 41];
+
+/// The storages of a version 9 save, in order. Later saves key each
+/// storage by these names instead.
+pub const V9_STORAGES : [&str; 41] = [
+    "Position", "Renderable", "Player", "Viewshed", "Monster", "Name", "BlocksTile", "CombatStats",
+    "TeleportsUser", "Item", "Consumable", "Ranged", "InflictsDamage", "AreaOfEffect", "InflictsStatus",
+    "ProvidesHealing", "InBackpack", "WantsToMelee", "WantsToPickupItem", "WantsToUseItem", "WantsToDropItem",
+    "SerializationHelper", "Equippable", "Equipped", "MeleeWeapon", "Wearable", "ParticleLifetime",
+    "HungerClock", "ProvidesFood", "MagicMapper", "OtherLevelPosition", "BlocksVisibility", "Door",
+    "Attributes", "Skills", "NaturalAttackDefense", "Experience", "StatusEffect", "RangedWeapon",
+    "Ammunition", "WantsToShoot",
+];
+
+/// Version 4 had flat power and defense instead of attributes, skills,
+/// weapon dice and armour class. Bonuses become the nearest weapon or
+/// armour, monsters get a natural attack and armour from their old
+/// stats, and everyone gets average attributes and a little training.
+fn migrate_v4(values: &mut Vec<Value>) {
+    fn entries(values: &mut [Value], storage: usize) -> Vec<Value> {
+        values.get_mut(storage).and_then(|s| s.as_array_mut()).map(std::mem::take).unwrap_or_default()
+    }
+    fn stat(component: &Value, name: &str) -> Option<i32> {
+        component.get(name).and_then(|v| v.as_i64()).map(|v| v as i32)
+    }
+
+    let mut weapons = entries(values, V4_MELEE_POWER_BONUS);
+    for entry in weapons.iter_mut() {
+        let component = &mut entry["components"][0];
+        if let Some(power) = stat(component, "power") {
+            *component = json!({ "attribute": "Might", "damage_n_dice": 1, "damage_die_type": 4, "damage_bonus": power - 1, "hit_bonus": 0 });
+        }
+    }
+    values[V4_MELEE_POWER_BONUS] = Value::Array(weapons);
+
+    let mut armour = entries(values, V4_DEFENSE_BONUS);
+    for entry in armour.iter_mut() {
+        let component = &mut entry["components"][0];
+        if let Some(defense) = stat(component, "defense") {
+            *component = json!({ "armor_class": defense });
+        }
+    }
+    values[V4_DEFENSE_BONUS] = Value::Array(armour);
+
+    let players : Vec<Value> = entries(values, V4_PLAYER);
+    let player_markers : Vec<&Value> = players.iter()
+        .filter(|p| !p["components"][0].is_null())
+        .map(|p| &p["marker"])
+        .collect();
+    let mut stats = entries(values, V4_COMBAT_STATS);
+    let mut attributes = Vec::new();
+    let mut skills = Vec::new();
+    let mut natural = Vec::new();
+    for entry in stats.iter_mut() {
+        let marker = entry["marker"].clone();
+        let component = &mut entry["components"][0];
+        let power = stat(component, "power");
+        let defense = stat(component, "defense");
+        if let Some(fields) = component.as_object_mut() {
+            fields.remove("power");
+            fields.remove("defense");
+        }
+        if let (Some(power), Some(defense)) = (power, defense) {
+            let attribute = json!({ "base": 11, "modifiers": 0 });
+            attributes.push(json!({ "marker": marker, "components": [
+                { "might": attribute, "fitness": attribute, "quickness": attribute, "intelligence": attribute }
+            ] }));
+            skills.push(json!({ "marker": marker, "components": [
+                { "skills": { "Melee": 1, "Defense": 1, "Magic": 1 } }
+            ] }));
+            if !player_markers.contains(&&marker) {
+                natural.push(json!({ "marker": marker, "components": [ {
+                    "armor_class": 10 + defense,
+                    "attacks": [ { "name": "hits", "damage_n_dice": 1, "damage_die_type": i32::max(1, 2 * power - 1), "damage_bonus": 0, "hit_bonus": 0 } ]
+                } ] }));
+            }
         }
     }
+    values[V4_COMBAT_STATS] = Value::Array(stats);
+    values[V4_PLAYER] = Value::Array(players);
+
+    values.push(Value::Array(attributes));
+    values.push(Value::Array(skills));
+    values.push(Value::Array(natural));
+}
 
-    let data = fs::read_to_string("./savegame.json").unwrap();
-    let mut de = serde_json::Deserializer::from_str(&data);
+/// Version 5 predates experience. Everyone who can fight starts at level
+/// 1, and damage in flight is credited to no one.
+fn migrate_v5(values: &mut Vec<Value>) {
+    let mut experience = Vec::new();
+    for entry in values[V4_COMBAT_STATS].as_array().into_iter().flatten() {
+        if !entry["components"][0].is_null() {
+            experience.push(json!({ "marker": entry["marker"], "components": [ { "level": 1, "xp": 0 } ] }));
+        }
+    }
+    for entry in values[V4_SUFFER_DAMAGE].as_array_mut().into_iter().flatten() {
+        if let Some(damage) = entry["components"][0].as_object_mut() {
+            damage.entry("from_player").or_insert(Value::Bool(false));
+        }
+    }
+    values.push(Value::Array(experience));
+}
+
+/// Version 6 only had confusion, kept as a component on both the scrolls
+/// that cause it and the monsters suffering from it. Scrolls now inflict
+/// a status, and each confused monster gets a status entity of its own.
+fn migrate_v6(values: &mut Vec<Value>) {
+    let items : Vec<Value> = values[V4_ITEM].as_array().into_iter().flatten()
+        .filter(|entry| !entry["components"][0].is_null())
+        .map(|entry| entry["marker"].clone())
+        .collect();
+    let mut next_marker = values.iter().skip(1)
+        .filter_map(|storage| storage.as_array())
+        .flatten()
+        .filter_map(|entry| entry.pointer("/marker/0").and_then(|m| m.as_u64()))
+        .max()
+        .unwrap_or(0) + 1;
+
+    let mut statuses = Vec::new();
+    for entry in values[V4_CONFUSION].as_array_mut().into_iter().flatten() {
+        let turns = match entry.pointer("/components/0/turns").and_then(|t| t.as_i64()) {
+            Some(turns) => turns,
+            None => continue
+        };
+        if items.contains(&entry["marker"]) {
+            entry["components"][0] = json!({ "kind": "Confusion", "turns": turns, "magnitude": 0 });
+        } else {
+            statuses.push(json!({ "marker": [next_marker], "components": [
+                { "target": entry["marker"], "kind": "Confusion", "turns": turns, "magnitude": 0 }
+            ] }));
+            next_marker += 1;
+            entry["components"][0] = Value::Null;
+        }
+    }
+    values.push(Value::Array(statuses));
+}
+
+/// Version 7 kept damage waiting to be applied in a component, where
+/// scrolls of teleportation now go. Any damage still pending lands now.
+#[allow(clippy::ptr_arg)] // must match `Migration`
+fn migrate_v7(values: &mut Vec<Value>) {
+    let mut pending = Vec::new();
+    for entry in values[V4_SUFFER_DAMAGE].as_array_mut().into_iter().flatten() {
+        if let Some(amount) = entry.pointer("/components/0/amount").and_then(|a| a.as_i64()) {
+            pending.push((entry["marker"].clone(), amount));
+        }
+        entry["components"][0] = Value::Null;
+    }
+    for entry in values[V4_COMBAT_STATS].as_array_mut().into_iter().flatten() {
+        let damage : i64 = pending.iter().filter(|(marker, _)| *marker == entry["marker"]).map(|(_, amount)| amount).sum();
+        if let Some(hp) = entry.pointer_mut("/components/0/hp") {
+            *hp = Value::from(hp.as_i64().unwrap_or(0) - damage);
+        }
+    }
+}
+
+/// Version 8 predates ranged weapons. The player gets the same training
+/// with them as with everything else, and there are three new storages.
+fn migrate_v8(values: &mut Vec<Value>) {
+    let players : Vec<Value> = values[V4_PLAYER].as_array().into_iter().flatten()
+        .filter(|entry| !entry["components"][0].is_null())
+        .map(|entry| entry["marker"].clone())
+        .collect();
+    for entry in values[V5_SKILLS].as_array_mut().into_iter().flatten() {
+        if !players.contains(&entry["marker"]) { continue; }
+        if let Some(skills) = entry.pointer_mut("/components/0/skills").and_then(|s| s.as_object_mut()) {
+            skills.entry("Ranged").or_insert(Value::from(1));
+        }
+    }
+    for _ in 0 .. 3 {
+        values.push(Value::Array(Vec::new()));
+    }
+}
+
+/// Version 9 kept its storages in a fixed order; they are now keyed by
+/// name, so the order they're written in no longer matters
+fn migrate_v9(values: &mut Vec<Value>) {
+    let positional = values.split_off(1);
+    let storages : JsonMap<String, Value> = V9_STORAGES.iter()
+        .map(|name| name.to_string())
+        .zip(positional)
+        .collect();
+    values.push(Value::Object(storages));
+}
+
+/// Version 10 didn't keep the state of the random number generator; a
+/// game loaded from it carries on from the seed plus the depth
+#[allow(clippy::ptr_arg)] // must match `Migration`
+fn migrate_v10(values: &mut Vec<Value>) {
+    let depth = values[0]["depth"].as_u64().unwrap_or(0);
+    for entry in values[1]["SerializationHelper"].as_array_mut().into_iter().flatten() {
+        if let Some(helper) = entry.pointer_mut("/components/0").and_then(|h| h.as_object_mut()) {
+            let seed = helper.get("seed").and_then(|s| s.as_u64()).unwrap_or(0);
+            helper.insert("rng_seed".to_string(), Value::from(seed.wrapping_add(depth)));
+        }
+    }
+}
+
+/// Version 11 kept only a seed for the random number generator, which
+/// was reseeded with it whenever the game was saved
+#[allow(clippy::ptr_arg)] // must match `Migration`
+fn migrate_v11(values: &mut Vec<Value>) {
+    for entry in values[1]["SerializationHelper"].as_array_mut().into_iter().flatten() {
+        if let Some(helper) = entry.pointer_mut("/components/0").and_then(|h| h.as_object_mut()) {
+            let rng_seed = helper.remove("rng_seed").and_then(|s| s.as_u64()).unwrap_or(0);
+            let rng = serde_json::to_value(RandomNumberGenerator::seeded(rng_seed)).expect("Unable to encode generator");
+            helper.insert("rng".to_string(), rng);
+        }
+    }
+}
+
+/// Version 12 wrote the compressed maps as arrays of numbers, which
+/// `packed_bytes` still reads
+fn migrate_v12(_values: &mut Vec<Value>) {}
+
+/// A value read from a binary save. JSON has no byte strings, so those
+/// (the compressed maps) become base64, as they are in JSON saves.
+struct BinaryValue(Value);
+
+impl<'de> Deserialize<'de> for BinaryValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BinaryValueVisitor)
+    }
+}
+
+struct BinaryValueVisitor;
+
+impl<'de> Visitor<'de> for BinaryValueVisitor {
+    type Value = BinaryValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a MessagePack value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::from(v))) }
+    fn visit_i64<E>(self, v: i64) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::from(v))) }
+    fn visit_u64<E>(self, v: u64) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::from(v))) }
+    fn visit_f64<E>(self, v: f64) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::from(v))) }
+    fn visit_str<E>(self, v: &str) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::from(v))) }
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::from(base64_encode(v)))) }
+    fn visit_none<E>(self) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::Null)) }
+    fn visit_unit<E>(self) -> Result<BinaryValue, E> { Ok(BinaryValue(Value::Null)) }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<BinaryValue, D::Error> {
+        BinaryValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<BinaryValue, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element::<BinaryValue>()? { values.push(value.0); }
+        Ok(BinaryValue(Value::Array(values)))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<BinaryValue, A::Error> {
+        let mut values = JsonMap::new();
+        while let Some((key, value)) = map.next_entry::<String, BinaryValue>()? { values.insert(key, value.0); }
+        Ok(BinaryValue(Value::Object(values)))
+    }
+}
+
+/// Reads the top-level values of a save (the header, then the component
+/// storages) in either format, stopping after `limit` if given.
+fn decode_values(data: &[u8], limit: Option<usize>) -> Result<Vec<Value>, LoadError> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut values = Vec::new();
+
+    if data.starts_with(BINARY_MAGIC) {
+        let body = &data[BINARY_MAGIC.len()..];
+        let mut de = rmp_serde::Deserializer::new(Cursor::new(body));
+        while values.len() < limit && (de.position() as usize) < body.len() {
+            values.push(BinaryValue::deserialize(&mut de).map_err(parse_error)?.0);
+        }
+    } else {
+        for value in serde_json::Deserializer::from_slice(data).into_iter::<Value>().take(limit) {
+            values.push(value.map_err(parse_error)?);
+        }
+    }
+
+    Ok(values)
+}
+
+/// Splits a save into its header and component storages, applying any
+/// migrations needed to bring it up to the current version.
+fn migrate(data: &[u8]) -> Result<(SaveMetadata, JsonMap<String, Value>), LoadError> {
+    let mut values = decode_values(data, None)?;
+    if values.is_empty() { return Err(LoadError::Missing("header")); }
+
+    let mut version = values[0].get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > SAVE_VERSION { return Err(LoadError::NewerVersion(version)); }
+    // since version 10 the storages have all been in one value
+    let storage_values = POSITIONAL_STORAGE_COUNTS.get(version as usize).copied().unwrap_or(1);
+    if values.len() < 1 + storage_values { return Err(LoadError::Missing("components")); }
+    while version < SAVE_VERSION {
+        MIGRATIONS[version as usize](&mut values);
+        version += 1;
+    }
+    values[0]["version"] = Value::from(SAVE_VERSION);
+
+    let storages = match values.pop() {
+        Some(Value::Object(storages)) if !values.is_empty() => storages,
+        _ => return Err(LoadError::Missing("components"))
+    };
+    let metadata : SaveMetadata = serde_json::from_value(values.remove(0)).map_err(parse_error)?;
+    Ok((metadata, storages))
+}
+
+/// Replaces the world with the contents of the slot and returns the
+/// slot's metadata. On failure the world is left as it was.
+pub fn load_game(ecs: &mut World, slot: usize) -> Result<SaveMetadata, LoadError> {
+    let path = find_save(slot).ok_or(LoadError::Missing("file"))?;
+    let data = fs::read(path).map_err(LoadError::Io)?;
+    load_from_bytes(ecs, &data)
+}
+
+pub fn load_from_bytes(ecs: &mut World, data: &[u8]) -> Result<SaveMetadata, LoadError> {
+    let (metadata, storages) = migrate(data)?;
+
+    // a save can still turn out to be damaged part way through loading it,
+    // so keep the game in progress (if any) to put back
+    let previous = if ecs.has_value::<Entity>() {
+        let mut buffer = Vec::new();
+        write_save(ecs, &mut buffer, SaveFormat::Binary).expect("Unable to keep the game in progress");
+        Some(buffer)
+    } else {
+        None
+    };
+
+    delete_all_entities(ecs);
+    if let Err(e) = deserialize_world(ecs, storages) {
+        delete_all_entities(ecs);
+        match previous {
+            Some(previous) => {
+                let (_, storages) = migrate(&previous).expect("Unable to restore the game");
+                deserialize_world(ecs, storages).expect("Unable to restore the game");
+            }
+            None => { ecs.remove::<Entity>(); }
+        }
+        return Err(e);
+    }
+
+    let mut save_mode = ecs.write_resource::<SaveMode>();
+    save_mode.permadeath = metadata.permadeath;
+    Ok(metadata)
+}
+
+fn deserialize_world(ecs: &mut World, mut storages: JsonMap<String, Value>) -> Result<(), LoadError> {
     {
         let mut d = ( 
             &mut ecs.entities(), 
@@ -136,7 +981,7 @@ pub fn load_game(ecs: &mut World) {
         );
 
         deserialize_individually!(ecs,
-            de,
+            storages,
             d,
             Position,
             Renderable,
@@ -146,13 +991,13 @@ pub fn load_game(ecs: &mut World) {
             Name,
             BlocksTile,
             CombatStats,
-            SufferDamage,
+            TeleportsUser,
             Item,
             Consumable,
             Ranged,
             InflictsDamage,
             AreaOfEffect,
-            Confusion,
+            InflictsStatus,
             ProvidesHealing,
             InBackpack,
             WantsToMelee,
@@ -162,33 +1007,56 @@ pub fn load_game(ecs: &mut World) {
             SerializationHelper,
             Equippable,
             Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
+            MeleeWeapon,
+            Wearable,
             ParticleLifetime,
             HungerClock,
             ProvidesFood,
-            MagicMapper
+            MagicMapper,
+            OtherLevelPosition,
+            BlocksVisibility,
+            Door,
+            Attributes,
+            Skills,
+            NaturalAttackDefense,
+            Experience,
+            StatusEffect,
+            RangedWeapon,
+            Ammunition,
+            WantsToShoot
         );
     }
 
-    let mut deleteme: Option<Entity> = None;
-    {
+    let helper = {
         let entities = ecs.entities();
-        let helper = ecs.read_storage::<SerializationHelper>();
-        let player = ecs.read_storage::<Player>();
-        let position = ecs.read_storage::<Position>();
-        for (e, h) in (&entities, &helper).join() {
-            let mut worldmap = ecs.write_resource::<super::map::Map>();
-            *worldmap = h.map.clone();
-            worldmap.contents = vec![Vec::new(); super::map::MAPCOUNT];
-            deleteme = Some(e);
-        }
-        for (e, _p, pos) in (&entities, &player, &position).join() {
-            let mut ppos = ecs.write_resource::<rltk::Point>();
-            *ppos = rltk::Point::new(pos.x, pos.y);
-            let mut player_resource = ecs.write_resource::<Entity>();
-            *player_resource = e;
-        }
-    }
-    ecs.delete_entity(deleteme.unwrap()).expect("Unable to delete helper");
+        let helpers = ecs.read_storage::<SerializationHelper>();
+        (&entities, &helpers).join().map(|(e, h)| (e, h.clone())).next()
+    };
+    let (helper_entity, helper) = helper.ok_or(LoadError::Missing("map"))?;
+    ecs.delete_entity(helper_entity).expect("Unable to delete helper");
+
+    let player = {
+        let entities = ecs.entities();
+        let players = ecs.read_storage::<Player>();
+        let positions = ecs.read_storage::<Position>();
+        (&entities, &players, &positions).join().map(|(e, _p, pos)| (e, rltk::Point::new(pos.x, pos.y))).next()
+    };
+    let (player_entity, player_pos) = player.ok_or(LoadError::Missing("player"))?;
+
+    let mut worldmap = decompress_map(&helper.map)?;
+    worldmap.contents = vec![Vec::new(); (worldmap.width * worldmap.height) as usize];
+    let mut dungeon = MasterDungeonMap::new();
+    for level in helper.levels.iter() {
+        dungeon.store_map(&decompress_map(level)?);
+    }
+
+    ecs.insert(helper.rng);
+    ecs.insert(worldmap);
+    ecs.insert(dungeon);
+    ecs.insert(GameSeed { seed: helper.seed });
+    ecs.insert(TurnCounter { turns: helper.turns });
+    ecs.insert(player_pos);
+    ecs.insert(player_entity);
+
+    Ok(())
 }
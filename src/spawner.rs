@@ -1,11 +1,12 @@
 extern crate rltk;
-use rltk::{ RGB, RandomNumberGenerator };
+use rltk::{ RGB };
+use crate::RandomNumberGenerator;
 
 extern crate specs;
 use specs::prelude::*;
 use specs::saveload::{ MarkedBuilder, SimpleMarker };
 
-use std::collections::{ HashMap };
+use std::collections::{ BTreeMap, HashMap };
 
 use super::{ 
     CombatStats, 
@@ -13,31 +14,37 @@ use super::{
     Renderable, 
     Name, 
     Rect,
+    Map,
+    TileType,
     Position, 
     Viewshed, 
-    Monster, 
-    BlocksTile,
-    Item,
-    Consumable,
-    Ranged,
-    AreaOfEffect,
-    Confusion,
-    ProvidesHealing,
-    InflictsDamage,
     SerializeMe,
-    Equippable,
-    EquipmentSlot,
-    MeleePowerBonus,
-    DefenseBonus,
     HungerState,
     HungerClock,
-    ProvidesFood,
-    MagicMapper,
+    Attribute,
+    Attributes,
+    Skill,
+    Skills,
+    Experience,
+    gamesystem::player_hp_at_level,
     random_table::RandomTable,
-    map::MAPWIDTH };
+    raws::* };
 
 /// Spawns the player and returns their entity object
 pub fn player(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let attributes = Attributes {
+        might: Attribute::new(11),
+        fitness: Attribute::new(11),
+        quickness: Attribute::new(11),
+        intelligence: Attribute::new(11),
+    };
+    let mut skills = Skills { skills: HashMap::new() };
+    skills.skills.insert(Skill::Melee, 1);
+    skills.skills.insert(Skill::Defense, 1);
+    skills.skills.insert(Skill::Magic, 1);
+    skills.skills.insert(Skill::Ranged, 1);
+    let max_hp = player_hp_at_level(attributes.fitness.value(), 1);
+
     ecs.create_entity().
         with(Position { x, y }).
         with(Renderable {
@@ -49,7 +56,10 @@ pub fn player(ecs: &mut World, x: i32, y: i32) -> Entity {
         with(Player {}).
         with(Viewshed { tiles: Vec::new(), range: 8, dirty: true }).
         with(Name { name: "Player".to_string() }).
-        with(CombatStats { max_hp: 30, hp: 30, defense: 0, power: 3 }).
+        with(CombatStats { max_hp, hp: max_hp }).
+        with(attributes).
+        with(skills).
+        with(Experience { level: 1, xp: 0 }).
         with(HungerClock { state: HungerState::WellFed, duration: 20 }).
         marked::<SimpleMarker<SerializeMe>>().
         build()
@@ -57,265 +67,49 @@ pub fn player(ecs: &mut World, x: i32, y: i32) -> Entity {
 
 const MAX_THINGS : i32 = 5;
 
-#[allow(clippy::map_entry)]
-pub fn spawn_room(ecs: &mut World, room: &Rect, depth: i32) {
-    let spawn_table = room_table(depth);
-    let mut spawn_points : HashMap<usize, String> = HashMap::new();
-
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_spawns = rng.roll_dice(1, MAX_THINGS + 3) + (depth - 1) - 3;
-        for _i in 0 .. num_spawns {
-            let mut added = false;
-            let mut tries = 0;
-            while !added && tries < 20 {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAPWIDTH) + x;
-                if !spawn_points.contains_key(&idx) {
-                    spawn_points.insert(idx, spawn_table.roll(&mut rng));
-                    added = true;
-                } else {
-                    tries += 1;
-                }
+/// Picks a random selection of things to fill a room with, adding them
+/// to `spawn_list`
+pub fn spawn_room(map: &Map, rng: &mut RandomNumberGenerator, room: &Rect, depth: i32, spawn_list: &mut Vec<(usize, String)>) {
+    let mut possible_targets : Vec<usize> = Vec::new();
+    for y in room.y1 + 1 ..= room.y2 {
+        for x in room.x1 + 1 ..= room.x2 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor {
+                possible_targets.push(idx);
             }
         }
     }
 
-    for spawn in spawn_points.iter() {
-        let x = (*spawn.0 % MAPWIDTH) as i32;
-        let y = (*spawn.0 / MAPWIDTH) as i32;
-
-        match spawn.1.as_ref() {
-            "Goblin" => goblin(ecs, x, y),
-            "Orc" => orc(ecs, x, y),
-            "Health Potion" => health_potion(ecs, x, y),
-            "Fireball Scroll" => fireball_scroll(ecs, x, y),
-            "Confusion Scroll" => confusion_scroll(ecs, x, y),
-            "Magic Missile Scroll" => magic_missile_scroll(ecs, x, y),
-            "Dagger" => dagger(ecs, x, y),
-            "Shield" => shield(ecs, x, y),
-            "Longsword" => longsword(ecs, x, y),
-            "Tower Shield" => tower_shield(ecs, x, y),
-            "Rations" => rations(ecs, x, y),
-            "Magic Mapping Scroll" => magic_mapping_scroll(ecs, x, y),
-            _ => {}
-        }
-    }
-}
-
-fn room_table(depth: i32) -> RandomTable {
-    RandomTable::new().
-        add("Goblin", 10).
-        add("Orc", 1 + depth).
-        add("Health Potion", 6).
-        add("Fireball Scroll", 2 + depth).
-        add("Confusion Scroll", 2 + depth).
-        add("Magic Missile Scroll", 3).
-        add("Dagger", 3).
-        add("Shield", 3).
-        add("Longsword", depth - 1).
-        add("Tower Shield", depth - 1).
-        add("Rations", 5).
-        add("Magic Mapping Scroll", 100)
-}
-
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('o'), "Orc", 1, 4)
-}
-
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('g'), "Goblin", 1, 5)
-}
-
-fn monster<S : ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S, def: i32, pow: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 1,
-        }).
-        with(Viewshed { tiles: Vec::new(), range: 8, dirty: true }).
-        with(Monster {}).
-        with(Name { name: name.to_string() }).
-        with(BlocksTile {}).
-        with(CombatStats { max_hp: 16, hp: 16, defense: def, power: pow }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
-
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable { 
-            glyph: rltk::to_cp437('¡'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Health Potion".to_string() }).
-        with(Item {}).
-        with(ProvidesHealing { heal_amount: 8 }).
-        with(Consumable {}).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
-
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('?'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        }).
-        with(Name { name: "Magic Missile Scroll".to_string() }).
-        with(Item {}).
-        with(Consumable {}).
-        with(Ranged { range: 6 }).
-        with(InflictsDamage { damage: 8 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
-
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('?'),
-            fg: RGB::named(rltk::ORANGE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Fireball Scroll".to_string() }).
-        with(Item {}).
-        with(Consumable {}).
-        with(Ranged { range: 6 }).
-        with(InflictsDamage { damage: 20 }).
-        with(AreaOfEffect { radius: 3 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
-
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('?'),
-            fg: RGB::named(rltk::PINK),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Confusion Scroll".to_string() }).
-        with(Item {}).
-        with(Consumable {}).
-        with(Ranged { range: 6 }).
-        with(Confusion { turns: 4 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
-
-fn dagger(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Dagger".to_string() }).
-        with(Item {}).
-        with(Equippable { slot: EquipmentSlot::Melee }).
-        with(MeleePowerBonus { power: 2 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
+    spawn_region(rng, &possible_targets, depth, spawn_list);
 }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Longsword".to_string() }).
-        with(Item {}).
-        with(Equippable { slot: EquipmentSlot::Melee }).
-        with(MeleePowerBonus { power: 4 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
+/// Picks a random selection of things to fill an area, given as a list
+/// of map indices, adding them to `spawn_list`. Each tile holds at most
+/// one spawn.
+pub fn spawn_region(rng: &mut RandomNumberGenerator, area: &[usize], depth: i32, spawn_list: &mut Vec<(usize, String)>) {
+    let spawn_table = room_table(depth);
+    let mut spawn_points : BTreeMap<usize, String> = BTreeMap::new();
+    let mut areas : Vec<usize> = Vec::from(area);
+
+    let num_spawns = i32::min(areas.len() as i32, rng.roll_dice(1, MAX_THINGS + 3) + (depth - 1) - 3);
+    for _i in 0 .. num_spawns {
+        let array_index = (rng.roll_dice(1, areas.len() as i32) - 1) as usize;
+        let map_idx = areas.remove(array_index);
+        spawn_points.insert(map_idx, spawn_table.roll(rng));
+    }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('('),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Shield".to_string() }).
-        with(Item {}).
-        with(Equippable { slot: EquipmentSlot::Shield }).
-        with(DefenseBonus { defense: 2 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
+    spawn_list.extend(spawn_points);
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('('),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        }).
-        with(Name { name: "Tower Shield".to_string() }).
-        with(Item {}).
-        with(Equippable { slot: EquipmentSlot::Shield }).
-        with(DefenseBonus { defense: 3 }).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
-}
+/// Creates one entry from a builder's spawn list
+pub fn spawn_entity(ecs: &mut World, spawn: &(usize, String)) {
+    let width = ecs.fetch::<Map>().width as usize;
+    let x = (spawn.0 % width) as i32;
+    let y = (spawn.0 / width) as i32;
 
-fn rations(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('='),
-            fg: RGB::named(rltk::GREEN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        }).
-        with(Name { name: "Rations".to_string() }).
-        with(Item {}).
-        with(ProvidesFood {}).
-        with(Consumable {}).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
+    spawn_named_entity(&RAWS.lock().unwrap(), ecs, &spawn.1, SpawnType::AtPosition { x, y });
 }
 
-fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity().
-        with(Position { x, y }).
-        with(Renderable {
-            glyph: rltk::to_cp437('?'),
-            fg: RGB::named(rltk::CYAN3),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        }).
-        with(Name { name: "Scroll of Magic Mapping".to_string() }).
-        with(Item {}).
-        with(MagicMapper {}).
-        with(Consumable {}).
-        marked::<SimpleMarker<SerializeMe>>().
-        build();
+fn room_table(depth: i32) -> RandomTable {
+    get_spawn_table_for_depth(&RAWS.lock().unwrap(), depth)
 }
@@ -0,0 +1,87 @@
+extern crate specs;
+use specs::prelude::*;
+
+extern crate rltk;
+use crate::RandomNumberGenerator;
+
+use super::{
+    StatusEffect,
+    StatusKind,
+    Viewshed,
+    RunState,
+    OtherLevelPosition,
+    effects::{ EffectQueue, EffectType, Targets },
+    gamelog::GameLog };
+
+/// Whether `target` is currently under the effect
+pub fn has_status<'a, J: Join<Type = &'a StatusEffect>>(statuses: J, target: Entity, kind: StatusKind) -> bool {
+    statuses.join().any(|s| s.target == target && s.kind == kind)
+}
+
+/// Which way a confused actor stumbles
+pub fn random_direction(rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    const DIRECTIONS : [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+    DIRECTIONS[rng.roll_dice(1, 8) as usize - 1]
+}
+
+/// Counts down every status effect once per game turn (on the monsters'
+/// turn, so a hasted player's free turns don't count), queueing the
+/// poison and regeneration as it goes. Effects wear off when their
+/// turns run out, or when whoever they were on is gone. Effects on
+/// anyone frozen on another level wait until they're back.
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, StatusEffect>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        ReadStorage<'a, OtherLevelPosition>,
+                        WriteStorage<'a, Viewshed>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, EffectQueue> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities,
+            mut statuses,
+            runstate,
+            player,
+            frozen,
+            mut viewsheds,
+            mut log,
+            mut effects) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let mut finished : Vec<Entity> = Vec::new();
+        for (entity, status) in (&entities, &mut statuses).join() {
+            if !entities.is_alive(status.target) {
+                finished.push(entity);
+                continue;
+            }
+            if frozen.get(status.target).is_some() { continue; }
+
+            let target = Targets::Single { target: status.target };
+            match status.kind {
+                StatusKind::Poison => effects.add(None, EffectType::Damage { amount: status.magnitude }, target),
+                StatusKind::Regeneration => effects.add(None, EffectType::Healing { amount: status.magnitude }, target),
+                _ => {}
+            }
+
+            status.turns -= 1;
+            if status.turns < 1 {
+                finished.push(entity);
+                if status.target == *player {
+                    log.entries.insert(0, format!("You are no longer {}", status.kind.adjective()));
+                }
+                if let Some(viewshed) = viewsheds.get_mut(status.target) {
+                    viewshed.dirty = true;
+                }
+            }
+        }
+        for entity in finished.iter() {
+            statuses.remove(*entity);
+            entities.delete(*entity).expect("Unable to delete status");
+        }
+    }
+}
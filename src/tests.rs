@@ -0,0 +1,1958 @@
+use rltk::{ BaseMap, Point };
+use crate::RandomNumberGenerator;
+use specs::prelude::*;
+use specs::saveload::{ Marker, MarkerAllocator };
+
+use super::*;
+use super::damage_system::delete_the_dead;
+use super::effects::{ EffectQueue, EffectType, Targets, run_effects_queue };
+use super::headless::HeadlessGame;
+use super::hunger_system::HungerSystem;
+use super::ranged_combat_system::line_of_fire;
+use super::status_system::StatusEffectSystem;
+
+/// Builds a world with an open 80x50 map, the player at (10, 10) and
+/// none of the random content of a real level.
+fn test_state() -> State {
+    let mut gs = State::new();
+    gs.ecs.write_resource::<saveload_system::SaveMode>().autosave = false;
+
+    let mut map = Map::new(1, 80, 50);
+    for y in 1 .. map.height - 1 {
+        for x in 1 .. map.width - 1 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+    gs.ecs.insert(map);
+    gs.ecs.insert(GameLog { entries: Vec::new() });
+    gs.ecs.insert(GameSeed { seed: 1 });
+    gs.ecs.insert(RandomNumberGenerator::seeded(1));
+    gs.ecs.insert(RunState::PlayerTurn);
+    gs.ecs.insert(TurnCounter { turns: 0 });
+
+    let player = spawner::player(&mut gs.ecs, 10, 10);
+    gs.ecs.insert(player);
+    gs.ecs.insert(Point::new(10, 10));
+    set_hunger(&mut gs, player, HungerState::Normal, 200);
+
+    gs
+}
+
+fn player_entity(gs: &State) -> Entity {
+    *gs.ecs.fetch::<Entity>()
+}
+
+/// An untrained monster with no attributes, so it has no bonuses and an
+/// armour class of 10
+fn mob(gs: &mut State, x: i32, y: i32, hp: i32) -> Entity {
+    gs.ecs.create_entity().
+        with(Position { x, y }).
+        with(Name { name: "Test Mob".to_string() }).
+        with(Monster {}).
+        with(CombatStats { max_hp: hp, hp }).
+        build()
+}
+
+fn give_natural(gs: &mut State, entity: Entity, armor_class: i32, damage: &str) {
+    let (damage_n_dice, damage_die_type, damage_bonus) = raws::parse_dice_string(damage);
+    let attack = NaturalAttack { name: "bite".to_string(), damage_n_dice, damage_die_type, damage_bonus, hit_bonus: 0 };
+    gs.ecs.write_storage::<NaturalAttackDefense>()
+        .insert(entity, NaturalAttackDefense { armor_class: Some(armor_class), attacks: vec![attack] })
+        .unwrap();
+}
+
+fn backpack_item(gs: &mut State, name: &str) -> Entity {
+    let player = player_entity(gs);
+    gs.ecs.create_entity().
+        with(Name { name: name.to_string() }).
+        with(Item {}).
+        with(InBackpack { owner: player }).
+        build()
+}
+
+fn equip(gs: &mut State, item: Entity, owner: Entity, slot: EquipmentSlot) {
+    gs.ecs.write_storage::<Equipped>().insert(item, Equipped { owner, slot }).unwrap();
+}
+
+fn set_hunger(gs: &mut State, entity: Entity, state: HungerState, duration: i32) {
+    gs.ecs.write_storage::<HungerClock>().insert(entity, HungerClock { state, duration }).unwrap();
+}
+
+fn melee(gs: &mut State, attacker: Entity, target: Entity) {
+    gs.ecs.write_storage::<WantsToMelee>().insert(attacker, WantsToMelee { target }).unwrap();
+    MeleeCombatSystem{}.run_now(&gs.ecs);
+    run_effects_queue(&mut gs.ecs);
+    gs.ecs.maintain();
+}
+
+fn use_item(gs: &mut State, item: Entity, target: Option<Point>) {
+    let player = player_entity(gs);
+    MapIndexingSystem{}.run_now(&gs.ecs);
+    gs.ecs.write_storage::<WantsToUseItem>().insert(player, WantsToUseItem { item, target }).unwrap();
+    ItemEquipOnUse{}.run_now(&gs.ecs);
+    ItemUseSystem{}.run_now(&gs.ecs);
+    run_effects_queue(&mut gs.ecs);
+    gs.ecs.maintain();
+}
+
+fn queue_effect(gs: &mut State, effect_type: EffectType, target: Entity) {
+    gs.ecs.write_resource::<EffectQueue>().add(None, effect_type, Targets::Single { target });
+}
+
+fn hp(gs: &State, entity: Entity) -> i32 {
+    gs.ecs.read_storage::<CombatStats>().get(entity).unwrap().hp
+}
+
+/// Makes `rounds` attacks and returns the damage each one did, None for a miss
+fn melee_rounds(gs: &mut State, attacker: Entity, target: Entity, rounds: usize) -> Vec<Option<i32>> {
+    let mut results = Vec::new();
+    for _ in 0 .. rounds {
+        let before = hp(gs, target);
+        melee(gs, attacker, target);
+        let after = hp(gs, target);
+        results.push(if after < before { Some(before - after) } else { None });
+        gs.ecs.write_storage::<CombatStats>().get_mut(target).unwrap().hp = before;
+    }
+    results
+}
+
+#[test]
+fn attacks_follow_the_d20_rules() {
+    assert!(!gamesystem::attack_hits(1, 100, 5));
+    assert!(gamesystem::attack_hits(20, -100, 30));
+    assert!(gamesystem::attack_hits(10, 2, 12));
+    assert!(!gamesystem::attack_hits(10, 1, 12));
+
+    let bonuses : Vec<i32> = (7 ..= 13).map(gamesystem::attr_bonus).collect();
+    assert_eq!(bonuses, vec![-2, -1, -1, 0, 0, 1, 1]);
+
+    let mut skills = Skills { skills: std::collections::HashMap::new() };
+    skills.skills.insert(Skill::Melee, 2);
+    assert_eq!(gamesystem::skill_bonus(Skill::Melee, &skills), 2);
+    assert_eq!(gamesystem::skill_bonus(Skill::Magic, &skills), -4);
+}
+
+#[test]
+fn melee_damage_is_rolled_on_the_attack_dice() {
+    let mut gs = test_state();
+    let attacker = mob(&mut gs, 5, 5, 10);
+    give_natural(&mut gs, attacker, 10, "2d4+1");
+    let target = mob(&mut gs, 6, 5, 10);
+    give_natural(&mut gs, target, 0, "1d4");
+
+    let results = melee_rounds(&mut gs, attacker, target, 200);
+
+    let damage : Vec<i32> = results.iter().flatten().copied().collect();
+    assert!(damage.iter().all(|d| (3 ..= 9).contains(d)));
+    assert!(damage.contains(&3) && damage.contains(&9));
+    // only natural 1s miss such a low armour class
+    let misses = results.len() - damage.len();
+    assert!(misses > 0 && misses < 30);
+    assert!(gs.ecs.read_storage::<WantsToMelee>().is_empty());
+}
+
+#[test]
+fn melee_uses_the_equipped_weapon_and_armour() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let target = mob(&mut gs, 11, 10, 10);
+    give_natural(&mut gs, target, 0, "1d4");
+
+    let dagger = backpack_item(&mut gs, "Dagger");
+    let weapon = MeleeWeapon { attribute: WeaponAttribute::Quickness, damage_n_dice: 1, damage_die_type: 1, damage_bonus: 4, hit_bonus: 0 };
+    gs.ecs.write_storage::<MeleeWeapon>().insert(dagger, weapon).unwrap();
+    equip(&mut gs, dagger, player, EquipmentSlot::Melee);
+
+    // 1d1 + 4, and the player's average might adds nothing
+    let damage : Vec<i32> = melee_rounds(&mut gs, player, target, 50).into_iter().flatten().collect();
+    assert!(!damage.is_empty());
+    assert!(damage.iter().all(|d| *d == 5));
+
+    let shield = backpack_item(&mut gs, "Shield");
+    gs.ecs.write_storage::<Wearable>().insert(shield, Wearable { armor_class: 100 }).unwrap();
+    equip(&mut gs, shield, target, EquipmentSlot::Shield);
+
+    // now only a natural 20 gets through
+    let hits = melee_rounds(&mut gs, player, target, 200).into_iter().flatten().count();
+    assert!(hits > 0 && hits < 30);
+}
+
+#[test]
+fn an_unarmed_hit_always_does_some_damage() {
+    let mut gs = test_state();
+    let attacker = mob(&mut gs, 5, 5, 10);
+    gs.ecs.write_storage::<Attributes>().insert(attacker, Attributes {
+        might: Attribute::new(3),
+        fitness: Attribute::new(11),
+        quickness: Attribute::new(11),
+        intelligence: Attribute::new(11),
+    }).unwrap();
+    let target = mob(&mut gs, 6, 5, 10);
+    give_natural(&mut gs, target, 0, "1d4");
+
+    // 1d4 fists with -4 for might 3
+    let damage : Vec<i32> = melee_rounds(&mut gs, attacker, target, 50).into_iter().flatten().collect();
+    assert!(!damage.is_empty());
+    assert!(damage.iter().all(|d| *d == 1));
+}
+
+#[test]
+fn dice_strings_are_parsed() {
+    assert_eq!(raws::parse_dice_string("1d6"), (1, 6, 0));
+    assert_eq!(raws::parse_dice_string("2d4+1"), (2, 4, 1));
+    assert_eq!(raws::parse_dice_string("3d8-2"), (3, 8, -2));
+}
+
+#[test]
+#[should_panic(expected = "Bad Potion: effect provides_healing lots is not a number")]
+fn typos_in_the_raws_fail_when_they_load() {
+    let raws : raws::Raws = serde_json::from_str(r#"{
+        "items" : [ { "name" : "Bad Potion", "consumable" : { "effects" : { "provides_healing" : "lots" } } } ],
+        "mobs" : [], "props" : [], "spawn_table" : []
+    }"#).unwrap();
+    raws::RawMaster::empty().load(raws);
+}
+
+/// What the raws validator makes of a raw file with the given entries
+fn raw_problem(items: &str, mobs: &str, props: &str) -> Option<String> {
+    let json = format!(r#"{{ "items" : [{}], "mobs" : [{}], "props" : [{}], "spawn_table" : [] }}"#, items, mobs, props);
+    let raws : raws::Raws = serde_json::from_str(&json).unwrap();
+    raws::validate(&raws).err()
+}
+
+#[test]
+fn bad_dice_and_renderables_are_caught_when_the_raws_load() {
+    let sword = r#"{ "name" : "Bent Sword", "weapon" : { "range" : "melee", "attribute" : "Might", "base_damage" : "1d", "hit_bonus" : 0 } }"#;
+    assert_eq!(raw_problem(sword, "", "").unwrap(), "Bent Sword: base damage 1d is not a dice string");
+
+    let rat = r#"{ "name" : "Rat", "blocks_tile" : true, "hp" : 2, "attributes" : {}, "vision_range" : 4,
+        "natural" : { "attacks" : [ { "name" : "bite", "hit_bonus" : 0, "damage" : "d4" } ] } }"#;
+    assert_eq!(raw_problem("", rat, "").unwrap(), "Rat: bite damage d4 is not a dice string");
+
+    let trap = r##"{ "name" : "Trap", "renderable" : { "glyph" : "", "fg" : "#FF0000", "bg" : "#000000", "order" : 2 } }"##;
+    assert_eq!(raw_problem("", "", trap).unwrap(), "Trap: glyph \"\" is not a single character");
+
+    let apple = r##"{ "name" : "Apple", "renderable" : { "glyph" : "%", "fg" : "red", "bg" : "#000000", "order" : 2 } }"##;
+    assert_eq!(raw_problem(apple, "", "").unwrap(), "Apple: red is not a colour");
+}
+
+#[test]
+fn the_spawn_table_only_names_things_that_exist() {
+    let raws : raws::Raws = serde_json::from_str(r#"{
+        "items" : [ { "name" : "Rations" } ], "mobs" : [], "props" : [],
+        "spawn_table" : [
+            { "name" : "Rations", "weight" : 5, "min_depth" : 0, "max_depth" : 100 },
+            { "name" : "Goblin Cheif", "weight" : 1, "min_depth" : 0, "max_depth" : 100 }
+        ]
+    }"#).unwrap();
+    assert_eq!(raws::validate(&raws).unwrap_err(), "spawn table: Goblin Cheif is not an item, mob or prop");
+}
+
+#[test]
+fn healing_is_capped_at_max_hp() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    gs.ecs.write_storage::<CombatStats>().get_mut(player).unwrap().hp = 20;
+
+    let potion = backpack_item(&mut gs, "Health Potion");
+    gs.ecs.write_storage::<ProvidesHealing>().insert(potion, ProvidesHealing { heal_amount: 8 }).unwrap();
+    gs.ecs.write_storage::<Consumable>().insert(potion, Consumable {}).unwrap();
+    use_item(&mut gs, potion, None);
+    assert_eq!(hp(&gs, player), 28);
+    assert!(!gs.ecs.is_alive(potion));
+
+    let potion = backpack_item(&mut gs, "Health Potion");
+    gs.ecs.write_storage::<ProvidesHealing>().insert(potion, ProvidesHealing { heal_amount: 8 }).unwrap();
+    use_item(&mut gs, potion, None);
+    assert_eq!(hp(&gs, player), 30);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "The Health Potion heals you by 8 points");
+}
+
+#[test]
+fn healing_someone_else_says_who_was_healed() {
+    let mut gs = test_state();
+    let target = mob(&mut gs, 15, 10, 10);
+    gs.ecs.write_storage::<CombatStats>().get_mut(target).unwrap().hp = 2;
+
+    let potion = backpack_item(&mut gs, "Health Potion");
+    gs.ecs.write_storage::<ProvidesHealing>().insert(potion, ProvidesHealing { heal_amount: 5 }).unwrap();
+    use_item(&mut gs, potion, Some(Point::new(15, 10)));
+
+    assert_eq!(hp(&gs, target), 7);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "The Health Potion heals Test Mob by 5 points");
+}
+
+#[test]
+fn area_of_effect_hits_everything_in_the_blast() {
+    let mut gs = test_state();
+    let near = mob(&mut gs, 21, 21, 30);
+    let center = mob(&mut gs, 20, 20, 30);
+    let far = mob(&mut gs, 30, 30, 30);
+
+    let scroll = backpack_item(&mut gs, "Fireball Scroll");
+    gs.ecs.write_storage::<InflictsDamage>().insert(scroll, InflictsDamage { damage: 20 }).unwrap();
+    gs.ecs.write_storage::<AreaOfEffect>().insert(scroll, AreaOfEffect { radius: 3 }).unwrap();
+    gs.ecs.write_storage::<Consumable>().insert(scroll, Consumable {}).unwrap();
+    use_item(&mut gs, scroll, Some(Point::new(20, 20)));
+
+    assert_eq!(hp(&gs, near), 10);
+    assert_eq!(hp(&gs, center), 10);
+    assert_eq!(hp(&gs, far), 30);
+    assert!(!gs.ecs.is_alive(scroll));
+}
+
+#[test]
+fn area_of_effect_does_not_pass_through_walls() {
+    let mut gs = test_state();
+    let hidden = mob(&mut gs, 22, 20, 30);
+    {
+        let mut map = gs.ecs.write_resource::<Map>();
+        for y in 15 .. 25 {
+            let idx = map.xy_idx(21, y);
+            map.tiles[idx] = TileType::Wall;
+        }
+    }
+
+    let scroll = backpack_item(&mut gs, "Fireball Scroll");
+    gs.ecs.write_storage::<InflictsDamage>().insert(scroll, InflictsDamage { damage: 20 }).unwrap();
+    gs.ecs.write_storage::<AreaOfEffect>().insert(scroll, AreaOfEffect { radius: 3 }).unwrap();
+    use_item(&mut gs, scroll, Some(Point::new(20, 20)));
+
+    assert_eq!(hp(&gs, hidden), 30);
+}
+
+#[test]
+fn confusion_is_applied_to_the_target() {
+    let mut gs = test_state();
+    let target = mob(&mut gs, 15, 10, 10);
+
+    let scroll = backpack_item(&mut gs, "Confusion Scroll");
+    gs.ecs.write_storage::<InflictsStatus>().insert(scroll, InflictsStatus { kind: StatusKind::Confusion, turns: 4, magnitude: 0 }).unwrap();
+    use_item(&mut gs, scroll, Some(Point::new(15, 10)));
+
+    assert_eq!(status_turns(&gs, target, StatusKind::Confusion), Some(4));
+}
+
+fn add_status(gs: &mut State, target: Entity, kind: StatusKind, turns: i32, magnitude: i32) {
+    queue_effect(gs, EffectType::Status { kind, turns, magnitude }, target);
+    run_effects_queue(&mut gs.ecs);
+    gs.ecs.maintain();
+}
+
+/// Runs the effects for one player turn
+fn tick_statuses(gs: &mut State) {
+    gs.ecs.insert(RunState::MonsterTurn);
+    StatusEffectSystem{}.run_now(&gs.ecs);
+    run_effects_queue(&mut gs.ecs);
+    gs.ecs.maintain();
+}
+
+fn status_turns(gs: &State, target: Entity, kind: StatusKind) -> Option<i32> {
+    let statuses = gs.ecs.read_storage::<StatusEffect>();
+    statuses.join().find(|s| s.target == target && s.kind == kind).map(|s| s.turns)
+}
+
+#[test]
+fn poison_and_regeneration_tick_until_they_wear_off() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let monster = mob(&mut gs, 5, 5, 20);
+    gs.ecs.write_storage::<CombatStats>().get_mut(player).unwrap().hp = 20;
+    add_status(&mut gs, monster, StatusKind::Poison, 3, 2);
+    add_status(&mut gs, player, StatusKind::Regeneration, 2, 3);
+
+    for _ in 0 .. 5 { tick_statuses(&mut gs); }
+
+    assert_eq!(hp(&gs, monster), 14);
+    assert_eq!(hp(&gs, player), 26);
+    assert!(gs.ecs.read_storage::<StatusEffect>().is_empty());
+    assert!(gs.ecs.fetch::<GameLog>().entries.contains(&"You are no longer regenerating".to_string()));
+}
+
+#[test]
+fn statuses_stack_refresh_or_cancel() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+
+    // poison gets worse
+    add_status(&mut gs, player, StatusKind::Poison, 3, 1);
+    add_status(&mut gs, player, StatusKind::Poison, 2, 2);
+    {
+        let statuses = gs.ecs.read_storage::<StatusEffect>();
+        let poison : Vec<&StatusEffect> = statuses.join().filter(|s| s.kind == StatusKind::Poison).collect();
+        assert_eq!(poison.len(), 1);
+        assert_eq!((poison[0].turns, poison[0].magnitude), (3, 3));
+    }
+
+    // other effects last as long as the longer dose
+    add_status(&mut gs, player, StatusKind::Confusion, 2, 0);
+    add_status(&mut gs, player, StatusKind::Confusion, 5, 0);
+    add_status(&mut gs, player, StatusKind::Confusion, 1, 0);
+    assert_eq!(status_turns(&gs, player, StatusKind::Confusion), Some(5));
+
+    // haste and slow cancel out
+    add_status(&mut gs, player, StatusKind::Slow, 5, 0);
+    add_status(&mut gs, player, StatusKind::Haste, 5, 0);
+    assert_eq!(status_turns(&gs, player, StatusKind::Slow), None);
+    assert_eq!(status_turns(&gs, player, StatusKind::Haste), None);
+    assert_eq!(gs.ecs.read_storage::<StatusEffect>().join().count(), 2);
+}
+
+#[test]
+fn statuses_on_the_dead_are_cleaned_up() {
+    let mut gs = test_state();
+    let monster = mob(&mut gs, 5, 5, 1);
+    add_status(&mut gs, monster, StatusKind::Poison, 10, 1);
+    tick_statuses(&mut gs);
+    delete_the_dead(&mut gs.ecs);
+    gs.ecs.maintain();
+
+    tick_statuses(&mut gs);
+    assert!(gs.ecs.read_storage::<StatusEffect>().is_empty());
+}
+
+#[test]
+fn statuses_wait_while_their_target_is_on_another_level() {
+    let mut gs = test_state();
+    let monster = mob(&mut gs, 5, 5, 10);
+    add_status(&mut gs, monster, StatusKind::Poison, 3, 1);
+    dungeon::freeze_level_entities(&mut gs.ecs);
+
+    for _ in 0 .. 5 { tick_statuses(&mut gs); }
+    assert_eq!(status_turns(&gs, monster, StatusKind::Poison), Some(3));
+    assert_eq!(gs.ecs.read_storage::<CombatStats>().get(monster).unwrap().hp, 10);
+}
+
+#[test]
+fn the_player_suffers_statuses_too() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+
+    add_status(&mut gs, player, StatusKind::Paralysis, 2, 0);
+    assert!(player::apply_command(&mut gs.ecs, player::PlayerCommand::Move { delta_x: 1, delta_y: 0 }) == RunState::PlayerTurn);
+    assert_eq!(position_of(&gs, player), (10, 10));
+    for _ in 0 .. 2 { tick_statuses(&mut gs); }
+
+    // a confused player stumbles about instead of going where they meant to
+    add_status(&mut gs, player, StatusKind::Confusion, 20, 0);
+    let mut went_right = 0;
+    for _ in 0 .. 20 {
+        let (x, _) = position_of(&gs, player);
+        player::apply_command(&mut gs.ecs, player::PlayerCommand::Move { delta_x: 1, delta_y: 0 });
+        if position_of(&gs, player).0 == x + 1 { went_right += 1; }
+    }
+    assert!(went_right < 15);
+
+    // and the blind can only see what's next to them
+    add_status(&mut gs, player, StatusKind::Blindness, 5, 0);
+    VisibilitySystem{}.run_now(&gs.ecs);
+    let viewsheds = gs.ecs.read_storage::<Viewshed>();
+    assert!(viewsheds.get(player).unwrap().tiles.len() <= 9);
+}
+
+#[test]
+fn paralysed_and_slowed_monsters_lose_turns() {
+    let mut gs = test_state();
+    let paralysed = mob(&mut gs, 11, 10, 10);
+    let slowed = mob(&mut gs, 9, 10, 10);
+    add_status(&mut gs, paralysed, StatusKind::Paralysis, 5, 0);
+    add_status(&mut gs, slowed, StatusKind::Slow, 5, 0);
+    for monster in [paralysed, slowed].iter() {
+        gs.ecs.write_storage::<Viewshed>().insert(*monster, Viewshed { tiles: vec![Point::new(10, 10)], range: 8, dirty: false }).unwrap();
+    }
+    gs.ecs.insert(RunState::MonsterTurn);
+
+    let mut attacks = Vec::new();
+    for turn in 1 ..= 4 {
+        gs.ecs.write_resource::<TurnCounter>().turns = turn;
+        MonsterAI{ hasted_only: false }.run_now(&gs.ecs);
+        let wants_melee = gs.ecs.read_storage::<WantsToMelee>();
+        attacks.push((wants_melee.get(paralysed).is_some(), wants_melee.get(slowed).is_some()));
+        drop(wants_melee);
+        gs.ecs.write_storage::<WantsToMelee>().clear();
+    }
+
+    assert_eq!(attacks, vec![(false, true), (false, false), (false, true), (false, false)]);
+}
+
+#[test]
+fn a_hasted_player_moves_twice_for_each_monster_turn() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    queue_effect(&mut game.gs, EffectType::Status { kind: StatusKind::Haste, turns: 10, magnitude: 0 }, player);
+
+    let commands : Vec<player::PlayerCommand> = "ssss".chars().filter_map(player::PlayerCommand::from_char).collect();
+    game.run_script(&commands);
+
+    // the haste lands during the first turn, then every other turn is free
+    let turns = game.gs.ecs.fetch::<TurnCounter>().turns;
+    assert_eq!(turns, 4);
+    // and the haste only counts down on the turns the monsters get too
+    assert_eq!(status_turns(&game.gs, player, StatusKind::Haste), Some(8));
+}
+
+#[test]
+fn statuses_are_saved_with_their_target() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    queue_effect(&mut game.gs, EffectType::Status { kind: StatusKind::Poison, turns: 10, magnitude: 1 }, player);
+    game.run_script(&[player::PlayerCommand::Wait]);
+
+    for format in [saveload_system::SaveFormat::Json, saveload_system::SaveFormat::Binary].iter() {
+        let mut buffer = Vec::new();
+        saveload_system::write_save(&mut game.gs.ecs, &mut buffer, *format).unwrap();
+        let mut loaded = HeadlessGame::new(8);
+        saveload_system::load_from_bytes(&mut loaded.gs.ecs, &buffer).unwrap();
+        let player = player_entity(&loaded.gs);
+        assert!(status_turns(&loaded.gs, player, StatusKind::Poison).is_some());
+    }
+}
+
+#[test]
+fn equipping_swaps_out_the_item_in_the_same_slot() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+
+    let dagger = backpack_item(&mut gs, "Dagger");
+    gs.ecs.write_storage::<InBackpack>().remove(dagger);
+    equip(&mut gs, dagger, player, EquipmentSlot::Melee);
+    let shield = backpack_item(&mut gs, "Shield");
+    gs.ecs.write_storage::<InBackpack>().remove(shield);
+    equip(&mut gs, shield, player, EquipmentSlot::Shield);
+
+    let longsword = backpack_item(&mut gs, "Longsword");
+    gs.ecs.write_storage::<Equippable>().insert(longsword, Equippable { slot: EquipmentSlot::Melee }).unwrap();
+    use_item(&mut gs, longsword, None);
+
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    assert!(equipped.get(longsword).is_some());
+    assert!(backpack.get(longsword).is_none());
+    assert!(equipped.get(dagger).is_none());
+    assert_eq!(backpack.get(dagger).map(|b| b.owner), Some(player));
+    assert!(equipped.get(shield).is_some());
+}
+
+#[test]
+fn eating_makes_the_player_well_fed() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    set_hunger(&mut gs, player, HungerState::Hungry, 50);
+
+    let rations = backpack_item(&mut gs, "Rations");
+    gs.ecs.write_storage::<ProvidesFood>().insert(rations, ProvidesFood {}).unwrap();
+    use_item(&mut gs, rations, None);
+
+    let clocks = gs.ecs.read_storage::<HungerClock>();
+    let clock = clocks.get(player).unwrap();
+    assert!(clock.state == HungerState::WellFed);
+    assert_eq!(clock.duration, 20);
+}
+
+#[test]
+fn hunger_steps_down_when_the_clock_runs_out() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let transitions = [
+        (HungerState::WellFed, HungerState::Normal),
+        (HungerState::Normal, HungerState::Hungry),
+        (HungerState::Hungry, HungerState::Starving),
+    ];
+
+    for (from, to) in transitions.iter() {
+        set_hunger(&mut gs, player, *from, 1);
+        HungerSystem{}.run_now(&gs.ecs);
+
+        let clocks = gs.ecs.read_storage::<HungerClock>();
+        let clock = clocks.get(player).unwrap();
+        assert!(clock.state == *to);
+        assert_eq!(clock.duration, 200);
+    }
+}
+
+#[test]
+fn starving_causes_damage() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    set_hunger(&mut gs, player, HungerState::Starving, 1);
+
+    HungerSystem{}.run_now(&gs.ecs);
+    run_effects_queue(&mut gs.ecs);
+
+    assert_eq!(hp(&gs, player), 29);
+}
+
+#[test]
+fn hunger_only_ticks_on_the_owners_turn() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let monster = mob(&mut gs, 5, 5, 10);
+    set_hunger(&mut gs, monster, HungerState::Normal, 10);
+
+    HungerSystem{}.run_now(&gs.ecs);
+    assert_eq!(gs.ecs.read_storage::<HungerClock>().get(player).unwrap().duration, 199);
+    assert_eq!(gs.ecs.read_storage::<HungerClock>().get(monster).unwrap().duration, 10);
+
+    gs.ecs.insert(RunState::MonsterTurn);
+    HungerSystem{}.run_now(&gs.ecs);
+    assert_eq!(gs.ecs.read_storage::<HungerClock>().get(player).unwrap().duration, 199);
+    assert_eq!(gs.ecs.read_storage::<HungerClock>().get(monster).unwrap().duration, 9);
+}
+
+#[test]
+fn delete_the_dead_removes_dead_monsters() {
+    let mut gs = test_state();
+    let dead = mob(&mut gs, 5, 5, 0);
+    let alive = mob(&mut gs, 6, 5, 1);
+
+    delete_the_dead(&mut gs.ecs);
+
+    assert!(!gs.ecs.is_alive(dead));
+    assert!(gs.ecs.is_alive(alive));
+    assert!(*gs.ecs.fetch::<RunState>() == RunState::PlayerTurn);
+}
+
+#[test]
+fn delete_the_dead_ends_the_game_when_the_player_dies() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    gs.ecs.write_storage::<CombatStats>().get_mut(player).unwrap().hp = 0;
+
+    delete_the_dead(&mut gs.ecs);
+
+    assert!(gs.ecs.is_alive(player));
+    assert!(*gs.ecs.fetch::<RunState>() == RunState::GameOver);
+}
+
+#[test]
+fn every_hit_in_a_turn_lands() {
+    let mut gs = test_state();
+    let monster = mob(&mut gs, 5, 5, 20);
+    for amount in [3, 4].iter() {
+        queue_effect(&mut gs, EffectType::Damage { amount: *amount }, monster);
+    }
+    queue_effect(&mut gs, EffectType::Healing { amount: 2 }, monster);
+    run_effects_queue(&mut gs.ecs);
+
+    assert_eq!(hp(&gs, monster), 15);
+    let map = gs.ecs.fetch::<Map>();
+    assert!(map.bloodstains.contains(&map.xy_idx(5, 5)));
+}
+
+#[test]
+fn tile_effects_land_on_everything_there() {
+    let mut gs = test_state();
+    let first = mob(&mut gs, 5, 5, 20);
+    let second = mob(&mut gs, 5, 5, 20);
+    let elsewhere = mob(&mut gs, 6, 6, 20);
+    MapIndexingSystem{}.run_now(&gs.ecs);
+    let (here, there) = {
+        let map = gs.ecs.fetch::<Map>();
+        (map.xy_idx(5, 5), map.xy_idx(6, 6))
+    };
+
+    gs.ecs.write_resource::<EffectQueue>().add(None, EffectType::Damage { amount: 5 }, Targets::Tile { tile_idx: here });
+    gs.ecs.write_resource::<EffectQueue>().add(None, EffectType::Damage { amount: 1 }, Targets::Tiles { tiles: vec![here, there] });
+    run_effects_queue(&mut gs.ecs);
+
+    assert_eq!((hp(&gs, first), hp(&gs, second), hp(&gs, elsewhere)), (14, 14, 19));
+}
+
+#[test]
+fn a_blink_scroll_teleports_the_reader() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let blocker = mob(&mut gs, 15, 15, 10);
+    gs.ecs.write_storage::<BlocksTile>().insert(blocker, BlocksTile {}).unwrap();
+    for (target, expected) in [((15, 15), (10, 10)), ((12, 14), (12, 14))].iter() {
+        let scroll = backpack_item(&mut gs, "Blink Scroll");
+        gs.ecs.write_storage::<TeleportsUser>().insert(scroll, TeleportsUser {}).unwrap();
+        gs.ecs.write_storage::<Consumable>().insert(scroll, Consumable {}).unwrap();
+        use_item(&mut gs, scroll, Some(Point::new(target.0, target.1)));
+
+        assert_eq!(position_of(&gs, player), *expected);
+        assert!(!gs.ecs.is_alive(scroll));
+    }
+    assert_eq!(*gs.ecs.fetch::<Point>(), Point::new(12, 14));
+    assert!(gs.ecs.fetch::<GameLog>().entries.contains(&"Something blocks the way".to_string()));
+}
+
+/// Spawns the named item from the raws and hands it to `owner`, equipped
+fn equip_named(gs: &mut State, name: &str, owner: Entity, slot: EquipmentSlot) -> Entity {
+    raws::load_raws();
+    let item = raws::spawn_named_entity(&raws::RAWS.lock().unwrap(), &mut gs.ecs, name, raws::SpawnType::AtPosition { x: 1, y: 1 }).unwrap();
+    gs.ecs.write_storage::<Position>().remove(item);
+    equip(gs, item, owner, slot);
+    item
+}
+
+fn ammo_count(gs: &State, ammo: Entity) -> i32 {
+    gs.ecs.read_storage::<Ammunition>().get(ammo).unwrap().count
+}
+
+/// Fires the player's weapon at `target` and lands the results
+fn shoot(gs: &mut State, target: Point) -> RunState {
+    VisibilitySystem{}.run_now(&gs.ecs);
+    MapIndexingSystem{}.run_now(&gs.ecs);
+    let next = player::apply_command(&mut gs.ecs, PlayerCommand::Fire { target: Some(target) });
+    RangedCombatSystem{}.run_now(&gs.ecs);
+    run_effects_queue(&mut gs.ecs);
+    gs.ecs.maintain();
+    next
+}
+
+/// The spent ammunition lying at (x, y), by count
+fn ammo_on_floor(gs: &State, x: i32, y: i32) -> Vec<i32> {
+    let positions = gs.ecs.read_storage::<Position>();
+    let ammunition = gs.ecs.read_storage::<Ammunition>();
+    (&positions, &ammunition).join()
+        .filter(|(pos, _)| pos.x == x && pos.y == y)
+        .map(|(_, ammo)| ammo.count)
+        .collect()
+}
+
+#[test]
+fn line_of_fire_stops_at_walls_and_whatever_is_in_the_way() {
+    let mut gs = test_state();
+    {
+        let mut map = gs.ecs.write_resource::<Map>();
+        let idx = map.xy_idx(13, 10);
+        map.tiles[idx] = TileType::Wall;
+    }
+    let blocker = mob(&mut gs, 12, 12, 10);
+    gs.ecs.write_storage::<BlocksTile>().insert(blocker, BlocksTile {}).unwrap();
+    MapIndexingSystem{}.run_now(&gs.ecs);
+
+    let map = gs.ecs.fetch::<Map>();
+    let start = Point::new(10, 10);
+    assert!(line_of_fire(&map, start, Point::new(15, 10)) == vec![Point::new(11, 10), Point::new(12, 10)]);
+    assert!(line_of_fire(&map, start, Point::new(14, 14)) == vec![Point::new(11, 11), Point::new(12, 12)]);
+    assert!(line_of_fire(&map, start, Point::new(10, 13)).last() == Some(&Point::new(10, 13)));
+}
+
+#[test]
+fn hitting_gets_harder_with_range() {
+    assert_eq!(gamesystem::range_penalty(1.0), 0);
+    assert_eq!(gamesystem::range_penalty(1.5), 0);
+    assert_eq!(gamesystem::range_penalty(3.0), 1);
+    assert_eq!(gamesystem::range_penalty(7.0), 3);
+}
+
+#[test]
+fn shooting_spends_ammunition_and_leaves_it_where_it_lands() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    equip_named(&mut gs, "Shortbow", player, EquipmentSlot::Ranged);
+    let arrows = equip_named(&mut gs, "Arrows", player, EquipmentSlot::Ammo);
+    let target = mob(&mut gs, 14, 10, 1000);
+    gs.ecs.write_storage::<BlocksTile>().insert(target, BlocksTile {}).unwrap();
+
+    for shot in 1 ..= 12 {
+        assert!(shoot(&mut gs, Point::new(14, 10)) == RunState::PlayerTurn);
+        assert_eq!(ammo_on_floor(&gs, 14, 10).len(), shot);
+    }
+    assert!(ammo_on_floor(&gs, 14, 10).iter().all(|count| *count == 1));
+    assert!(!gs.ecs.is_alive(arrows));
+    assert!(hp(&gs, target) < 1000);
+
+    // with the quiver empty, the bow can't be fired
+    assert!(shoot(&mut gs, Point::new(14, 10)) == RunState::AwaitingInput);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "You have nothing to shoot");
+}
+
+#[test]
+fn picked_up_ammunition_goes_back_in_the_quiver() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    equip_named(&mut gs, "Shortbow", player, EquipmentSlot::Ranged);
+    let arrows = equip_named(&mut gs, "Arrows", player, EquipmentSlot::Ammo);
+    shoot(&mut gs, Point::new(14, 10));
+    assert_eq!(ammo_count(&gs, arrows), 11);
+
+    let spent = {
+        let positions = gs.ecs.read_storage::<Position>();
+        let ammunition = gs.ecs.read_storage::<Ammunition>();
+        (&gs.ecs.entities(), &positions, &ammunition).join().map(|(e, _, _)| e).next().unwrap()
+    };
+    gs.ecs.write_storage::<WantsToPickupItem>().insert(player, WantsToPickupItem { collected_by: player, item: spent }).unwrap();
+    ItemCollectionSystem{}.run_now(&gs.ecs);
+    gs.ecs.maintain();
+
+    assert_eq!(ammo_count(&gs, arrows), 12);
+    assert!(!gs.ecs.is_alive(spent));
+}
+
+#[test]
+fn a_shot_that_hits_a_door_drops_in_front_of_it() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    wall_with_door(&mut gs, 14);
+    equip_named(&mut gs, "Sling", player, EquipmentSlot::Ranged);
+    equip_named(&mut gs, "Sling Stones", player, EquipmentSlot::Ammo);
+
+    assert!(shoot(&mut gs, Point::new(14, 10)) == RunState::PlayerTurn);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "Player shoots, but hits nothing");
+    assert!(ammo_on_floor(&gs, 13, 10) == vec![1]);
+}
+
+#[test]
+fn a_chosen_target_must_be_in_range_and_in_sight() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    wall_with_door(&mut gs, 14);
+    equip_named(&mut gs, "Shortbow", player, EquipmentSlot::Ranged);
+    equip_named(&mut gs, "Arrows", player, EquipmentSlot::Ammo);
+    mob(&mut gs, 10, 18, 10);
+    mob(&mut gs, 16, 10, 10);
+
+    for target in [Point::new(10, 18), Point::new(16, 10)].iter() {
+        assert!(shoot(&mut gs, *target) == RunState::AwaitingInput);
+        assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "You can't shoot that from here");
+        assert!(gs.ecs.read_storage::<WantsToShoot>().get(player).is_none());
+    }
+}
+
+#[test]
+fn firing_needs_a_weapon_ammunition_and_a_target() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let fire = PlayerCommand::Fire { target: None };
+    assert!(player::apply_command(&mut gs.ecs, fire) == RunState::AwaitingInput);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "You have nothing to shoot with");
+
+    // bolts don't fit a bow
+    equip_named(&mut gs, "Shortbow", player, EquipmentSlot::Ranged);
+    let bolts = equip_named(&mut gs, "Crossbow Bolts", player, EquipmentSlot::Ammo);
+    assert!(player::apply_command(&mut gs.ecs, fire) == RunState::AwaitingInput);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "You have nothing to shoot");
+
+    gs.ecs.delete_entity(bolts).unwrap();
+    equip_named(&mut gs, "Arrows", player, EquipmentSlot::Ammo);
+    VisibilitySystem{}.run_now(&gs.ecs);
+    MapIndexingSystem{}.run_now(&gs.ecs);
+    assert!(player::apply_command(&mut gs.ecs, fire) == RunState::AwaitingInput);
+    assert_eq!(gs.ecs.fetch::<GameLog>().entries[0], "There is nothing in range to shoot at");
+
+    // with no target given, it picks the nearest monster it can see
+    mob(&mut gs, 20, 10, 10);
+    mob(&mut gs, 13, 11, 10);
+    MapIndexingSystem{}.run_now(&gs.ecs);
+    assert!(player::apply_command(&mut gs.ecs, fire) == RunState::PlayerTurn);
+    assert_eq!(gs.ecs.read_storage::<WantsToShoot>().get(player).unwrap().target, Point::new(13, 11));
+}
+
+fn xp(gs: &State) -> Experience {
+    gs.ecs.read_storage::<Experience>().get(player_entity(gs)).unwrap().clone()
+}
+
+#[test]
+fn only_the_players_kills_earn_experience() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let mut victims = Vec::new();
+    for (x, from_player) in [(5, true), (6, false)].iter() {
+        let victim = mob(&mut gs, *x, 5, 3);
+        gs.ecs.write_storage::<Experience>().insert(victim, Experience { level: 3, xp: 0 }).unwrap();
+        // hitting the dead again earns nothing more
+        for _ in 0 .. 2 {
+            let creator = if *from_player { Some(player) } else { None };
+            gs.ecs.write_resource::<EffectQueue>().add(creator, EffectType::Damage { amount: 5 }, Targets::Single { target: victim });
+        }
+        victims.push(victim);
+    }
+    let survivor = mob(&mut gs, 7, 5, 10);
+    gs.ecs.write_resource::<EffectQueue>().add(Some(player), EffectType::Damage { amount: 5 }, Targets::Single { target: survivor });
+
+    run_effects_queue(&mut gs.ecs);
+
+    assert_eq!(xp(&gs).xp, gamesystem::xp_for_kill(3));
+    assert!(victims.iter().all(|v| hp(&gs, *v) < 1));
+}
+
+#[test]
+fn levelling_up_raises_the_chosen_attribute_and_hit_points() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    gs.ecs.write_storage::<CombatStats>().get_mut(player).unwrap().hp = 5;
+    gs.ecs.write_storage::<Experience>().get_mut(player).unwrap().xp = gamesystem::xp_to_level_up(1);
+
+    assert!(player::level_up_due(&gs.ecs));
+    let next = player::apply_command(&mut gs.ecs, player::PlayerCommand::LevelUp { attribute: AttributeName::Fitness });
+
+    assert!(next == RunState::AwaitingInput);
+    assert_eq!(xp(&gs).level, 2);
+    let fitness = gs.ecs.read_storage::<Attributes>().get(player).unwrap().fitness.value();
+    assert_eq!(fitness, 12);
+    let max_hp = gamesystem::player_hp_at_level(12, 2);
+    assert!(max_hp > 30);
+    assert_eq!(hp(&gs, player), max_hp);
+
+    // without the experience for another level, nothing more happens
+    let next = player::apply_command(&mut gs.ecs, player::PlayerCommand::LevelUp { attribute: AttributeName::Might });
+    assert!(next == RunState::AwaitingInput);
+    assert_eq!(xp(&gs).level, 2);
+}
+
+#[test]
+fn a_headless_script_can_pick_level_ups() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    // enough for two levels at once
+    game.gs.ecs.write_storage::<Experience>().get_mut(player).unwrap().xp = gamesystem::xp_to_level_up(2);
+
+    let commands : Vec<player::PlayerCommand> = "sMsQs".chars().filter_map(player::PlayerCommand::from_char).collect();
+    assert_eq!(game.run_script(&commands), 5);
+
+    assert_eq!(xp(&game.gs).level, 3);
+    let attributes = game.gs.ecs.read_storage::<Attributes>().get(player).unwrap().clone();
+    assert_eq!((attributes.might.value(), attributes.quickness.value()), (12, 12));
+}
+
+#[test]
+fn the_same_seed_replays_the_same_game() {
+    let script = [
+        PlayerCommand::Move { delta_x: 1, delta_y: 0 },
+        PlayerCommand::Wait,
+        PlayerCommand::Move { delta_x: 0, delta_y: 1 },
+        PlayerCommand::Wait,
+    ];
+
+    let mut first = HeadlessGame::new(42);
+    let mut second = HeadlessGame::new(42);
+    first.run_script(&script);
+    second.run_script(&script);
+
+    let first_map = first.gs.ecs.fetch::<Map>();
+    let second_map = second.gs.ecs.fetch::<Map>();
+    assert!(first_map.tiles == second_map.tiles);
+    assert_eq!(*first.gs.ecs.fetch::<Point>(), *second.gs.ecs.fetch::<Point>());
+}
+
+fn saved_game(seed: u64, format: saveload_system::SaveFormat) -> Vec<u8> {
+    let mut game = HeadlessGame::new(seed);
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, format).unwrap();
+    buffer
+}
+
+fn saved_json(seed: u64) -> String {
+    String::from_utf8(saved_game(seed, saveload_system::SaveFormat::Json)).unwrap()
+}
+
+/// Rewrites the header of a JSON save with `edit`, leaving the components alone
+fn edit_header(save: &str, edit: impl Fn(&mut serde_json::Map<String, serde_json::Value>)) -> String {
+    let mut de = serde_json::Deserializer::from_str(save).into_iter::<serde_json::Value>();
+    let mut header = de.next().unwrap().unwrap();
+    let rest = &save[de.byte_offset()..];
+    edit(header.as_object_mut().unwrap());
+    format!("{}{}", header, rest)
+}
+
+/// Lays a save out the way versions before 10 did: the header, then one
+/// array per storage in a fixed order
+fn positional_save(save: &[u8], version: u32) -> Vec<serde_json::Value> {
+    let mut values : Vec<serde_json::Value> = serde_json::Deserializer::from_slice(save)
+        .into_iter().map(|v| v.unwrap()).collect();
+    let mut storages = values.pop().unwrap();
+    values[0]["version"] = serde_json::Value::from(version);
+    for name in saveload_system::V9_STORAGES.iter() {
+        values.push(storages[*name].take());
+    }
+    values
+}
+
+#[test]
+fn a_saved_game_loads_back() {
+    let save = saved_json(7);
+    let mut game = HeadlessGame::new(8);
+
+    let metadata = saveload_system::load_from_bytes(&mut game.gs.ecs, save.as_bytes()).unwrap();
+    assert_eq!(metadata.version, saveload_system::SAVE_VERSION);
+    assert_eq!(game.gs.ecs.fetch::<game_seed::GameSeed>().seed, 7);
+    let player = player_entity(&game.gs);
+    assert!(game.gs.ecs.read_storage::<Player>().get(player).is_some());
+}
+
+#[test]
+fn a_loaded_game_plays_on_exactly_as_the_saved_one() {
+    let script = [
+        PlayerCommand::Move { delta_x: 1, delta_y: 0 },
+        PlayerCommand::Wait,
+        PlayerCommand::Move { delta_x: 0, delta_y: 1 },
+        PlayerCommand::Wait,
+        PlayerCommand::Wait,
+    ];
+    let mut original = HeadlessGame::new(42);
+    original.run_script(&script[..2]);
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut original.gs.ecs, &mut buffer, saveload_system::SaveFormat::Json).unwrap();
+
+    let mut loaded = HeadlessGame::new(7);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, &buffer).unwrap();
+    original.run_script(&script[2..]);
+    loaded.run_script(&script[2..]);
+
+    assert_eq!(*original.gs.ecs.fetch::<Point>(), *loaded.gs.ecs.fetch::<Point>());
+    assert_eq!(hp(&original.gs, player_entity(&original.gs)), hp(&loaded.gs, player_entity(&loaded.gs)));
+    let next_roll = |game: &HeadlessGame| game.gs.ecs.write_resource::<RandomNumberGenerator>().next_u64();
+    assert_eq!(next_roll(&original), next_roll(&loaded));
+}
+
+#[test]
+fn a_failed_autosave_is_logged_and_the_game_goes_on() {
+    let mut game = HeadlessGame::new(7);
+    game.gs.ecs.write_resource::<saveload_system::SaveMode>().autosave = true;
+    // a directory where the save is written makes creating the file fail
+    std::fs::create_dir_all("./autosave.json.tmp").unwrap();
+    saveload_system::autosave(&mut game.gs.ecs);
+    std::fs::remove_dir("./autosave.json.tmp").unwrap();
+
+    assert!(game.gs.ecs.fetch::<GameLog>().entries[0].starts_with("Unable to autosave"));
+    assert!(!std::path::Path::new("./autosave.json").exists());
+}
+
+#[test]
+fn saving_does_not_change_how_the_game_plays_out() {
+    let script = [
+        PlayerCommand::Move { delta_x: 1, delta_y: 0 },
+        PlayerCommand::Wait,
+        PlayerCommand::Move { delta_x: 0, delta_y: 1 },
+        PlayerCommand::Wait,
+    ];
+    let mut unsaved = HeadlessGame::new(42);
+    unsaved.run_script(&script);
+
+    let mut saved = HeadlessGame::new(42);
+    for command in script.iter() {
+        saveload_system::write_save(&mut saved.gs.ecs, &mut Vec::new(), saveload_system::SaveFormat::Binary).unwrap();
+        saved.run_command(*command);
+    }
+
+    assert_eq!(*unsaved.gs.ecs.fetch::<Point>(), *saved.gs.ecs.fetch::<Point>());
+    let next_roll = |game: &HeadlessGame| game.gs.ecs.write_resource::<RandomNumberGenerator>().next_u64();
+    assert_eq!(next_roll(&unsaved), next_roll(&saved));
+}
+
+#[test]
+fn a_binary_save_loads_back_and_is_smaller() {
+    let binary = saved_game(7, saveload_system::SaveFormat::Binary);
+    assert!(binary.len() < saved_json(7).len());
+
+    let original = HeadlessGame::new(7);
+    let mut game = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut game.gs.ecs, &binary).unwrap();
+    assert!(game.gs.ecs.fetch::<Map>().tiles == original.gs.ecs.fetch::<Map>().tiles);
+    assert_eq!(*game.gs.ecs.fetch::<Point>(), *original.gs.ecs.fetch::<Point>());
+}
+
+#[test]
+fn compressed_maps_are_saved_as_bytes_not_arrays_of_numbers() {
+    let values : Vec<serde_json::Value> = serde_json::Deserializer::from_slice(saved_json(7).as_bytes())
+        .into_iter().map(|v| v.unwrap()).collect();
+    let helper = values[1]["SerializationHelper"].as_array().unwrap().iter()
+        .find_map(|entry| entry["components"][0].as_object()).unwrap();
+    let map = saveload_system::base64_decode(helper["map"].as_str().unwrap()).unwrap();
+
+    // the binary save holds the very same bytes, as a byte string
+    let binary = saved_game(7, saveload_system::SaveFormat::Binary);
+    assert!(binary.windows(map.len()).any(|window| window == &map[..]));
+}
+
+#[test]
+fn the_menu_reads_metadata_from_the_start_of_a_save() {
+    for format in [saveload_system::SaveFormat::Json, saveload_system::SaveFormat::Binary].iter() {
+        let save = saved_game(7, *format);
+        assert!(save.len() > saveload_system::HEADER_BYTES);
+        let metadata = saveload_system::metadata_from_header(&save[.. saveload_system::HEADER_BYTES]).unwrap();
+        assert_eq!(metadata.version, saveload_system::SAVE_VERSION);
+        assert_eq!(metadata.depth, 1);
+    }
+}
+
+#[test]
+fn a_damaged_save_is_an_error() {
+    let mut game = HeadlessGame::new(7);
+    let result = saveload_system::load_from_bytes(&mut game.gs.ecs, b"{ \"name\": ");
+    assert!(matches!(result, Err(saveload_system::LoadError::Parse(_))));
+
+    for format in [saveload_system::SaveFormat::Json, saveload_system::SaveFormat::Binary].iter() {
+        let save = saved_game(7, *format);
+        let truncated = &save[..save.len() / 2];
+        assert!(saveload_system::load_from_bytes(&mut game.gs.ecs, truncated).is_err());
+    }
+}
+
+#[test]
+fn storages_are_found_by_name() {
+    // a storage the save doesn't have is empty, and one the game no longer
+    // knows about is ignored
+    let mut values : Vec<serde_json::Value> = serde_json::Deserializer::from_str(&saved_json(7))
+        .into_iter().map(|v| v.unwrap()).collect();
+    let storages = values[1].as_object_mut().unwrap();
+    storages.remove("Renderable");
+    storages.insert("Retired".to_string(), serde_json::json!([]));
+    let save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut game = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut game.gs.ecs, save.join("\n").as_bytes()).unwrap();
+    assert!(game.gs.ecs.read_storage::<Player>().get(player_entity(&game.gs)).is_some());
+    assert!(game.gs.ecs.read_storage::<Renderable>().is_empty());
+}
+
+#[test]
+fn a_save_that_fails_to_load_leaves_the_game_as_it_was() {
+    let mut values : Vec<serde_json::Value> = serde_json::Deserializer::from_str(&saved_json(7))
+        .into_iter().map(|v| v.unwrap()).collect();
+    values[1]["Player"] = serde_json::json!([]);
+    let save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut game = HeadlessGame::new(8);
+    let hp_before = hp(&game.gs, player_entity(&game.gs));
+    let tiles_before = game.gs.ecs.fetch::<Map>().tiles.clone();
+    let result = saveload_system::load_from_bytes(&mut game.gs.ecs, save.join("\n").as_bytes());
+
+    assert!(matches!(result, Err(saveload_system::LoadError::Missing("player"))));
+    let player = player_entity(&game.gs);
+    assert!(game.gs.ecs.read_storage::<Player>().get(player).is_some());
+    assert_eq!(hp(&game.gs, player), hp_before);
+    assert!(game.gs.ecs.fetch::<Map>().tiles == tiles_before);
+    assert!(game.run_command(PlayerCommand::Wait) == RunState::AwaitingInput);
+}
+
+#[test]
+fn a_save_from_a_newer_version_is_refused() {
+    let save = edit_header(&saved_json(7), |header| {
+        header.insert("version".to_string(), serde_json::Value::from(saveload_system::SAVE_VERSION + 1));
+    });
+    let mut game = HeadlessGame::new(7);
+    let result = saveload_system::load_from_bytes(&mut game.gs.ecs, save.as_bytes());
+    assert!(matches!(result, Err(saveload_system::LoadError::NewerVersion(_))));
+}
+
+#[test]
+fn an_unversioned_save_is_migrated() {
+    // the first saves had no version, and the 30 storages before `OtherLevelPosition`
+    let mut values = positional_save(saved_json(7).as_bytes(), 0);
+    values[0].as_object_mut().unwrap().remove("version");
+    values.truncate(31);
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    let mut game = HeadlessGame::new(8);
+    let metadata = saveload_system::load_from_bytes(&mut game.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    assert_eq!(metadata.version, saveload_system::SAVE_VERSION);
+    assert_eq!(game.gs.ecs.fetch::<game_seed::GameSeed>().seed, 7);
+}
+
+#[test]
+fn a_version_one_save_has_its_map_compressed() {
+    let original = HeadlessGame::new(7);
+    let map = serde_json::to_value(&*original.gs.ecs.fetch::<Map>()).unwrap();
+
+    // version 1 stored the helper's map as plain JSON
+    let save = saved_json(7);
+    let mut values = positional_save(save.as_bytes(), 1);
+    let mut replaced = 0;
+    for storage in values.iter_mut().skip(1) {
+        for entry in storage.as_array_mut().unwrap().iter_mut() {
+            if let Some(helper_map) = entry.pointer_mut("/components/0/map") {
+                *helper_map = map.clone();
+                replaced += 1;
+            }
+        }
+    }
+    assert_eq!(replaced, 1);
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut game = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut game.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    assert!(game.gs.ecs.fetch::<Map>().tiles == original.gs.ecs.fetch::<Map>().tiles);
+}
+
+#[test]
+fn a_version_four_save_gets_attributes_and_armour() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    for (name, slot) in [("Longsword", EquipmentSlot::Melee), ("Tower Shield", EquipmentSlot::Shield)].iter() {
+        let item = raws::spawn_named_entity(&raws::RAWS.lock().unwrap(), &mut game.gs.ecs, name, raws::SpawnType::AtPosition { x: 1, y: 1 }).unwrap();
+        game.gs.ecs.write_storage::<Position>().remove(item);
+        game.gs.ecs.write_storage::<InBackpack>().insert(item, InBackpack { owner: player }).unwrap();
+        equip(&mut game.gs, item, player, *slot);
+    }
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, saveload_system::SaveFormat::Json).unwrap();
+
+    // version 4 had flat power and defense, and ended with the door
+    // storages after the header and 33 others
+    let mut values = positional_save(&buffer, 4);
+    values.truncate(34);
+    let old_components = [(8, serde_json::json!({ "power": 4, "defense": 1 })), (25, serde_json::json!({ "power": 4 })), (26, serde_json::json!({ "defense": 3 }))];
+    for (storage, old) in old_components.iter() {
+        for entry in values[*storage].as_array_mut().unwrap().iter_mut() {
+            let component = &mut entry["components"][0];
+            if component.is_null() { continue; }
+            if *storage == 8 {
+                let hp = component["hp"].clone();
+                *component = old.clone();
+                component["hp"] = hp.clone();
+                component["max_hp"] = hp;
+            } else {
+                *component = old.clone();
+            }
+        }
+    }
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    let ecs = &loaded.gs.ecs;
+    let player = player_entity(&loaded.gs);
+    assert!(ecs.read_storage::<Attributes>().get(player).is_some());
+    assert!(ecs.read_storage::<Skills>().get(player).is_some());
+    assert!(ecs.read_storage::<NaturalAttackDefense>().get(player).is_none());
+    assert_eq!(ecs.read_storage::<Experience>().get(player).unwrap().level, 1);
+
+    let monsters = ecs.read_storage::<Monster>();
+    let natural = ecs.read_storage::<NaturalAttackDefense>();
+    assert!(monsters.join().count() > 0);
+    for (nature, _monster) in (&natural, &monsters).join() {
+        assert_eq!(nature.armor_class, Some(11));
+        assert_eq!(nature.attacks[0].damage_die_type, 7);
+    }
+    assert_eq!(natural.join().count(), monsters.join().count());
+
+    let names = ecs.read_storage::<Name>();
+    let equipped = ecs.read_storage::<Equipped>();
+    assert_eq!((&ecs.read_storage::<MeleeWeapon>(), &equipped).join().count(), 1);
+    assert_eq!((&ecs.read_storage::<Wearable>(), &equipped).join().count(), 1);
+    for (name, weapon, _equipped) in (&names, &ecs.read_storage::<MeleeWeapon>(), &equipped).join() {
+        assert_eq!(name.name, "Longsword");
+        assert_eq!(weapon.damage_bonus, 3);
+    }
+    for (name, armour, _equipped) in (&names, &ecs.read_storage::<Wearable>(), &equipped).join() {
+        assert_eq!(name.name, "Tower Shield");
+        assert_eq!(armour.armor_class, 3);
+    }
+}
+
+#[test]
+fn a_version_six_save_turns_confusion_into_a_status() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    let monster = mob(&mut game.gs, 12, 10, 10);
+    let scroll = backpack_item(&mut game.gs, "Confusion Scroll");
+    for entity in [monster, scroll].iter() {
+        game.gs.ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>().mark(*entity, &mut game.gs.ecs.write_storage::<SimpleMarker<SerializeMe>>());
+    }
+    let monster_marker = game.gs.ecs.read_storage::<SimpleMarker<SerializeMe>>().get(monster).unwrap().id();
+    let scroll_marker = game.gs.ecs.read_storage::<SimpleMarker<SerializeMe>>().get(scroll).unwrap().id();
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, saveload_system::SaveFormat::Json).unwrap();
+
+    // version 6 kept confusion as a plain component on its target
+    let mut values = positional_save(&buffer, 6);
+    values.truncate(38);
+    for entry in values[15].as_array_mut().unwrap().iter_mut() {
+        let marker = entry["marker"][0].as_u64();
+        if marker == Some(monster_marker) || marker == Some(scroll_marker) {
+            entry["components"][0] = serde_json::json!({ "turns": 3 });
+        }
+    }
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    let ecs = &loaded.gs.ecs;
+    let names = ecs.read_storage::<Name>();
+    let statuses = ecs.read_storage::<StatusEffect>();
+    assert_eq!(statuses.join().count(), 1);
+    for status in statuses.join() {
+        assert_eq!(status.kind, StatusKind::Confusion);
+        assert_eq!(status.turns, 3);
+        assert!(ecs.read_storage::<Monster>().get(status.target).is_some());
+        assert!(status.target != player);
+    }
+    let inflicts = ecs.read_storage::<InflictsStatus>();
+    assert!((&names, &inflicts).join()
+        .any(|(name, i)| name.name == "Confusion Scroll" && i.kind == StatusKind::Confusion && i.turns == 3));
+}
+
+#[test]
+fn a_version_seven_save_lands_pending_damage() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    let player_marker = game.gs.ecs.read_storage::<SimpleMarker<SerializeMe>>().get(player).unwrap().id();
+    let hp_before = hp(&game.gs, player);
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, saveload_system::SaveFormat::Json).unwrap();
+
+    // version 7 kept damage waiting to land where scrolls of teleportation now go
+    let mut values = positional_save(&buffer, 7);
+    values.truncate(39);
+    for entry in values[9].as_array_mut().unwrap().iter_mut() {
+        if entry["marker"][0].as_u64() == Some(player_marker) {
+            entry["components"][0] = serde_json::json!({ "amount": 5, "from_player": false });
+        }
+    }
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    let player = player_entity(&loaded.gs);
+    assert_eq!(hp(&loaded.gs, player), hp_before - 5);
+    assert!(loaded.gs.ecs.read_storage::<TeleportsUser>().is_empty());
+}
+
+#[test]
+fn a_version_eight_save_teaches_the_player_to_shoot() {
+    let mut game = HeadlessGame::new(7);
+    let player = player_entity(&game.gs);
+    let player_marker = game.gs.ecs.read_storage::<SimpleMarker<SerializeMe>>().get(player).unwrap().id();
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, saveload_system::SaveFormat::Json).unwrap();
+
+    let mut values = positional_save(&buffer, 8);
+    values.truncate(39);
+    for entry in values[35].as_array_mut().unwrap().iter_mut() {
+        if entry["marker"][0].as_u64() == Some(player_marker) {
+            entry["components"][0]["skills"].as_object_mut().unwrap().remove("Ranged");
+        }
+    }
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    let player = player_entity(&loaded.gs);
+    let skills = loaded.gs.ecs.read_storage::<Skills>().get(player).unwrap().clone();
+    assert_eq!(gamesystem::skill_bonus(Skill::Ranged, &skills), 1);
+}
+
+#[test]
+fn a_version_eleven_save_rolls_on_from_its_generator_seed() {
+    let mut values : Vec<serde_json::Value> = serde_json::Deserializer::from_slice(saved_json(7).as_bytes())
+        .into_iter().map(|v| v.unwrap()).collect();
+    values[0]["version"] = serde_json::Value::from(11);
+    let helper = values[1]["SerializationHelper"].as_array_mut().unwrap().iter_mut()
+        .find_map(|entry| entry["components"][0].as_object_mut()).unwrap();
+    helper.remove("rng");
+    helper.insert("rng_seed".to_string(), serde_json::Value::from(5));
+    let old_save : Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, old_save.join("\n").as_bytes()).unwrap();
+    let next_roll = loaded.gs.ecs.write_resource::<RandomNumberGenerator>().next_u64();
+    assert_eq!(next_roll, RandomNumberGenerator::seeded(5).next_u64());
+}
+
+/// Moves the player onto the first tile of the given type
+fn stand_on(game: &mut HeadlessGame, tile: TileType) {
+    let (x, y) = dungeon::find_tile(&game.gs.ecs.fetch::<Map>(), tile).unwrap();
+    let player = player_entity(&game.gs);
+    game.gs.ecs.write_storage::<Position>().insert(player, Position { x, y }).unwrap();
+    *game.gs.ecs.write_resource::<Point>() = Point::new(x, y);
+}
+
+fn positioned_entities(gs: &State) -> usize {
+    let positions = gs.ecs.read_storage::<Position>();
+    let players = gs.ecs.read_storage::<Player>();
+    (&positions, !&players).join().count()
+}
+
+#[test]
+fn a_level_is_restored_when_the_player_returns() {
+    let mut game = HeadlessGame::new(7);
+    let tiles = game.gs.ecs.fetch::<Map>().tiles.clone();
+    let entities = positioned_entities(&game.gs);
+
+    stand_on(&mut game, TileType::DownStairs);
+    game.run_command(PlayerCommand::Descend);
+    assert_eq!(game.gs.ecs.fetch::<Map>().depth, 2);
+    let player_pos = *game.gs.ecs.fetch::<Point>();
+    {
+        let map = game.gs.ecs.fetch::<Map>();
+        assert!(map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::UpStairs);
+    }
+
+    game.run_command(PlayerCommand::Ascend);
+    assert_eq!(game.gs.ecs.fetch::<Map>().depth, 1);
+    assert!(game.gs.ecs.fetch::<Map>().tiles == tiles);
+    assert_eq!(positioned_entities(&game.gs), entities);
+    let player_pos = *game.gs.ecs.fetch::<Point>();
+    let map = game.gs.ecs.fetch::<Map>();
+    assert!(map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::DownStairs);
+}
+
+#[test]
+fn visited_levels_are_saved() {
+    let mut game = HeadlessGame::new(7);
+    let tiles = game.gs.ecs.fetch::<Map>().tiles.clone();
+    let entities = positioned_entities(&game.gs);
+    stand_on(&mut game, TileType::DownStairs);
+    game.run_command(PlayerCommand::Descend);
+
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, saveload_system::SaveFormat::Binary).unwrap();
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, &buffer).unwrap();
+    loaded.gs.ecs.insert(RunState::AwaitingInput);
+
+    loaded.run_command(PlayerCommand::Ascend);
+    assert_eq!(loaded.gs.ecs.fetch::<Map>().depth, 1);
+    assert!(loaded.gs.ecs.fetch::<Map>().tiles == tiles);
+    assert_eq!(positioned_entities(&loaded.gs), entities);
+}
+
+#[test]
+fn the_camera_follows_the_player() {
+    let gs = test_state();
+    assert_eq!(camera::map_to_screen(&gs.ecs, Point::new(10, 10)), Point::new(40, 21));
+
+    *gs.ecs.write_resource::<Point>() = Point::new(70, 45);
+    let screen = camera::map_to_screen(&gs.ecs, Point::new(72, 44));
+    assert_eq!(screen, Point::new(42, 20));
+    assert_eq!(camera::screen_to_map(&gs.ecs, (screen.x, screen.y)), Point::new(72, 44));
+}
+
+/// Walls off the column at `x` apart from a closed door at (x, 10)
+fn wall_with_door(gs: &mut State, x: i32) -> Entity {
+    raws::load_raws();
+    {
+        let mut map = gs.ecs.write_resource::<Map>();
+        for y in 1 .. map.height - 1 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = if y == 10 { TileType::Floor } else { TileType::Wall };
+        }
+    }
+    raws::spawn_named_entity(&raws::RAWS.lock().unwrap(), &mut gs.ecs, "Door", raws::SpawnType::AtPosition { x, y: 10 }).unwrap()
+}
+
+fn door_is_open(gs: &State, door: Entity) -> bool {
+    gs.ecs.read_storage::<Door>().get(door).unwrap().open
+}
+
+fn position_of(gs: &State, entity: Entity) -> (i32, i32) {
+    let pos = gs.ecs.read_storage::<Position>().get(entity).unwrap().clone();
+    (pos.x, pos.y)
+}
+
+#[test]
+fn a_slowed_player_gives_the_monsters_a_second_move_and_nothing_else() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let monster = mob(&mut gs, 15, 10, 10);
+    gs.ecs.write_storage::<Viewshed>().insert(monster, Viewshed { tiles: Vec::new(), range: 8, dirty: true }).unwrap();
+    gs.ecs.write_storage::<HungerClock>().insert(monster, HungerClock { state: HungerState::Normal, duration: 100 }).unwrap();
+    add_status(&mut gs, player, StatusKind::Slow, 5, 0);
+    gs.ecs.write_resource::<TurnCounter>().turns = 2;
+    gs.ecs.insert(RunState::MonsterTurn);
+
+    gs.advance(RunState::MonsterTurn);
+    assert_eq!(position_of(&gs, monster), (13, 10));
+    assert_eq!(gs.ecs.read_storage::<HungerClock>().get(monster).unwrap().duration, 99);
+}
+
+#[test]
+fn a_hasted_monster_attacks_twice_each_turn() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    let monster = mob(&mut gs, 11, 10, 10);
+    gs.ecs.write_storage::<Viewshed>().insert(monster, Viewshed { tiles: Vec::new(), range: 8, dirty: true }).unwrap();
+    add_status(&mut gs, monster, StatusKind::Haste, 5, 0);
+    gs.ecs.write_storage::<CombatStats>().get_mut(player).unwrap().hp = 1000;
+    gs.ecs.insert(RunState::MonsterTurn);
+
+    gs.advance(RunState::MonsterTurn);
+    let log = gs.ecs.fetch::<GameLog>();
+    assert_eq!(log.entries.iter().filter(|entry| entry.starts_with("Test Mob")).count(), 2);
+}
+
+#[test]
+fn a_closed_door_blocks_sight_and_movement_until_bumped() {
+    let mut gs = test_state();
+    let player = player_entity(&gs);
+    gs.ecs.write_storage::<Position>().insert(player, Position { x: 11, y: 10 }).unwrap();
+    *gs.ecs.write_resource::<Point>() = Point::new(11, 10);
+    let door = wall_with_door(&mut gs, 12);
+    gs.run_systems();
+
+    let sees_past_the_door = |gs: &State| {
+        gs.ecs.read_storage::<Viewshed>().get(player).unwrap().tiles.contains(&Point::new(14, 10))
+    };
+    assert!(!sees_past_the_door(&gs));
+
+    player::try_move_player(1, 0, &mut gs.ecs);
+    assert!(door_is_open(&gs, door));
+    assert_eq!(position_of(&gs, player), (11, 10));
+    gs.run_systems();
+    assert!(sees_past_the_door(&gs));
+
+    player::try_move_player(1, 0, &mut gs.ecs);
+    assert_eq!(position_of(&gs, player), (12, 10));
+}
+
+#[test]
+fn monsters_open_doors_to_get_at_the_player() {
+    let mut gs = test_state();
+    let door = wall_with_door(&mut gs, 12);
+    let orc = mob(&mut gs, 14, 10, 10);
+    gs.ecs.write_storage::<Viewshed>().insert(orc, Viewshed { tiles: vec![Point::new(10, 10)], range: 8, dirty: false }).unwrap();
+    MapIndexingSystem{}.run_now(&gs.ecs);
+    gs.ecs.insert(RunState::MonsterTurn);
+
+    MonsterAI{ hasted_only: false }.run_now(&gs.ecs);
+    assert_eq!(position_of(&gs, orc), (13, 10));
+    MonsterAI{ hasted_only: false }.run_now(&gs.ecs);
+    assert!(door_is_open(&gs, door));
+    assert_eq!(position_of(&gs, orc), (13, 10));
+    MonsterAI{ hasted_only: false }.run_now(&gs.ecs);
+    assert_eq!(position_of(&gs, orc), (12, 10));
+}
+
+fn doors(gs: &State) -> Vec<(i32, i32, bool, bool)> {
+    let doors = gs.ecs.read_storage::<Door>();
+    let positions = gs.ecs.read_storage::<Position>();
+    let blocks_visibility = gs.ecs.read_storage::<BlocksVisibility>();
+    let mut found : Vec<(i32, i32, bool, bool)> = (&gs.ecs.entities(), &doors, &positions).join()
+        .map(|(entity, door, pos)| (pos.x, pos.y, door.open, blocks_visibility.get(entity).is_some()))
+        .collect();
+    found.sort();
+    found
+}
+
+#[test]
+fn doors_stay_open_or_shut_through_a_save() {
+    let mut game = HeadlessGame::new(7);
+    let shut = wall_with_door(&mut game.gs, 40);
+    let opened = raws::spawn_named_entity(&raws::RAWS.lock().unwrap(), &mut game.gs.ecs, "Door", raws::SpawnType::AtPosition { x: 41, y: 10 }).unwrap();
+    game.gs.ecs.write_storage::<Door>().get_mut(opened).unwrap().open = true;
+    game.gs.ecs.write_storage::<BlocksVisibility>().remove(opened);
+    let before = doors(&game.gs);
+    assert!(before.contains(&(40, 10, false, true)) && before.contains(&(41, 10, true, false)));
+    assert!(!door_is_open(&game.gs, shut));
+
+    let mut buffer = Vec::new();
+    saveload_system::write_save(&mut game.gs.ecs, &mut buffer, saveload_system::SaveFormat::Binary).unwrap();
+    let mut loaded = HeadlessGame::new(8);
+    saveload_system::load_from_bytes(&mut loaded.gs.ecs, &buffer).unwrap();
+    assert_eq!(doors(&loaded.gs), before);
+}
+
+/// Checks that every walkable tile can be reached from the start, moving
+/// diagonally as the player can
+fn assert_fully_connected(map: &Map, start: &Position) {
+    let mut seen = vec![false; map.tiles.len()];
+    let mut open = vec![map.xy_idx(start.x, start.y)];
+    while let Some(idx) = open.pop() {
+        if seen[idx] || !tile_walkable(map.tiles[idx]) { continue; }
+        seen[idx] = true;
+        let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)].iter() {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx >= 0 && nx < map.width && ny >= 0 && ny < map.height {
+                open.push(map.xy_idx(nx, ny));
+            }
+        }
+    }
+
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        assert!(!tile_walkable(*tile) || seen[idx], "tile {} is unreachable", idx);
+    }
+}
+
+fn build_with(mut builder: map_builders::BuilderChain, seed: u64) -> (Map, Position) {
+    let mut rng = RandomNumberGenerator::seeded(seed);
+    builder.build_map(&mut rng);
+    (builder.build_data.map, builder.build_data.starting_position.unwrap())
+}
+
+/// A chain for builders that make rooms: start in the first, leave from
+/// the last
+fn room_chain(starter: Box<dyn map_builders::InitialMapBuilder>, metas: Vec<Box<dyn map_builders::MetaMapBuilder>>) -> map_builders::BuilderChain {
+    let mut chain = map_builders::BuilderChain::new(2, 80, 50);
+    chain.start_with(starter);
+    for meta in metas { chain.with(meta); }
+    chain.with(map_builders::RoomBasedStartingPosition::new());
+    chain.with(map_builders::RoomBasedStairs::new());
+    chain
+}
+
+/// A chain for builders that carve one open shape: start in the middle,
+/// cull what can't be reached, leave from the far end
+fn area_chain(starter: Box<dyn map_builders::InitialMapBuilder>, metas: Vec<Box<dyn map_builders::MetaMapBuilder>>) -> map_builders::BuilderChain {
+    let mut chain = map_builders::BuilderChain::new(2, 80, 50);
+    chain.start_with(starter);
+    for meta in metas { chain.with(meta); }
+    chain.with(map_builders::AreaStartingPosition::new(map_builders::XStart::Center, map_builders::YStart::Center));
+    chain.with(map_builders::CullUnreachable::new());
+    chain.with(map_builders::DistantExit::new());
+    chain
+}
+
+#[test]
+fn bsp_levels_are_connected_and_have_stairs() {
+    for seed in 0 .. 20 {
+        let (map, start) = build_with(room_chain(map_builders::BspDungeonBuilder::new(), Vec::new()), seed);
+        assert_fully_connected(&map, &start);
+        assert!(dungeon::find_tile(&map, TileType::DownStairs).is_some());
+        assert!(map.tiles[map.xy_idx(start.x, start.y)] == TileType::UpStairs);
+    }
+}
+
+#[test]
+fn every_corridor_style_connects_the_rooms() {
+    let corridors : Vec<fn() -> Box<dyn map_builders::MetaMapBuilder>> = vec![
+        || map_builders::DoglegCorridors::new(),
+        || map_builders::BspCorridors::new(),
+        || map_builders::NearestCorridors::new(),
+        || map_builders::PrimCorridors::new(),
+    ];
+
+    for corridor in corridors.iter() {
+        for seed in 0 .. 5 {
+            let metas : Vec<Box<dyn map_builders::MetaMapBuilder>> = vec![map_builders::RoomCornerRounder::new(), corridor()];
+            let (map, start) = build_with(room_chain(map_builders::SimpleMapBuilder::new(), metas), seed);
+            assert_fully_connected(&map, &start);
+            assert!(dungeon::find_tile(&map, TileType::DownStairs).is_some());
+        }
+    }
+}
+
+#[test]
+fn caves_are_connected_with_stairs_at_the_far_end() {
+    for seed in 0 .. 10 {
+        let (map, start) = build_with(area_chain(map_builders::CellularAutomataBuilder::new(), Vec::new()), seed);
+        assert_fully_connected(&map, &start);
+        assert!(map.tiles[map.xy_idx(start.x, start.y)] == TileType::UpStairs);
+
+        let (x, y) = dungeon::find_tile(&map, TileType::DownStairs).unwrap();
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(x, y), Point::new(start.x, start.y));
+        assert!(distance > 10.0);
+    }
+}
+
+#[test]
+fn walker_levels_are_connected_and_have_stairs() {
+    let builders : Vec<fn() -> Box<dyn map_builders::InitialMapBuilder>> = vec![
+        || map_builders::DrunkardsWalkBuilder::open_area(),
+        || map_builders::DrunkardsWalkBuilder::open_halls(),
+        || map_builders::DrunkardsWalkBuilder::winding_passages(),
+        || map_builders::DrunkardsWalkBuilder::fearful_symmetry(),
+        || map_builders::DLABuilder::walk_inwards(),
+        || map_builders::DLABuilder::walk_outwards(),
+        || map_builders::DLABuilder::central_attractor(),
+        || map_builders::DLABuilder::insectoid(),
+    ];
+
+    for builder in builders.iter() {
+        for seed in 0 .. 3 {
+            let (map, start) = build_with(area_chain(builder(), Vec::new()), seed);
+            assert_fully_connected(&map, &start);
+            assert!(dungeon::find_tile(&map, TileType::DownStairs).is_some());
+            assert!(map.tiles[map.xy_idx(start.x, start.y)] == TileType::UpStairs);
+        }
+    }
+}
+
+#[test]
+fn wave_function_collapse_levels_are_connected_and_have_stairs() {
+    for seed in 0 .. 3 {
+        let chain = area_chain(map_builders::BspDungeonBuilder::new(), vec![map_builders::WaveformCollapseBuilder::new()]);
+        let (map, start) = build_with(chain, seed);
+        assert_fully_connected(&map, &start);
+        assert!(dungeon::find_tile(&map, TileType::DownStairs).is_some());
+        assert!(map.tiles[map.xy_idx(start.x, start.y)] == TileType::UpStairs);
+        for x in 0 .. map.width {
+            assert!(map.tiles[map.xy_idx(x, 0)] == TileType::Wall);
+            assert!(map.tiles[map.xy_idx(x, map.height - 1)] == TileType::Wall);
+        }
+    }
+}
+
+/// An area chain at the given depth, dressed in the theme's terrain
+fn themed_chain(depth: i32, width: i32, height: i32, starter: Box<dyn map_builders::InitialMapBuilder>, metas: Vec<Box<dyn map_builders::MetaMapBuilder>>) -> map_builders::BuilderChain {
+    let mut chain = map_builders::BuilderChain::new(depth, width, height);
+    chain.start_with(starter);
+    chain.with(map_builders::TerrainFeatures::new());
+    for meta in metas { chain.with(meta); }
+    chain.with(map_builders::AreaStartingPosition::new(map_builders::XStart::Center, map_builders::YStart::Center));
+    chain.with(map_builders::CullUnreachable::new());
+    chain.with(map_builders::DistantExit::new());
+    chain
+}
+
+fn count_tiles(map: &Map, tile: TileType) -> usize {
+    map.tiles.iter().filter(|t| **t == tile).count()
+}
+
+#[test]
+fn cave_levels_get_terrain_to_suit_their_theme() {
+    for seed in 0 .. 3 {
+        let (forest, start) = build_with(themed_chain(3, 80, 50, map_builders::CellularAutomataBuilder::new(), Vec::new()), seed);
+        assert_fully_connected(&forest, &start);
+        assert!(count_tiles(&forest, TileType::Grass) > 0);
+
+        let (cavern, start) = build_with(themed_chain(5, 80, 50, map_builders::CellularAutomataBuilder::new(), Vec::new()), seed);
+        assert_fully_connected(&cavern, &start);
+        assert!(count_tiles(&cavern, TileType::ShallowWater) > 0);
+
+        let (dungeon, _start) = build_with(themed_chain(1, 80, 50, map_builders::CellularAutomataBuilder::new(), Vec::new()), seed);
+        assert_eq!(count_tiles(&dungeon, TileType::Grass) + count_tiles(&dungeon, TileType::ShallowWater), 0);
+    }
+}
+
+#[test]
+fn wave_function_collapse_keeps_the_terrain() {
+    let chain = themed_chain(5, 50, 30, Box::new(OpenMap {}), vec![map_builders::WaveformCollapseBuilder::new()]);
+    let (map, start) = build_with(chain, 0);
+    assert_fully_connected(&map, &start);
+    assert!(count_tiles(&map, TileType::ShallowWater) > 0);
+    assert!(count_tiles(&map, TileType::DeepWater) > 0);
+    assert!(count_tiles(&map, TileType::Gravel) > 0);
+}
+
+#[test]
+fn random_levels_spawn_off_the_starting_tile() {
+    for seed in 0 .. 10 {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let mut builder = map_builders::random_builder(3, &mut rng, 80, 50);
+        builder.build_map(&mut rng);
+        let start = builder.build_data.starting_position.clone().unwrap();
+        let map = &builder.build_data.map;
+        assert_fully_connected(map, &start);
+        assert!(dungeon::find_tile(map, TileType::DownStairs).is_some());
+        for (idx, _name) in builder.build_data.spawn_list.iter() {
+            assert!(*idx != map.xy_idx(start.x, start.y));
+            assert!(map.tiles[*idx] == TileType::Floor);
+        }
+    }
+}
+
+#[test]
+fn random_levels_keep_doors_clear_of_everything_else() {
+    let near = |map: &Map, a: usize, b: usize| {
+        (a as i32 % map.width - b as i32 % map.width).abs() <= 1 && (a as i32 / map.width - b as i32 / map.width).abs() <= 1
+    };
+    let mut doors = 0;
+    for seed in 0 .. 40 {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let mut builder = map_builders::random_builder(3, &mut rng, 80, 50);
+        builder.build_map(&mut rng);
+        let start = builder.build_data.starting_position.clone().unwrap();
+        let map = &builder.build_data.map;
+        let spawns = &builder.build_data.spawn_list;
+        for (door, _) in spawns.iter().filter(|(_, name)| name == "Door") {
+            doors += 1;
+            assert!(!near(map, *door, map.xy_idx(start.x, start.y)));
+            for (other, _) in spawns.iter().filter(|(_, name)| name != "Door") {
+                assert!(!near(map, *door, *other));
+            }
+        }
+    }
+    assert!(doors > 0);
+}
+
+#[test]
+fn levels_can_be_built_at_any_size() {
+    for (width, height) in [(64, 40), (120, 70)].iter() {
+        for seed in 0 .. 20 {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let mut builder = map_builders::random_builder(3, &mut rng, *width, *height);
+            builder.build_map(&mut rng);
+            let start = builder.build_data.starting_position.clone().unwrap();
+            let map = &builder.build_data.map;
+            assert_eq!((map.width, map.height), (*width, *height));
+            assert_eq!(map.tiles.len(), (width * height) as usize);
+            assert_fully_connected(map, &start);
+            assert!(dungeon::find_tile(map, TileType::DownStairs).is_some());
+            for x in 0 .. map.width {
+                assert!(map.tiles[map.xy_idx(x, map.height - 1)] == TileType::Wall);
+            }
+            for y in 0 .. map.height {
+                assert!(map.tiles[map.xy_idx(map.width - 1, y)] == TileType::Wall);
+            }
+        }
+    }
+}
+
+#[test]
+fn deeper_levels_are_bigger_and_keep_their_size_when_revisited() {
+    let map_size = |game: &HeadlessGame| {
+        let map = game.gs.ecs.fetch::<Map>();
+        (map.width, map.height)
+    };
+    let mut game = HeadlessGame::new(7);
+    let mut sizes = vec![map_size(&game)];
+    for _ in 0 .. 3 {
+        game.gs.advance(RunState::NextLevel);
+        sizes.push(map_size(&game));
+    }
+    assert!(sizes[3].0 > sizes[0].0 && sizes[3].1 > sizes[0].1);
+
+    for depth in (0 .. 3).rev() {
+        game.gs.advance(RunState::PreviousLevel);
+        game.gs.ecs.insert(RunState::PreRun);
+        game.gs.run_systems();
+        assert_eq!(map_size(&game), sizes[depth]);
+        let map = game.gs.ecs.fetch::<Map>();
+        let pos = *game.gs.ecs.fetch::<Point>();
+        assert_eq!(map.tiles.len(), (map.width * map.height) as usize);
+        assert!(map.tiles[map.xy_idx(pos.x, pos.y)] == TileType::DownStairs);
+    }
+}
+
+#[test]
+fn terrain_sets_walkability_sight_and_cost() {
+    let mut map = Map::new(1, 10, 10);
+    for (tile, x) in [(TileType::Floor, 2), (TileType::DeepWater, 3), (TileType::Road, 4), (TileType::ShallowWater, 5), (TileType::Wall, 6)].iter() {
+        for y in 1 .. 9 {
+            let idx = map.xy_idx(*x, y);
+            map.tiles[idx] = *tile;
+        }
+    }
+    map.populate_blocked();
+
+    let deep_water = map.xy_idx(3, 5);
+    assert!(map.blocked[deep_water]);
+    assert!(!map.is_opaque(deep_water as i32));
+    assert!(map.is_opaque(map.xy_idx(6, 5) as i32));
+    assert!(!map.blocked[map.xy_idx(5, 5)]);
+
+    let exits = map.get_available_exits(map.xy_idx(4, 5) as i32);
+    let cost_to = |x: i32, y: i32| exits.iter().find(|(idx, _cost)| *idx == map.xy_idx(x, y) as i32).map(|(_idx, cost)| *cost);
+    assert_eq!(cost_to(3, 5), None);
+    assert!(cost_to(4, 4).unwrap() < 1.0);
+    assert!(cost_to(5, 5).unwrap() > 1.0);
+}
+
+/// An empty room the size of the map, to stamp prefabs onto
+struct OpenMap {}
+
+impl map_builders::InitialMapBuilder for OpenMap {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut map_builders::BuilderMap) {
+        let map = &mut build_data.map;
+        for y in 1 .. map.height - 1 {
+            for x in 1 .. map.width - 1 {
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+}
+
+fn assert_spawns_exist(spawn_list: &[(usize, String)]) {
+    raws::load_raws();
+    let mut gs = test_state();
+    for (_idx, name) in spawn_list.iter() {
+        let spawned = raws::spawn_named_entity(&raws::RAWS.lock().unwrap(), &mut gs.ecs, name, raws::SpawnType::AtPosition { x: 1, y: 1 });
+        assert!(spawned.is_some(), "{} is not in the raw files", name);
+    }
+}
+
+#[test]
+fn prefabs_too_big_for_the_map_are_never_stamped_off_it() {
+    // the warren is 58 wide; only its corner is laid out, with the start
+    let mut chain = map_builders::BuilderChain::new(2, 40, 20);
+    chain.start_with(map_builders::PrefabLevelBuilder::new(map_builders::prefab_builder::prefab_levels::GOBLIN_WARREN));
+    chain.build_map(&mut RandomNumberGenerator::seeded(1));
+    assert!(chain.build_data.starting_position.is_some());
+    assert!(!map_builders::PrefabLevelBuilder::fits(&map_builders::prefab_builder::prefab_levels::GOBLIN_WARREN, 40, 20));
+
+    let mut chain = map_builders::BuilderChain::new(2, 15, 12);
+    chain.start_with(map_builders::SimpleMapBuilder::new());
+    chain.with(map_builders::PrefabSectionBuilder::new(map_builders::prefab_builder::prefab_sections::ORC_FORT));
+    chain.with(map_builders::RoomBasedStartingPosition::new());
+    chain.build_map(&mut RandomNumberGenerator::seeded(1));
+    assert!(chain.build_data.spawn_list.is_empty());
+}
+
+#[test]
+fn every_prefab_glyph_means_something() {
+    let mut templates = vec![
+        map_builders::prefab_builder::prefab_levels::GOBLIN_WARREN.template,
+        map_builders::prefab_builder::prefab_sections::ORC_FORT.template,
+    ];
+    templates.extend(map_builders::prefab_builder::prefab_rooms::ALL_VAULTS.iter().map(|vault| vault.template));
+    for template in templates.iter() {
+        assert!(map_builders::prefab_builder::unknown_glyphs(template).is_empty(), "unknown glyphs in {}", template);
+    }
+}
+
+#[test]
+fn the_prefab_level_is_playable() {
+    let mut chain = map_builders::BuilderChain::new(2, 80, 50);
+    chain.start_with(map_builders::PrefabLevelBuilder::new(map_builders::prefab_builder::prefab_levels::GOBLIN_WARREN));
+    let mut rng = RandomNumberGenerator::seeded(1);
+    chain.build_map(&mut rng);
+
+    let start = chain.build_data.starting_position.clone().unwrap();
+    assert_fully_connected(&chain.build_data.map, &start);
+    assert!(chain.build_data.map.tiles[chain.build_data.map.xy_idx(start.x, start.y)] == TileType::UpStairs);
+    assert!(dungeon::find_tile(&chain.build_data.map, TileType::DownStairs).is_some());
+    assert!(!chain.build_data.spawn_list.is_empty());
+    assert_spawns_exist(&chain.build_data.spawn_list);
+}
+
+#[test]
+fn prefab_sections_and_vaults_stay_reachable() {
+    for seed in 0 .. 5 {
+        let mut chain = map_builders::BuilderChain::new(5, 80, 50);
+        chain.start_with(Box::new(OpenMap {}));
+        chain.with(map_builders::PrefabSectionBuilder::new(map_builders::prefab_builder::prefab_sections::ORC_FORT));
+        chain.with(map_builders::AreaStartingPosition::new(map_builders::XStart::Left, map_builders::YStart::Center));
+        chain.with(map_builders::VaultBuilder::new());
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        chain.build_map(&mut rng);
+        let stamped = chain.build_data.spawn_list.clone();
+        let start = chain.build_data.starting_position.clone().unwrap();
+
+        let mut cull = map_builders::CullUnreachable::new();
+        map_builders::MetaMapBuilder::build_map(cull.as_mut(), &mut rng, &mut chain.build_data);
+        assert_fully_connected(&chain.build_data.map, &start);
+        assert!(chain.build_data.spawn_list == stamped, "part of a prefab was culled");
+        assert!(stamped.len() > 12);
+        assert_spawns_exist(&stamped);
+    }
+}
+
+#[test]
+fn map_generation_history_is_only_kept_when_asked_for() {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let mut quiet = map_builders::random_builder(2, &mut rng, 80, 50);
+    quiet.build_map(&mut rng);
+    assert!(quiet.build_data.history.is_empty());
+
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let mut recorded = map_builders::random_builder(2, &mut rng, 80, 50);
+    recorded.build_data.record_history = true;
+    recorded.build_map(&mut rng);
+    let history = &recorded.build_data.history;
+    assert!(history.len() > 2);
+    assert!(history.last().unwrap().tiles == recorded.build_data.map.tiles);
+    assert!(history.windows(2).all(|pair| pair[0].tiles != pair[1].tiles));
+
+    // recording doesn't change what gets built
+    assert!(quiet.build_data.map.tiles == recorded.build_data.map.tiles);
+}
+
+#[test]
+fn doors_are_hung_where_corridors_meet_rooms() {
+    let mut hung = 0;
+    for seed in 0 .. 5 {
+        let metas : Vec<Box<dyn map_builders::MetaMapBuilder>> = vec![map_builders::DoglegCorridors::new(), map_builders::DoorPlacement::new()];
+        let mut chain = room_chain(map_builders::SimpleMapBuilder::new(), metas);
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        chain.build_map(&mut rng);
+
+        let map = &chain.build_data.map;
+        for (idx, _name) in chain.build_data.spawn_list.iter().filter(|(_idx, name)| name == "Door") {
+            hung += 1;
+            let (x, y) = (*idx as i32 % map.width, *idx as i32 / map.width);
+            let walls = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                .filter(|(dx, dy)| map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall)
+                .count();
+            assert!(map.tiles[*idx] == TileType::Floor);
+            assert_eq!(walls, 2);
+        }
+        assert_spawns_exist(&chain.build_data.spawn_list);
+    }
+    assert!(hung > 0);
+}
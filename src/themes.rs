@@ -0,0 +1,124 @@
+extern crate rltk;
+use rltk::{ RGB };
+
+use super::{ Map, TileType };
+
+/// What sort of place a level is, which decides how it is drawn and
+/// what terrain the map builders dress it in
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Theme {
+    Dungeon,
+    Forest,
+    LimestoneCavern,
+}
+
+/// The theme for a level at the given depth
+pub fn theme_for_depth(depth: i32) -> Theme {
+    match depth {
+        3 | 4 => Theme::Forest,
+        5 | 6 => Theme::LimestoneCavern,
+        _ => Theme::Dungeon
+    }
+}
+
+/// The glyph and colours a revealed tile is drawn with. How the level
+/// looks depends on how deep it is.
+pub fn tile_glyph(idx: usize, map: &Map) -> (u8, RGB, RGB) {
+    let (glyph, mut fg, mut bg) = match theme_for_depth(map.depth) {
+        Theme::Forest => get_forest_glyph(idx, map),
+        Theme::LimestoneCavern => get_limestone_cavern_glyph(idx, map),
+        Theme::Dungeon => get_tile_glyph_default(idx, map)
+    };
+
+    if map.bloodstains.contains(&idx) {
+        bg = RGB::from_f32(0.75, 0.0, 0.0);
+    }
+
+    if !map.visible[idx] {
+        fg = fg.to_greyscale();
+        bg = RGB::from_f32(0.0, 0.0, 0.0);
+    }
+
+    (glyph, fg, bg)
+}
+
+fn get_tile_glyph_default(idx: usize, map: &Map) -> (u8, RGB, RGB) {
+    let glyph;
+    let fg;
+    let bg = RGB::from_f32(0.0, 0.0, 0.0);
+    let x = idx as i32 % map.width;
+    let y = idx as i32 / map.width;
+
+    match map.tiles[idx] {
+        TileType::Floor => { glyph = rltk::to_cp437('.'); fg = RGB::from_f32(0.0, 0.5, 0.5); }
+        TileType::WoodFloor => { glyph = rltk::to_cp437('░'); fg = RGB::named(rltk::CHOCOLATE); }
+        TileType::Wall => { glyph = wall_glyph(map, x, y); fg = RGB::from_f32(0.6, 0.6, 0.0); }
+        TileType::DownStairs => { glyph = rltk::to_cp437('>'); fg = RGB::from_f32(0.0, 1.0, 1.0); }
+        TileType::UpStairs => { glyph = rltk::to_cp437('<'); fg = RGB::from_f32(0.0, 1.0, 1.0); }
+        TileType::Bridge => { glyph = rltk::to_cp437('='); fg = RGB::named(rltk::CHOCOLATE); }
+        TileType::Road => { glyph = rltk::to_cp437('≡'); fg = RGB::named(rltk::GRAY); }
+        TileType::Grass => { glyph = rltk::to_cp437('"'); fg = RGB::named(rltk::GREEN); }
+        TileType::ShallowWater => { glyph = rltk::to_cp437('~'); fg = RGB::named(rltk::CYAN); }
+        TileType::DeepWater => { glyph = rltk::to_cp437('~'); fg = RGB::named(rltk::BLUE); }
+        TileType::Gravel => { glyph = rltk::to_cp437(';'); fg = RGB::named(rltk::GRAY); }
+    }
+
+    (glyph, fg, bg)
+}
+
+/// The walls are trees and the floor is a woodland path
+fn get_forest_glyph(idx: usize, map: &Map) -> (u8, RGB, RGB) {
+    let bg = RGB::from_f32(0.0, 0.0, 0.0);
+    match map.tiles[idx] {
+        TileType::Wall => (rltk::to_cp437('♣'), RGB::from_f32(0.0, 0.6, 0.0), bg),
+        TileType::Floor => (rltk::to_cp437('.'), RGB::from_f32(0.4, 0.6, 0.2), bg),
+        _ => get_tile_glyph_default(idx, map)
+    }
+}
+
+/// Pale, worn rock
+fn get_limestone_cavern_glyph(idx: usize, map: &Map) -> (u8, RGB, RGB) {
+    let bg = RGB::from_f32(0.0, 0.0, 0.0);
+    match map.tiles[idx] {
+        TileType::Wall => (rltk::to_cp437('▒'), RGB::from_f32(0.7, 0.7, 0.6), bg),
+        TileType::Floor => (rltk::to_cp437('░'), RGB::from_f32(0.4, 0.4, 0.35), bg),
+        _ => get_tile_glyph_default(idx, map)
+    }
+}
+
+fn wall_glyph(map: &Map, x: i32, y: i32) -> u8 {
+    let mut mask: u8 = 0;
+
+    if is_revealed_and_wall(map, x, y-1) { mask += 1; }
+    if is_revealed_and_wall(map, x, y+1) { mask += 2; }
+    if is_revealed_and_wall(map, x-1, y) { mask += 4; }
+    if is_revealed_and_wall(map, x+1, y) { mask += 8; }
+    mask_to_glyph(mask)
+}
+
+fn mask_to_glyph(mask: u8) -> u8 {
+    match mask {
+        0 => 9,
+        1 => 186,
+        2 => 186,
+        3 => 186,
+        4 => 205,
+        5 => 188,
+        6 => 187,
+        7 => 185,
+        8 => 205,
+        9 => 200,
+        10 => 201,
+        11 => 204,
+        12 => 205,
+        13 => 202,
+        14 => 203,
+        _ => 35
+    }
+}
+
+fn is_revealed_and_wall(map: &Map, x: i32, y: i32) -> bool {
+    if x < 0 || x > map.width - 1 || y < 0 || y > map.height - 1 { return false; }
+    let idx = map.xy_idx(x, y);
+    map.tiles[idx] == TileType::Wall && map.revealed[idx]
+}
@@ -1,6 +1,6 @@
 extern crate specs;
 use specs::prelude::*;
-use super::{Viewshed, Position, Map, Player};
+use super::{Viewshed, Position, Map, Player, BlocksVisibility, StatusEffect, StatusKind, status_system::has_status};
 use rltk::{field_of_view, Point};
 
 pub struct VisibilitySystem {}
@@ -10,22 +10,33 @@ impl<'a> System<'a> for VisibilitySystem {
                         Entities<'a>,
                         WriteStorage<'a, Viewshed>,
                         WriteStorage<'a, Position>,
-                        ReadStorage<'a, Player> );
+                        ReadStorage<'a, Player>,
+                        ReadStorage<'a, BlocksVisibility>,
+                        ReadStorage<'a, StatusEffect> );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, blocks_visibility, statuses) = data;
+
+        map.view_blocked.clear();
+        for (_block, pos) in (&blocks_visibility, &pos).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.view_blocked.insert(idx);
+        }
+
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
                 viewshed.dirty = false;
                 viewshed.tiles.clear();
-                viewshed.tiles = field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
+                // the blind can only feel their way around
+                let range = if has_status(&statuses, ent, StatusKind::Blindness) { 1 } else { viewshed.range };
+                viewshed.tiles = field_of_view(Point::new(pos.x, pos.y), range, &*map);
                 viewshed.tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height);
                
                 let _p : Option<&Player> = player.get(ent);
                 if let Some(_p) = _p {
                     for t in map.visible.iter_mut() { *t = false; }
                     for vis in viewshed.tiles.iter() {
-                        let idx = Map::xy_idx(vis.x, vis.y);
+                        let idx = map.xy_idx(vis.x, vis.y);
                         map.revealed[idx] = true;
                         map.visible[idx] = true;
                     }